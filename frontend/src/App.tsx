@@ -260,28 +260,14 @@ function App() {
       setUploadConflict(state);
     });
 
-  const buildCopyPath = (path: string) => {
-    const parts = path.split("/");
-    const name = parts.pop() ?? path;
-    const dot = name.lastIndexOf(".");
-    const base = dot > 0 ? name.slice(0, dot) : name;
-    const ext = dot > 0 ? name.slice(dot) : "";
-    const timestamp = new Date()
-      .toISOString()
-      .replace(/[-:]/g, "")
-      .replace("T", "-")
-      .slice(0, 15);
-    const copyName = `${base} (copy ${timestamp})${ext}`;
-    return [...parts, copyName].filter(Boolean).join("/");
-  };
-
   const completeUpload = async (
     uploadId: string,
     headers?: Record<string, string>,
+    onConflict?: "rename",
   ) => {
-    return axios.post(
+    return axios.post<{ path?: string }>(
       `${UPLOAD_API}/complete`,
-      { uploadId },
+      { uploadId, onConflict },
       headers ? { headers } : undefined,
     );
   };
@@ -296,6 +282,7 @@ function App() {
       {
         name: targetPath,
         totalSize: file.size,
+        lastModified: file.lastModified,
       },
     );
     const uploadId = initResponse.data.uploadId;
@@ -412,10 +399,13 @@ function App() {
         headers = { "If-None-Match": "*" };
       }
 
+      // “另存为新文件”时由服务端在持有路径锁的情况下选择空闲名称。
+      let onConflict: "rename" | undefined;
       while (true) {
         try {
-          const response = await completeUpload(uploadId, headers);
-          if (shouldUpdateList(targetPath)) {
+          const response = await completeUpload(uploadId, headers, onConflict);
+          const finalPath = response.data.path ?? targetPath;
+          if (shouldUpdateList(finalPath)) {
             const headerValue = response.headers["last-modified"] as
               | string
               | undefined;
@@ -423,8 +413,8 @@ function App() {
               ? formatUtcTimestamp(new Date(headerValue))
               : formatUtcTimestamp(new Date());
             upsertEntry({
-              name: file.name,
-              path: targetPath,
+              name: finalPath.split("/").pop() ?? file.name,
+              path: finalPath,
               is_dir: false,
               size: file.size,
               modified,
@@ -460,12 +450,9 @@ function App() {
               continue;
             }
             if (action === "saveAs") {
-              const newPath = buildCopyPath(targetPath);
-              await abortUpload(uploadId);
-              uploadIdsRef.current.delete(uploadId);
-              await uploadFileInChunks(file, newPath, onChunk);
-              completed = true;
-              break;
+              headers = undefined;
+              onConflict = "rename";
+              continue;
             }
             await abortUpload(uploadId);
             uploadIdsRef.current.delete(uploadId);
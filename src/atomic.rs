@@ -1,96 +1,267 @@
 //! 临时写入与原子替换的辅助方法。
 
+use std::ffi::{OsStr, OsString};
 use std::io;
-use std::path::{Path, PathBuf};
-use tokio::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::beneath::DirHandle;
+use crate::content::ContentWriter;
 use crate::error::ApiError;
+use crate::storage::{ResolvedPath, Storage, StorageError};
+use crate::volumes::Volume;
+
+/// 临时文件名中位于目标名与 UUID 之间的标记：`.{name}.tmp.{uuid}`。
+const TEMP_MARKER: &str = ".tmp.";
+
+/// 判断名称是否为原子写入使用的临时文件名。
+pub fn is_temp_name(name: &OsStr) -> bool {
+    let Some(name) = name.to_str() else {
+        return false;
+    };
+    match name.rsplit_once(TEMP_MARKER) {
+        Some((base, uuid)) => {
+            base.len() > 1 && base.starts_with('.') && Uuid::try_parse(uuid).is_ok()
+        }
+        None => false,
+    }
+}
+
+/// 删除存储中修改时间早于 `max_age` 的原子写入临时文件（崩溃或中断遗留），
+/// 返回删除的数量。遍历不跟随符号链接，并跳过根目录下的上传临时目录。
+pub async fn sweep_stale_temp_files(storage: &Storage, max_age: Duration) -> io::Result<u64> {
+    let now = SystemTime::now();
+    let mut removed = 0;
+    let mut pending = vec![DirHandle::open_root(storage.root_path()).await?];
+    while let Some(dir) = pending.pop() {
+        let at_root = dir.path() == storage.root_path();
+        for name in dir.read_dir().await? {
+            let Ok(metadata) = dir.metadata(Some(&name)).await else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if at_root && storage.internal_dir_name() == Some(name.as_os_str()) {
+                    continue;
+                }
+                if let Ok(child) = dir.open_dir(Path::new(&name), false).await {
+                    pending.push(child);
+                }
+                continue;
+            }
+            if !metadata.is_file() || !is_temp_name(&name) {
+                continue;
+            }
+            let stale = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age >= max_age);
+            if !stale {
+                continue;
+            }
+            let path = dir.path().join(&name);
+            match dir.remove_file(&name).await {
+                Ok(()) => {
+                    info!(path = ?path, "removed stale atomic temp file");
+                    removed += 1;
+                }
+                Err(err) => warn!(path = ?path, error = %err, "failed to remove atomic temp file"),
+            }
+        }
+    }
+    Ok(removed)
+}
 
 /// 可用于原子替换的临时文件封装。
+///
+/// 临时文件的创建、重命名与清理都相对于目标所在目录的句柄进行。
 pub struct AtomicFile {
-    target: PathBuf,
-    temp_path: PathBuf,
-    file: File,
+    dir: DirHandle,
+    target_name: OsString,
+    temp_name: OsString,
+    writer: ContentWriter,
+    volume: Option<Arc<Volume>>,
+    modified: Option<SystemTime>,
 }
 
 impl AtomicFile {
-    /// 在目标路径同目录创建临时文件。
-    pub async fn new(target: &Path) -> Result<Self, ApiError> {
-        let parent = target
-            .parent()
-            .ok_or_else(|| ApiError::BadRequest("invalid target path".into()))?;
-        let base = target
-            .file_name()
-            .map(|name| name.to_string_lossy())
-            .unwrap_or_else(|| "file".into());
-        let temp_name = format!(".{base}.tmp.{}", Uuid::new_v4());
-        let temp_path = parent.join(temp_name);
-        let file = File::create(&temp_path)
+    /// 在目标同目录创建临时文件，写入内容按存储配置压缩与加密。
+    pub async fn new(storage: &Storage, target: &ResolvedPath) -> Result<Self, ApiError> {
+        let target_name = target
+            .name()
+            .ok_or_else(|| ApiError::BadRequest("invalid target path".into()))?
+            .to_os_string();
+        // `show` 策略下展示的符号链接不可被写入替换。
+        if let Ok(metadata) = target.metadata().await
+            && metadata.file_type().is_symlink()
+        {
+            return Err(StorageError::InvalidPath.into());
+        }
+        let temp_name = OsString::from(format!(
+            ".{}{TEMP_MARKER}{}",
+            target_name.to_string_lossy(),
+            Uuid::new_v4()
+        ));
+        let file = target
+            .dir()
+            .create_new_file(&temp_name)
             .await
             .map_err(|err| ApiError::Internal(err.to_string()))?;
         Ok(Self {
-            target: target.to_path_buf(),
-            temp_path,
-            file,
+            dir: target.dir().clone(),
+            target_name,
+            temp_name,
+            writer: ContentWriter::new(
+                file,
+                storage.keyring(),
+                storage.compression_level(target.path()),
+            ),
+            volume: None,
+            modified: None,
         })
     }
 
-    /// 返回临时文件的可写句柄。
-    pub fn file_mut(&mut self) -> &mut File {
-        &mut self.file
+    /// 替换目标前按所属卷的容量配额校验写入结果。
+    pub fn with_volume(mut self, volume: &Arc<Volume>) -> Self {
+        self.volume = Some(volume.clone());
+        self
+    }
+
+    /// 替换目标前将临时文件的修改时间设为指定值。
+    pub fn with_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// 返回临时文件的内容写入器。
+    pub fn writer_mut(&mut self) -> &mut ContentWriter {
+        &mut self.writer
     }
 
     /// 放弃并清理临时文件。
     pub async fn cleanup(self) {
-        let _ = fs::remove_file(&self.temp_path).await;
+        let _ = self.dir.remove_file(&self.temp_name).await;
     }
 
     /// 同步并原子替换目标文件。
     pub async fn finalize(self) -> Result<(), ApiError> {
-        self.file
-            .sync_all()
+        let file = match self.writer.finish().await {
+            Ok(file) => file,
+            Err(err) => {
+                let _ = self.dir.remove_file(&self.temp_name).await;
+                return Err(ApiError::Internal(err.to_string()));
+            }
+        };
+        if let Some(modified) = self.modified {
+            let std_file = file.into_std().await;
+            let result = tokio::task::spawn_blocking(move || {
+                std_file.set_modified(modified)?;
+                std_file.sync_all()
+            })
             .await
             .map_err(|err| ApiError::Internal(err.to_string()))?;
-        drop(self.file);
+            if let Err(err) = result {
+                let _ = self.dir.remove_file(&self.temp_name).await;
+                return Err(ApiError::Internal(err.to_string()));
+            }
+        } else {
+            file.sync_all()
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+            drop(file);
+        }
 
-        if let Some(parent) = self.target.parent() {
-            let _ = sync_dir(parent).await;
+        if let Some(volume) = &self.volume {
+            // 临时文件不计入卷占用，替换时计入其大小并释放被替换的旧文件。
+            let size = |metadata: io::Result<std::fs::Metadata>| {
+                metadata
+                    .map(|metadata| {
+                        if metadata.is_file() {
+                            metadata.len()
+                        } else {
+                            0
+                        }
+                    })
+                    .unwrap_or(0)
+            };
+            let added = size(self.dir.metadata(Some(&self.temp_name)).await);
+            let released = size(self.dir.metadata(Some(&self.target_name)).await);
+            if let Err(err) = volume.charge_quota(added, released).await {
+                let _ = self.dir.remove_file(&self.temp_name).await;
+                return Err(err.into());
+            }
         }
 
-        if let Err(err) = fs::rename(&self.temp_path, &self.target).await {
+        let _ = self.dir.sync().await;
+
+        if let Err(err) = self.dir.rename(&self.temp_name, &self.target_name).await {
             #[cfg(windows)]
             {
-                if fs::remove_file(&self.target).await.is_ok() {
-                    fs::rename(&self.temp_path, &self.target)
+                if self.dir.remove_file(&self.target_name).await.is_ok() {
+                    self.dir
+                        .rename(&self.temp_name, &self.target_name)
                         .await
                         .map_err(|err| ApiError::Internal(err.to_string()))?;
                 } else {
-                    let _ = fs::remove_file(&self.temp_path).await;
+                    let _ = self.dir.remove_file(&self.temp_name).await;
                     return Err(ApiError::Internal(err.to_string()));
                 }
             }
             #[cfg(not(windows))]
             {
-                let _ = fs::remove_file(&self.temp_path).await;
+                let _ = self.dir.remove_file(&self.temp_name).await;
                 return Err(ApiError::Internal(err.to_string()));
             }
         }
 
-        if let Some(parent) = self.target.parent() {
-            let _ = sync_dir(parent).await;
-        }
+        let _ = self.dir.sync().await;
 
         Ok(())
     }
 }
 
-async fn sync_dir(path: &Path) -> io::Result<()> {
-    let path = path.to_path_buf();
-    tokio::task::spawn_blocking(move || {
-        let dir = std::fs::File::open(path)?;
-        dir.sync_all()
-    })
-    .await
-    .map_err(|err| io::Error::other(err.to_string()))?
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hidden::HiddenNames;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn temp_files_are_hidden_and_stale_ones_swept() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("root");
+        let docs = root.join("docs");
+        std::fs::create_dir_all(&docs).expect("create docs");
+        let stale = format!(".a.txt.tmp.{}", Uuid::new_v4());
+        let fresh = format!(".b.txt.tmp.{}", Uuid::new_v4());
+        for name in ["a.txt", ".DS_Store", ".c.tmp.draft", &stale, &fresh] {
+            std::fs::write(docs.join(name), b"x").expect("write file");
+        }
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 60 * 60);
+        std::fs::File::options()
+            .write(true)
+            .open(docs.join(&stale))
+            .and_then(|file| file.set_modified(two_hours_ago))
+            .expect("age temp file");
+
+        let storage = Storage::new(root).with_hidden(HiddenNames::from_patterns(".DS_Store"));
+        let names: Vec<String> = storage
+            .list_dir(Some("docs"))
+            .await
+            .unwrap_or_else(|_| panic!("list"))
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, vec![".c.tmp.draft", "a.txt"]);
+
+        let removed = sweep_stale_temp_files(&storage, Duration::from_secs(60 * 60))
+            .await
+            .expect("sweep");
+        assert_eq!(removed, 1);
+        assert!(!docs.join(&stale).exists());
+        assert!(docs.join(&fresh).exists());
+    }
 }
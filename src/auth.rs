@@ -184,7 +184,7 @@ async fn remove_session(auth: &AuthConfig, token: &str) {
     sessions.remove(token);
 }
 
-async fn check_login_rate_limit(auth: &AuthConfig, ip: IpAddr) -> Option<u64> {
+pub(crate) async fn check_login_rate_limit(auth: &AuthConfig, ip: IpAddr) -> Option<u64> {
     if auth.login_max_attempts == 0 {
         return None;
     }
@@ -214,7 +214,7 @@ async fn check_login_rate_limit(auth: &AuthConfig, ip: IpAddr) -> Option<u64> {
     None
 }
 
-async fn register_login_failure(auth: &AuthConfig, ip: IpAddr) {
+pub(crate) async fn register_login_failure(auth: &AuthConfig, ip: IpAddr) {
     if auth.login_max_attempts == 0 {
         return;
     }
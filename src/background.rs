@@ -1,19 +1,29 @@
-//! 会话清理与上传临时目录清理的后台任务。
+//! 会话清理、上传临时目录清理、原子写入临时文件清理、缩略图与元数据缓存清理及失效链接清理的后台任务。
 
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::warn;
+use tracing::{info, warn};
 
+use crate::atomic::sweep_stale_temp_files;
 use crate::auth::{AuthConfig, prune_expired_sessions, prune_login_attempts};
-use crate::config::{SESSION_PRUNE_INTERVAL_SECS, UPLOAD_CLEAN_INTERVAL_SECS};
-use crate::storage::Storage;
+use crate::config::{
+    LINK_PRUNE_INTERVAL_SECS, SESSION_PRUNE_INTERVAL_SECS, STALE_ATOMIC_TEMP_SECS,
+    UPLOAD_CLEAN_INTERVAL_SECS,
+};
+use crate::links::{LinkStore, prune_links};
+use crate::metadata::MetadataExtractor;
+use crate::thumbnail::Thumbnailer;
 use crate::upload::{UploadConfig, cleanup_upload_temp};
+use crate::volumes::Volumes;
 
-/// 启动后台任务（会话清理与上传临时目录清理）。
+/// 启动后台任务（会话清理、临时文件清理与失效链接清理，启动时立即执行一次）。
 pub fn spawn_background_tasks(
-    storage: Arc<Storage>,
+    volumes: Arc<Volumes>,
     auth: Arc<AuthConfig>,
     upload: Arc<UploadConfig>,
+    thumbnailer: Arc<Thumbnailer>,
+    metadata: Arc<MetadataExtractor>,
+    links: Arc<LinkStore>,
 ) {
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(SESSION_PRUNE_INTERVAL_SECS));
@@ -24,12 +34,47 @@ pub fn spawn_background_tasks(
         }
     });
 
+    let volumes_for_links = volumes.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(LINK_PRUNE_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match prune_links(&volumes_for_links, &links).await {
+                Ok(0) => {}
+                Ok(removed) => info!(removed, "pruned expired links"),
+                Err(err) => warn!(error = %err, "link prune failed"),
+            }
+        }
+    });
+
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(UPLOAD_CLEAN_INTERVAL_SECS));
         loop {
             interval.tick().await;
-            if let Err(err) = cleanup_upload_temp(&storage, &upload).await {
-                warn!(error = %err, "upload temp cleanup failed");
+            for volume in volumes.iter() {
+                if let Err(err) = cleanup_upload_temp(volume.storage(), &upload).await {
+                    warn!(volume = volume.name(), error = %err, "upload temp cleanup failed");
+                }
+                let max_age = Duration::from_secs(STALE_ATOMIC_TEMP_SECS);
+                match sweep_stale_temp_files(volume.storage(), max_age).await {
+                    Ok(0) => {}
+                    Ok(removed) => {
+                        info!(volume = volume.name(), removed, "swept atomic temp files")
+                    }
+                    Err(err) => {
+                        warn!(volume = volume.name(), error = %err, "atomic temp sweep failed")
+                    }
+                }
+            }
+            match thumbnailer.prune_cache().await {
+                Ok(0) => {}
+                Ok(removed) => info!(removed, "pruned thumbnail cache"),
+                Err(err) => warn!(error = %err, "thumbnail cache prune failed"),
+            }
+            match metadata.prune_cache().await {
+                Ok(0) => {}
+                Ok(removed) => info!(removed, "pruned metadata cache"),
+                Err(err) => warn!(error = %err, "metadata cache prune failed"),
             }
         }
     });
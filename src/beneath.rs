@@ -0,0 +1,635 @@
+//! 基于目录句柄的路径操作：从已打开的根目录出发逐级打开路径。
+//!
+//! Unix 平台上每一级都以 `openat` + `O_NOFOLLOW` 相对于上一级句柄打开，
+//! 后续的读写、重命名与删除同样相对于目录句柄进行，因此检查之后被替换
+//! 进来的符号链接无法让操作逃逸出根目录。其他平台退化为基于路径的
+//! 逐级符号链接检查。
+
+use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fs::Metadata;
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use tokio::fs::File;
+
+#[cfg(unix)]
+use rustix::fs::{AtFlags, Dir, Mode, OFlags};
+#[cfg(unix)]
+use rustix::io::Errno;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::sync::Arc;
+
+/// 逐级打开目录时使用的标志：只接受真实目录，不跟随符号链接。
+#[cfg(unix)]
+const DIR_FLAGS: OFlags = OFlags::RDONLY
+    .union(OFlags::DIRECTORY)
+    .union(OFlags::NOFOLLOW)
+    .union(OFlags::CLOEXEC);
+
+/// 读取目录项元数据时使用的标志：`O_PATH` 可对符号链接本身及不可读文件取元数据。
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const META_FLAGS: OFlags = OFlags::PATH.union(OFlags::NOFOLLOW).union(OFlags::CLOEXEC);
+
+/// 跟随符号链接解析时允许的最大跳转次数。
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// 以写入方式打开目录中文件时的选项。
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WriteOptions {
+    pub append: bool,
+    pub truncate: bool,
+    pub create: bool,
+    pub create_new: bool,
+}
+
+/// 已打开目录的句柄，所有操作都相对于该目录进行。
+#[derive(Clone, Debug)]
+pub struct DirHandle {
+    #[cfg(unix)]
+    fd: Arc<OwnedFd>,
+    path: PathBuf,
+}
+
+impl DirHandle {
+    /// 打开根目录（根目录本身由配置决定，允许为符号链接）。
+    pub async fn open_root(root: &Path) -> io::Result<Self> {
+        let path = root.to_path_buf();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                let fd = rustix::fs::open(
+                    &path,
+                    OFlags::RDONLY | OFlags::DIRECTORY | OFlags::CLOEXEC,
+                    Mode::empty(),
+                )?;
+                Ok(Self {
+                    fd: Arc::new(fd),
+                    path,
+                })
+            }
+            #[cfg(not(unix))]
+            {
+                if !std::fs::metadata(&path)?.is_dir() {
+                    return Err(io::Error::new(ErrorKind::NotADirectory, "not a directory"));
+                }
+                Ok(Self { path })
+            }
+        })
+        .await
+    }
+
+    /// 返回句柄对应的路径，仅用于日志与配置匹配，不应用于文件操作。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 逐级打开子目录（不跟随符号链接），`create` 为真时创建缺失的目录。
+    pub async fn open_dir(&self, relative: &Path, create: bool) -> io::Result<Self> {
+        let handle = self.clone();
+        let relative = relative.to_path_buf();
+        blocking(move || {
+            let mut current = handle;
+            for component in relative.components() {
+                let Component::Normal(name) = component else {
+                    return Err(escape_error());
+                };
+                current = current.open_child_dir(name, create)?;
+            }
+            Ok(current)
+        })
+        .await
+    }
+
+    /// 以当前句柄为边界解析相对路径，返回末端所在目录句柄与末端名称
+    /// （路径指向边界目录自身时名称为 None）。
+    ///
+    /// `follow` 为假时路径中的符号链接一律拒绝；为真时跟随符号链接（包括
+    /// 末端），但解析结果不得越出当前句柄，绝对路径目标需位于其路径之下。
+    pub async fn resolve(
+        &self,
+        relative: &Path,
+        create: bool,
+        follow: bool,
+    ) -> io::Result<(Self, Option<OsString>)> {
+        let handle = self.clone();
+        let relative = relative.to_path_buf();
+        blocking(move || {
+            let mut pending = VecDeque::new();
+            for component in relative.components() {
+                let Component::Normal(name) = component else {
+                    return Err(escape_error());
+                };
+                pending.push_back(name.to_os_string());
+            }
+            let mut stack = vec![handle];
+            let mut hops = 0;
+            while let Some(name) = pending.pop_front() {
+                if name == ".." {
+                    if stack.len() == 1 {
+                        return Err(escape_error());
+                    }
+                    stack.pop();
+                    continue;
+                }
+                let current = stack.last().cloned().ok_or_else(escape_error)?;
+                let target = if follow {
+                    current.symlink_target(&name)?
+                } else {
+                    None
+                };
+                if let Some(target) = target {
+                    hops += 1;
+                    if hops > MAX_SYMLINK_HOPS {
+                        return Err(escape_error());
+                    }
+                    let relative_target = if target.is_absolute() {
+                        stack.truncate(1);
+                        strip_root(&stack[0].path, &target)?
+                    } else {
+                        target
+                    };
+                    for component in relative_target.components().rev() {
+                        match component {
+                            Component::Normal(part) => pending.push_front(part.to_os_string()),
+                            Component::ParentDir => pending.push_front("..".into()),
+                            Component::CurDir => {}
+                            Component::RootDir | Component::Prefix(_) => {
+                                return Err(escape_error());
+                            }
+                        }
+                    }
+                    continue;
+                }
+                if pending.is_empty() {
+                    return Ok((current, Some(name)));
+                }
+                stack.push(current.open_child_dir(&name, create)?);
+            }
+            let dir = stack.pop().ok_or_else(escape_error)?;
+            Ok((dir, None))
+        })
+        .await
+    }
+
+    /// 读取目录项（`None` 表示目录自身）的元数据，不跟随符号链接。
+    pub async fn metadata(&self, name: Option<&OsStr>) -> io::Result<Metadata> {
+        let handle = self.clone();
+        let name = name.map(OsStr::to_os_string);
+        blocking(move || {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            {
+                let fd = match name {
+                    Some(name) => rustix::fs::openat(&*handle.fd, &name, META_FLAGS, Mode::empty())
+                        .map_err(map_errno)?,
+                    None => handle.fd.try_clone()?,
+                };
+                std::fs::File::from(fd).metadata()
+            }
+            // 没有 `O_PATH` 的平台上打开符号链接或不可读文件会失败，改为相对句柄
+            // `fstatat` 取得文件标识，再按路径读取元数据并核对是同一文件。
+            #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+            {
+                use std::os::unix::fs::MetadataExt;
+
+                let Some(name) = name else {
+                    return std::fs::File::from(handle.fd.try_clone()?).metadata();
+                };
+                let stat = rustix::fs::statat(&*handle.fd, &name, AtFlags::SYMLINK_NOFOLLOW)
+                    .map_err(map_errno)?;
+                let metadata = std::fs::symlink_metadata(handle.path.join(&name))?;
+                if metadata.dev() != stat.st_dev as u64 || metadata.ino() != stat.st_ino as u64 {
+                    return Err(escape_error());
+                }
+                Ok(metadata)
+            }
+            #[cfg(not(unix))]
+            {
+                let path = match name {
+                    Some(name) => handle.path.join(name),
+                    None => handle.path.clone(),
+                };
+                std::fs::symlink_metadata(path)
+            }
+        })
+        .await
+    }
+
+    /// 以只读方式打开目录中的文件，不跟随符号链接。
+    pub async fn open_file(&self, name: &OsStr) -> io::Result<File> {
+        let handle = self.clone();
+        let name = name.to_os_string();
+        let file = blocking(move || {
+            #[cfg(unix)]
+            {
+                let fd = rustix::fs::openat(
+                    &*handle.fd,
+                    &name,
+                    OFlags::RDONLY | OFlags::NOFOLLOW | OFlags::CLOEXEC,
+                    Mode::empty(),
+                )
+                .map_err(map_errno)?;
+                Ok(std::fs::File::from(fd))
+            }
+            #[cfg(not(unix))]
+            {
+                let path = handle.path.join(&name);
+                reject_symlink(&path)?;
+                std::fs::File::open(path)
+            }
+        })
+        .await?;
+        Ok(File::from_std(file))
+    }
+
+    /// 在目录中创建新文件用于写入，文件已存在时失败。
+    pub async fn create_new_file(&self, name: &OsStr) -> io::Result<File> {
+        let handle = self.clone();
+        let name = name.to_os_string();
+        let file = blocking(move || {
+            #[cfg(unix)]
+            {
+                let fd = rustix::fs::openat(
+                    &*handle.fd,
+                    &name,
+                    OFlags::WRONLY
+                        | OFlags::CREATE
+                        | OFlags::EXCL
+                        | OFlags::NOFOLLOW
+                        | OFlags::CLOEXEC,
+                    Mode::from_raw_mode(0o666),
+                )
+                .map_err(map_errno)?;
+                Ok(std::fs::File::from(fd))
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::File::options()
+                    .write(true)
+                    .create_new(true)
+                    .open(handle.path.join(&name))
+            }
+        })
+        .await?;
+        Ok(File::from_std(file))
+    }
+
+    /// 以读写方式打开目录中的文件，不跟随符号链接。
+    pub async fn open_write_file(&self, name: &OsStr, options: WriteOptions) -> io::Result<File> {
+        let handle = self.clone();
+        let name = name.to_os_string();
+        let file = blocking(move || {
+            #[cfg(unix)]
+            {
+                let mut flags = OFlags::RDWR | OFlags::NOFOLLOW | OFlags::CLOEXEC;
+                for (enabled, flag) in [
+                    (options.append, OFlags::APPEND),
+                    (options.truncate, OFlags::TRUNC),
+                    (options.create || options.create_new, OFlags::CREATE),
+                    (options.create_new, OFlags::EXCL),
+                ] {
+                    if enabled {
+                        flags |= flag;
+                    }
+                }
+                let fd = rustix::fs::openat(&*handle.fd, &name, flags, Mode::from_raw_mode(0o666))
+                    .map_err(map_errno)?;
+                Ok(std::fs::File::from(fd))
+            }
+            #[cfg(not(unix))]
+            {
+                let path = handle.path.join(&name);
+                reject_symlink(&path)?;
+                std::fs::File::options()
+                    .read(true)
+                    .write(true)
+                    .append(options.append)
+                    .truncate(options.truncate)
+                    .create(options.create)
+                    .create_new(options.create_new)
+                    .open(path)
+            }
+        })
+        .await?;
+        Ok(File::from_std(file))
+    }
+
+    /// 在目录中创建子目录。
+    pub async fn create_dir(&self, name: &OsStr) -> io::Result<()> {
+        let handle = self.clone();
+        let name = name.to_os_string();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                rustix::fs::mkdirat(&*handle.fd, &name, Mode::from_raw_mode(0o777))?;
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::create_dir(handle.path.join(name))
+            }
+        })
+        .await
+    }
+
+    /// 在目录内重命名（目标存在时按平台语义替换）。
+    pub async fn rename(&self, from: &OsStr, to: &OsStr) -> io::Result<()> {
+        self.rename_to(from, self, to).await
+    }
+
+    /// 将目录项重命名到另一个目录句柄下（目标存在时按平台语义替换）。
+    pub async fn rename_to(&self, from: &OsStr, to_dir: &DirHandle, to: &OsStr) -> io::Result<()> {
+        let handle = self.clone();
+        let to_dir = to_dir.clone();
+        let from = from.to_os_string();
+        let to = to.to_os_string();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                rustix::fs::renameat(&*handle.fd, &from, &*to_dir.fd, &to)?;
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::rename(handle.path.join(from), to_dir.path.join(to))
+            }
+        })
+        .await
+    }
+
+    /// 删除目录中的文件（符号链接只删除链接本身）。
+    pub async fn remove_file(&self, name: &OsStr) -> io::Result<()> {
+        let handle = self.clone();
+        let name = name.to_os_string();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                rustix::fs::unlinkat(&*handle.fd, &name, AtFlags::empty())?;
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::remove_file(handle.path.join(name))
+            }
+        })
+        .await
+    }
+
+    /// 删除目录中的空子目录。
+    pub async fn remove_dir(&self, name: &OsStr) -> io::Result<()> {
+        let handle = self.clone();
+        let name = name.to_os_string();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                rustix::fs::unlinkat(&*handle.fd, &name, AtFlags::REMOVEDIR)?;
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::remove_dir(handle.path.join(name))
+            }
+        })
+        .await
+    }
+
+    /// 递归删除目录中的子目录，不跟随其中的符号链接。
+    pub async fn remove_dir_all(&self, name: &OsStr) -> io::Result<()> {
+        let handle = self.clone();
+        let name = name.to_os_string();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                remove_tree(&handle.fd, &name)
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::remove_dir_all(handle.path.join(name))
+            }
+        })
+        .await
+    }
+
+    /// 列出目录中的全部名称（不含 `.` 与 `..`）。
+    pub async fn read_dir(&self) -> io::Result<Vec<OsString>> {
+        let handle = self.clone();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                list_names(&handle.fd)
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::read_dir(&handle.path)?
+                    .map(|entry| entry.map(|entry| entry.file_name()))
+                    .collect()
+            }
+        })
+        .await
+    }
+
+    /// 将目录项变更同步到磁盘。
+    pub async fn sync(&self) -> io::Result<()> {
+        let handle = self.clone();
+        blocking(move || {
+            #[cfg(unix)]
+            {
+                rustix::fs::fsync(&*handle.fd)?;
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            {
+                std::fs::File::open(&handle.path)?.sync_all()
+            }
+        })
+        .await
+    }
+
+    /// 若目录项是符号链接则返回其目标，不存在或不是链接时返回 None。
+    fn symlink_target(&self, name: &OsStr) -> io::Result<Option<PathBuf>> {
+        #[cfg(unix)]
+        {
+            match rustix::fs::readlinkat(&*self.fd, name, Vec::new()) {
+                Ok(target) => Ok(Some(PathBuf::from(OsStr::from_bytes(target.as_bytes())))),
+                Err(Errno::INVAL | Errno::NOENT) => Ok(None),
+                Err(err) => Err(map_errno(err)),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let path = self.path.join(name);
+            match std::fs::symlink_metadata(&path) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    std::fs::read_link(path).map(Some)
+                }
+                Ok(_) => Ok(None),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            }
+        }
+    }
+
+    fn open_child_dir(&self, name: &OsStr, create: bool) -> io::Result<Self> {
+        let path = self.path.join(name);
+        #[cfg(unix)]
+        {
+            if create {
+                match rustix::fs::mkdirat(&*self.fd, name, Mode::from_raw_mode(0o777)) {
+                    Ok(()) | Err(Errno::EXIST) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            let fd =
+                rustix::fs::openat(&*self.fd, name, DIR_FLAGS, Mode::empty()).map_err(map_errno)?;
+            Ok(Self {
+                fd: Arc::new(fd),
+                path,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            if create {
+                match std::fs::create_dir(&path) {
+                    Err(err) if err.kind() != ErrorKind::AlreadyExists => return Err(err),
+                    _ => {}
+                }
+            }
+            reject_symlink(&path)?;
+            if !std::fs::metadata(&path)?.is_dir() {
+                return Err(escape_error());
+            }
+            Ok(Self { path })
+        }
+    }
+}
+
+/// 判断错误是否表示路径经过符号链接或非目录组件。
+pub fn is_escape(err: &io::Error) -> bool {
+    err.kind() == ErrorKind::InvalidInput
+}
+
+/// 将指向根目录内的绝对链接目标转换为相对根目录的路径。
+fn strip_root(root: &Path, target: &Path) -> io::Result<PathBuf> {
+    if let Ok(relative) = target.strip_prefix(root) {
+        return Ok(relative.to_path_buf());
+    }
+    let canonical = std::fs::canonicalize(root)?;
+    target
+        .strip_prefix(&canonical)
+        .map(Path::to_path_buf)
+        .map_err(|_| escape_error())
+}
+
+fn escape_error() -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        "path traverses a symlink or non-directory",
+    )
+}
+
+#[cfg(unix)]
+fn map_errno(err: Errno) -> io::Error {
+    match err {
+        Errno::LOOP | Errno::NOTDIR => escape_error(),
+        other => other.into(),
+    }
+}
+
+#[cfg(unix)]
+fn list_names(fd: &OwnedFd) -> io::Result<Vec<OsString>> {
+    let mut names = Vec::new();
+    for entry in Dir::read_from(fd)? {
+        let entry = entry?;
+        let name = OsStr::from_bytes(entry.file_name().to_bytes());
+        if name != "." && name != ".." {
+            names.push(name.to_os_string());
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(unix)]
+fn remove_tree(parent: &OwnedFd, name: &OsStr) -> io::Result<()> {
+    let dir = match rustix::fs::openat(parent, name, DIR_FLAGS, Mode::empty()) {
+        Ok(dir) => dir,
+        // 普通文件或符号链接：直接删除目录项。
+        Err(Errno::LOOP | Errno::NOTDIR) => {
+            rustix::fs::unlinkat(parent, name, AtFlags::empty())?;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    for child in list_names(&dir)? {
+        remove_tree(&dir, &child)?;
+    }
+    drop(dir);
+    rustix::fs::unlinkat(parent, name, AtFlags::REMOVEDIR)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reject_symlink(path: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => Err(escape_error()),
+        _ => Ok(()),
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    task: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|err| io::Error::other(err.to_string()))?
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn symlink_swapped_in_after_open_does_not_escape() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("root");
+        let outside = temp.path().join("outside");
+        std::fs::create_dir_all(root.join("docs")).expect("create docs");
+        std::fs::create_dir_all(&outside).expect("create outside");
+        std::fs::write(outside.join("secret.txt"), b"secret").expect("write secret");
+
+        let handle = DirHandle::open_root(&root).await.expect("open root");
+        let docs = handle
+            .open_dir(Path::new("docs"), false)
+            .await
+            .expect("docs");
+
+        // 打开之后把目录替换为指向根目录外的符号链接。
+        std::fs::rename(root.join("docs"), root.join("old")).expect("move docs");
+        symlink(&outside, root.join("docs")).expect("symlink");
+
+        let err = handle
+            .open_dir(Path::new("docs"), false)
+            .await
+            .expect_err("symlink must be rejected");
+        assert!(is_escape(&err));
+        let err = docs
+            .open_file(OsStr::new("secret.txt"))
+            .await
+            .expect_err("old handle stays on the original directory");
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        handle
+            .remove_dir_all(OsStr::new("docs"))
+            .await
+            .expect("remove link");
+        assert!(outside.join("secret.txt").exists());
+        assert_eq!(
+            handle.read_dir().await.expect("list"),
+            vec![OsString::from("old")]
+        );
+    }
+}
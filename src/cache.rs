@@ -0,0 +1,94 @@
+//! 缩略图与元数据共用的磁盘缓存：按内容键分片保存，启用存储加密时同样加密。
+
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::content::{ContentFormat, ContentReader, ContentWriter};
+use crate::crypto::Keyring;
+
+/// 以 `{dir}/{key[..2]}/{key}.{ext}` 布局保存缓存条目的目录。
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// 创建使用指定目录的缓存（目录在首次写入时创建）。
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// 读取缓存条目，不存在或无法解密时返回 None。
+    pub async fn read(&self, keyring: Option<&Keyring>, key: &str, ext: &str) -> Option<Vec<u8>> {
+        let format = ContentFormat {
+            keyring,
+            compressed: false,
+        };
+        let mut reader = ContentReader::open(&self.path(key, ext), format)
+            .await
+            .ok()?;
+        reader.read_to_end().await.ok()
+    }
+
+    /// 写入缓存条目（写入临时文件后原子替换）。
+    pub async fn write(
+        &self,
+        keyring: Option<&Keyring>,
+        key: &str,
+        ext: &str,
+        data: &[u8],
+    ) -> io::Result<()> {
+        let path = self.path(key, ext);
+        let dir = path.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(dir).await?;
+        let temp_path = dir.join(format!(".{key}.tmp.{}", Uuid::new_v4()));
+        let result = async {
+            let file = fs::File::create(&temp_path).await?;
+            let mut writer = ContentWriter::new(file, keyring, None);
+            writer.write_all(data).await?;
+            writer.finish().await?.sync_all().await?;
+            fs::rename(&temp_path, &path).await
+        }
+        .await;
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path).await;
+        }
+        result
+    }
+
+    fn path(&self, key: &str, ext: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{key}.{ext}"))
+    }
+
+    /// 删除超过保留时长未更新的缓存文件，返回删除数量。
+    pub async fn prune(&self, max_age: Duration) -> io::Result<u64> {
+        let now = SystemTime::now();
+        let mut removed = 0;
+        let mut shards = match fs::read_dir(&self.dir).await {
+            Ok(shards) => shards,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        while let Some(shard) = shards.next_entry().await? {
+            if !shard.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut entries = fs::read_dir(shard.path()).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let expired = entry
+                    .metadata()
+                    .await
+                    .ok()
+                    .and_then(|metadata| metadata.modified().ok())
+                    .and_then(|modified| now.duration_since(modified).ok())
+                    .is_some_and(|age| age >= max_age);
+                if expired && fs::remove_file(entry.path()).await.is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
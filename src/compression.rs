@@ -0,0 +1,290 @@
+//! 静态压缩：zstd 可寻址帧格式（seekable format）的编码与索引解析。
+//!
+//! 压缩文件以一个标记用的可跳过帧开头（记录分帧大小），随后是若干独立
+//! 压缩的 zstd 帧，最后是标准的 seek table 可跳过帧。除末帧外每帧压缩同样
+//! 大小的明文，因此只需读取尾部即可得到原始长度，并可按原始偏移定位帧。
+
+use std::io::{self, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::COMPRESSION_FRAME_SIZE;
+
+/// 标记帧使用的可跳过帧魔数。
+const MARKER_FRAME_MAGIC: u32 = 0x184D_2A5B;
+/// 标记帧负载前缀。
+const MARKER_TAG: [u8; 8] = *b"AXOZSTD\x01";
+/// 标记帧总长度（魔数、帧长度、前缀、分帧大小与保留字段）。
+pub const MARKER_LEN: u64 = 24;
+/// seek table 可跳过帧魔数。
+const SEEK_TABLE_MAGIC: u32 = 0x184D_2A5E;
+/// seek table 尾部魔数。
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+/// seek table 尾部长度。
+pub const FOOTER_LEN: u64 = 9;
+/// seek table 单个条目长度。
+pub const ENTRY_LEN: u64 = 8;
+
+/// 按目录启用压缩的配置。
+#[derive(Clone, Debug)]
+pub struct CompressionConfig {
+    dirs: Vec<PathBuf>,
+    level: i32,
+}
+
+impl CompressionConfig {
+    /// 根据逗号分隔的存储相对目录列表创建配置，列表为空时返回 None。
+    pub fn from_dirs(dirs: &str, level: i32) -> Option<Self> {
+        let dirs: Vec<PathBuf> = dirs
+            .split(',')
+            .map(|dir| normalize_dir(dir.trim()))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect();
+        if dirs.is_empty() {
+            return None;
+        }
+        Some(Self { dirs, level })
+    }
+
+    /// 判断存储相对路径是否位于启用压缩的目录下，返回压缩级别。
+    pub fn level_for(&self, relative: &Path) -> Option<i32> {
+        self.dirs
+            .iter()
+            .any(|dir| relative.starts_with(dir))
+            .then_some(self.level)
+    }
+}
+
+fn normalize_dir(value: &str) -> PathBuf {
+    Path::new(value.trim_start_matches(['/', '\\']))
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(segment) => Some(segment),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 将明文按固定大小分帧压缩的流式压缩器。
+pub struct FrameCompressor {
+    level: i32,
+    pending: Vec<u8>,
+    entries: Vec<(u32, u32)>,
+    marker_written: bool,
+}
+
+impl FrameCompressor {
+    /// 创建指定压缩级别的压缩器。
+    pub fn new(level: i32) -> Self {
+        Self {
+            level,
+            pending: Vec::with_capacity(COMPRESSION_FRAME_SIZE),
+            entries: Vec::new(),
+            marker_written: false,
+        }
+    }
+
+    /// 追加明文，返回已完成帧的压缩数据（可能为空）。
+    pub fn update(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = self.take_marker();
+        self.pending.extend_from_slice(data);
+        while self.pending.len() >= COMPRESSION_FRAME_SIZE {
+            let rest = self.pending.split_off(COMPRESSION_FRAME_SIZE);
+            let frame = std::mem::replace(&mut self.pending, rest);
+            output.extend(self.compress_frame(&frame)?);
+        }
+        Ok(output)
+    }
+
+    /// 压缩剩余数据并写出 seek table。
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        let mut output = self.take_marker();
+        if !self.pending.is_empty() {
+            let frame = std::mem::take(&mut self.pending);
+            output.extend(self.compress_frame(&frame)?);
+        }
+        let table_len = self.entries.len() as u64 * ENTRY_LEN + FOOTER_LEN;
+        output.extend(SEEK_TABLE_MAGIC.to_le_bytes());
+        output.extend((table_len as u32).to_le_bytes());
+        for (compressed, decompressed) in &self.entries {
+            output.extend(compressed.to_le_bytes());
+            output.extend(decompressed.to_le_bytes());
+        }
+        output.extend((self.entries.len() as u32).to_le_bytes());
+        output.push(0);
+        output.extend(SEEKABLE_MAGIC.to_le_bytes());
+        Ok(output)
+    }
+
+    fn take_marker(&mut self) -> Vec<u8> {
+        if self.marker_written {
+            return Vec::new();
+        }
+        self.marker_written = true;
+        let mut marker = Vec::with_capacity(MARKER_LEN as usize);
+        marker.extend(MARKER_FRAME_MAGIC.to_le_bytes());
+        marker.extend(16u32.to_le_bytes());
+        marker.extend(MARKER_TAG);
+        marker.extend((COMPRESSION_FRAME_SIZE as u32).to_le_bytes());
+        marker.extend(0u32.to_le_bytes());
+        marker
+    }
+
+    fn compress_frame(&mut self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        let compressed = zstd::bulk::compress(frame, self.level)?;
+        self.entries
+            .push((compressed.len() as u32, frame.len() as u32));
+        Ok(compressed)
+    }
+}
+
+/// 解析标记帧，若为压缩文件则返回分帧大小。
+pub fn parse_marker(head: &[u8]) -> Option<u64> {
+    if head.len() < MARKER_LEN as usize
+        || head[..4] != MARKER_FRAME_MAGIC.to_le_bytes()
+        || head[4..8] != 16u32.to_le_bytes()
+        || head[8..16] != MARKER_TAG
+    {
+        return None;
+    }
+    let frame_size = u32::from_le_bytes([head[16], head[17], head[18], head[19]]) as u64;
+    (frame_size > 0 && frame_size <= COMPRESSION_FRAME_SIZE as u64).then_some(frame_size)
+}
+
+/// 解析 seek table 尾部，返回帧数量。
+pub fn parse_footer(footer: &[u8]) -> io::Result<u64> {
+    if footer.len() != FOOTER_LEN as usize || footer[5..9] != SEEKABLE_MAGIC.to_le_bytes() {
+        return Err(io::Error::new(ErrorKind::InvalidData, "invalid seek table"));
+    }
+    if footer[4] & 0x80 != 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "seek table checksums are not supported",
+        ));
+    }
+    let mut raw = [0u8; 4];
+    raw.copy_from_slice(&footer[..4]);
+    Ok(u32::from_le_bytes(raw) as u64)
+}
+
+/// 压缩帧在存储内容与原始内容中的位置。
+#[derive(Clone, Copy, Debug)]
+pub struct FrameIndex {
+    pub stored_offset: u64,
+    pub stored_len: u64,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// 解析单个 seek table 条目，返回（压缩长度，原始长度）。
+pub fn parse_entry(entry: &[u8]) -> (u64, u64) {
+    (
+        u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as u64,
+        u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]) as u64,
+    )
+}
+
+/// 由 seek table 条目构建帧索引。
+///
+/// 校验除末帧外每帧原始长度均为 `frame_size`、末帧不超过 `frame_size`，
+/// 且各帧压缩数据恰好占满标记帧与 seek table 之间的区域（`data_end`）。
+pub fn build_index(entries: &[u8], frame_size: u64, data_end: u64) -> io::Result<Vec<FrameIndex>> {
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "invalid seek table");
+    let count = entries.len() / ENTRY_LEN as usize;
+    let mut frames = Vec::with_capacity(count);
+    let mut stored_offset = MARKER_LEN;
+    let mut offset = 0;
+    for (position, entry) in entries.chunks_exact(ENTRY_LEN as usize).enumerate() {
+        let (stored_len, len) = parse_entry(entry);
+        let last = position + 1 == count;
+        if len == 0 || len > frame_size || (!last && len != frame_size) {
+            return Err(invalid());
+        }
+        frames.push(FrameIndex {
+            stored_offset,
+            stored_len,
+            offset,
+            len,
+        });
+        stored_offset += stored_len;
+        offset += len;
+    }
+    if stored_offset != data_end {
+        return Err(invalid());
+    }
+    Ok(frames)
+}
+
+/// 解压单个帧（`len` 为 seek table 记录的原始长度，不超过分帧大小）。
+pub fn decompress_frame(data: &[u8], len: u64) -> io::Result<Vec<u8>> {
+    if len > COMPRESSION_FRAME_SIZE as u64 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "compressed frame is too large",
+        ));
+    }
+    let output = zstd::bulk::decompress(data, len as usize)?;
+    if output.len() as u64 != len {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "compressed frame size mismatch",
+        ));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_for_matches_configured_directories() {
+        let config = CompressionConfig::from_dirs("/logs, exports/text ,", 3).expect("config");
+        assert_eq!(config.level_for(Path::new("logs/app.log")), Some(3));
+        assert_eq!(config.level_for(Path::new("exports/text/a.csv")), Some(3));
+        assert_eq!(config.level_for(Path::new("logs2/app.log")), None);
+        assert_eq!(config.level_for(Path::new("exports/a.csv")), None);
+        assert!(CompressionConfig::from_dirs(" , ", 3).is_none());
+    }
+
+    #[test]
+    fn compressed_stream_is_valid_zstd_with_seek_table() {
+        let data: Vec<u8> = (0..COMPRESSION_FRAME_SIZE * 2 + 17)
+            .map(|i| (i % 7) as u8)
+            .collect();
+        let mut compressor = FrameCompressor::new(3);
+        let mut stored = compressor.update(&data).expect("update");
+        stored.extend(compressor.finish().expect("finish"));
+
+        assert_eq!(parse_marker(&stored), Some(COMPRESSION_FRAME_SIZE as u64));
+        assert_eq!(zstd::stream::decode_all(&stored[..]).expect("decode"), data);
+
+        let footer = &stored[stored.len() - FOOTER_LEN as usize..];
+        let frames = parse_footer(footer).expect("footer");
+        assert_eq!(frames, 3);
+        let entries_start = stored.len() - (FOOTER_LEN + frames * ENTRY_LEN) as usize;
+        let entries = &stored[entries_start..stored.len() - FOOTER_LEN as usize];
+        let frame_size = COMPRESSION_FRAME_SIZE as u64;
+        let data_end = entries_start as u64 - 8;
+        let index = build_index(entries, frame_size, data_end).expect("index");
+        let last = index[2];
+        let frame =
+            &stored[last.stored_offset as usize..(last.stored_offset + last.stored_len) as usize];
+        assert_eq!(
+            decompress_frame(frame, last.len).expect("frame"),
+            data[last.offset as usize..]
+        );
+    }
+
+    #[test]
+    fn seek_table_with_oversized_frames_is_rejected() {
+        let frame_size = COMPRESSION_FRAME_SIZE as u64;
+        let mut entries = Vec::new();
+        for (stored_len, len) in [(10u32, frame_size as u32 * 4), (10, 1)] {
+            entries.extend(stored_len.to_le_bytes());
+            entries.extend(len.to_le_bytes());
+        }
+        assert!(build_index(&entries, frame_size, MARKER_LEN + 20).is_err());
+        assert!(build_index(&entries[..8], frame_size, MARKER_LEN + 10).is_err());
+        assert!(decompress_frame(&[], frame_size * 4).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 //! CLI 参数与服务端默认配置。
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use shadow_rs::formatcp;
 
 use crate::build;
+use crate::storage::SymlinkPolicy;
 
 const VERSION_INFO: &str = formatcp!(
     r#"{}\ncommit_hash: {}\nbuild_time: {}\nbuild_env: {},{}"#,
@@ -16,6 +17,15 @@ const VERSION_INFO: &str = formatcp!(
 
 pub const MAX_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
 pub const UPLOAD_TEMP_DIR: &str = ".axo/temp";
+/// 命名卷的上传临时目录（位于卷根目录下，保证与卷在同一设备）。
+pub const VOLUME_UPLOAD_TEMP_DIR: &str = ".upload_temp";
+/// 卷占用缓存的有效期（秒）：写入即时计入缓存，删除与外部修改最迟在
+/// 过期重新统计后反映。
+pub const VOLUME_USAGE_CACHE_SECS: u64 = 60;
+/// 批量上传目录中记录各文件上传会话的清单文件名。
+pub const UPLOAD_BATCH_MANIFEST: &str = "batch.json";
+/// 单个批量上传清单允许的最大文件数。
+pub const UPLOAD_BATCH_MAX_FILES: usize = 10_000;
 pub const DEFAULT_AUTH_USER: &str = "axo";
 pub const DEFAULT_AUTH_PASS: &str = "axo";
 pub const AUTH_COOKIE_NAME: &str = "AXO_SESSION";
@@ -29,13 +39,88 @@ pub const DEFAULT_UPLOAD_MAX_CONCURRENT: u64 = 8;
 pub const DEFAULT_UPLOAD_TEMP_TTL_SECS: u64 = 24 * 60 * 60;
 pub const SESSION_PRUNE_INTERVAL_SECS: u64 = 300;
 pub const UPLOAD_CLEAN_INTERVAL_SECS: u64 = 900;
+/// 原子写入临时文件超过该时长未修改即视为遗留并清理（秒）。
+pub const STALE_ATOMIC_TEMP_SECS: u64 = 60 * 60;
 /// 获取路径锁的最大等待时间（秒）。
 pub const DEFAULT_LOCK_WAIT_TIMEOUT_SECS: u64 = 10;
+/// 单个下载请求允许的最大 Range 区间数，超过时忽略 Range 返回完整内容。
+pub const DOWNLOAD_MAX_RANGES: usize = 16;
+/// 局部写入时复制原文件内容的分块大小。
+pub const PATCH_COPY_CHUNK_SIZE: usize = 256 * 1024;
+/// 冲突重命名时最多尝试的序号，全部被占用时返回 409。
+pub const CONFLICT_RENAME_MAX_ATTEMPTS: u32 = 1000;
+/// 客户端可指定的最大修改时间（Unix 秒，9999-12-31T23:59:59Z），更晚的时间无法格式化为 HTTP 日期。
+pub const MAX_CLIENT_MTIME_SECS: u64 = 253_402_300_799;
+/// 响应压缩的最小正文大小（字节），已知长度更小的响应不压缩。
+pub const RESPONSE_COMPRESSION_MIN_SIZE: u16 = 1024;
+/// 对外链接令牌长度（字母与数字，约 95 位随机数）。
+pub const LINK_TOKEN_LEN: usize = 16;
+/// 失效链接的清理间隔（秒）。
+pub const LINK_PRUNE_INTERVAL_SECS: u64 = 300;
+/// 每个文件请求链接同时存在的分片上传会话上限。
+pub const FILE_REQUEST_MAX_PENDING_UPLOADS: u64 = 4;
+pub const DEFAULT_TRANSFER_DAYS: u64 = 14;
+/// 一次性上传链接允许的最长有效天数。
+pub const TRANSFER_MAX_DAYS: u64 = 365;
+/// 静态加密时每个分段的明文大小。
+pub const ENCRYPTION_SEGMENT_SIZE: usize = 64 * 1024;
+/// 静态压缩时每个 zstd 帧的明文大小。
+pub const COMPRESSION_FRAME_SIZE: usize = 128 * 1024;
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+/// 变更事件广播通道容量，订阅者落后超过该数量时收到 `rescan` 事件。
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// 合并文件系统事件的空闲窗口（毫秒）。
+pub const EVENT_COALESCE_MS: u64 = 100;
+/// 持续写入时事件的最长发布延迟（毫秒）。
+pub const EVENT_MAX_DELAY_MS: u64 = 1000;
+/// 事件流的保活间隔（秒）。
+pub const EVENT_KEEP_ALIVE_SECS: u64 = 15;
+/// 变更日志保留的最近记录数。
+pub const JOURNAL_MAX_ENTRIES: usize = 10_000;
+/// `/api/changes` 单次返回的最大记录数。
+pub const CHANGES_PAGE_LIMIT: usize = 1000;
+/// 缩略图预设边长，请求尺寸向上取整到其中之一以限制缓存数量。
+pub const THUMBNAIL_SIZES: &[u32] = &[64, 128, 256, 512, 1024];
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+/// 缩略图与未指定 `quality` 的图片变换使用的 JPEG 质量。
+pub const DEFAULT_IMAGE_QUALITY: u8 = 80;
+/// 可生成缩略图的源文件最大字节数。
+pub const THUMBNAIL_MAX_SOURCE_SIZE: u64 = 64 * 1024 * 1024;
+/// 解码时允许的最大图片宽高（像素）。
+pub const THUMBNAIL_MAX_DIMENSION: u32 = 16_384;
+/// 同时解码生成缩略图的最大数量。
+pub const THUMBNAIL_MAX_CONCURRENT: usize = 2;
+/// 缩略图缓存文件的保留时长（秒）。
+pub const THUMBNAIL_CACHE_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+/// 读取 PDF 元数据时允许载入内存的最大字节数（超出时只返回基本信息）。
+pub const METADATA_MAX_PDF_SIZE: u64 = 64 * 1024 * 1024;
+/// 同时提取元数据的最大文件数量。
+pub const METADATA_MAX_CONCURRENT: usize = 4;
+/// 列出目录详细字段时同时读取的最大条目数量。
+pub const LIST_DETAIL_MAX_CONCURRENT: usize = 8;
+/// 解析 MP4 盒子与 Matroska 元素时允许的最大嵌套层数，防止构造的文件耗尽栈空间。
+pub const METADATA_MAX_NESTING: usize = 16;
+/// 元数据缓存文件的保留时长（秒）。
+pub const METADATA_CACHE_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+/// 文本预览默认与最多返回的原始字节数。
+pub const PREVIEW_DEFAULT_BYTES: usize = 64 * 1024;
+pub const PREVIEW_MAX_BYTES: usize = 1024 * 1024;
+/// 按行预览时默认与最多返回的行数。
+pub const PREVIEW_DEFAULT_LINES: usize = 200;
+pub const PREVIEW_MAX_LINES: usize = 10_000;
+/// 识别编码时读取的文件开头字节数。
+pub const PREVIEW_DETECT_BYTES: usize = 64 * 1024;
+/// 按行预览时为定位起始行最多扫描的字节数。
+pub const PREVIEW_MAX_SCAN_BYTES: u64 = 32 * 1024 * 1024;
+/// stat 接口识别 MIME 类型时读取的文件头字节数。
+pub const STAT_SNIFF_BYTES: usize = 8 * 1024;
 
 /// CLI arguments and environment configuration for the server.
 #[derive(Parser, Debug)]
 #[command(name = "axo-drive", version = VERSION_INFO, about = "AxoDrive server")]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     #[arg(
         short = 's',
         long,
@@ -44,6 +129,13 @@ pub struct Args {
         help = "Storage directory for files"
     )]
     pub storage_dir: String,
+    #[arg(
+        long = "volume",
+        env = "AXO_VOLUMES",
+        value_delimiter = ',',
+        help = "Named storage volume name=path[;ro][;quota=bytes] (repeatable, replaces --storage-dir)"
+    )]
+    pub volumes: Vec<String>,
     #[arg(
         long,
         env = "AXO_AUTH_USER",
@@ -144,4 +236,139 @@ pub struct Args {
         help = "Upload temp cleanup threshold in seconds (0 to disable)"
     )]
     pub upload_temp_ttl_secs: u64,
+    #[arg(
+        long,
+        env = "AXO_ENCRYPTION_KEY",
+        help = "At-rest encryption key (64 hex chars)"
+    )]
+    pub encryption_key: Option<String>,
+    #[arg(
+        long,
+        env = "AXO_ENCRYPTION_KEY_FILE",
+        help = "File containing the at-rest encryption key"
+    )]
+    pub encryption_key_file: Option<String>,
+    #[arg(
+        long,
+        env = "AXO_ENCRYPTION_PREVIOUS_KEYS",
+        help = "Comma separated previous encryption keys for decryption"
+    )]
+    pub encryption_previous_keys: Option<String>,
+    #[arg(
+        long,
+        env = "AXO_COMPRESS_DIRS",
+        help = "Comma separated storage directories whose files are stored compressed"
+    )]
+    pub compress_dirs: Option<String>,
+    #[arg(
+        long,
+        env = "AXO_COMPRESS_LEVEL",
+        default_value_t = DEFAULT_COMPRESSION_LEVEL,
+        help = "zstd compression level for compressed directories"
+    )]
+    pub compress_level: i32,
+    #[arg(
+        long,
+        env = "AXO_SYMLINKS",
+        value_enum,
+        default_value_t = SymlinkPolicy::Reject,
+        help = "Symlink policy inside storage: reject, follow (inside root only) or show"
+    )]
+    pub symlinks: SymlinkPolicy,
+    #[arg(
+        long,
+        env = "AXO_HIDDEN_NAMES",
+        help = "Comma separated name patterns (* and ? wildcards) hidden from listings and WebDAV"
+    )]
+    pub hidden_names: Option<String>,
+    #[arg(
+        long,
+        env = "AXO_NO_WATCH",
+        help = "Disable the storage watcher that feeds /api/events"
+    )]
+    pub no_watch: bool,
+    #[arg(
+        long,
+        env = "AXO_JOURNAL_FILE",
+        default_value = ".axo/journal.jsonl",
+        help = "Change journal file backing /api/changes"
+    )]
+    pub journal_file: String,
+    #[arg(
+        long,
+        env = "AXO_THUMBNAIL_CACHE_DIR",
+        default_value = ".axo/thumbnails",
+        help = "Directory for cached image thumbnails"
+    )]
+    pub thumbnail_cache_dir: String,
+    #[arg(
+        long,
+        env = "AXO_THUMBNAIL_PREGENERATE",
+        help = "Generate thumbnails in the background for new and modified images"
+    )]
+    pub thumbnail_pregenerate: bool,
+    #[arg(
+        long,
+        env = "AXO_IMAGE_TRANSFORM_SIZES",
+        default_value = "320,640,800,1024,1280,1920",
+        help = "Comma separated widths/heights allowed for download image transforms (empty disables)"
+    )]
+    pub image_transform_sizes: String,
+    #[arg(
+        long,
+        env = "AXO_METADATA_CACHE_DIR",
+        default_value = ".axo/metadata",
+        help = "Directory for cached media and document metadata"
+    )]
+    pub metadata_cache_dir: String,
+    #[arg(
+        long,
+        env = "AXO_DOWNLOAD_CACHE_CONTROL",
+        default_value = "private, no-cache",
+        help = "Cache-Control header sent with file downloads"
+    )]
+    pub download_cache_control: String,
+    #[arg(
+        long,
+        env = "AXO_NO_RESPONSE_COMPRESSION",
+        help = "Disable gzip/brotli/zstd compression of API and text responses"
+    )]
+    pub no_response_compression: bool,
+    #[arg(
+        long,
+        env = "AXO_LINKS_FILE",
+        default_value = ".axo/links.json",
+        help = "Link store file backing transfer links"
+    )]
+    pub links_file: String,
+    #[arg(
+        long,
+        env = "AXO_PUBLIC_URL",
+        help = "Base URL used in generated links (defaults to the request host)"
+    )]
+    pub public_url: Option<String>,
+    #[arg(
+        long,
+        env = "AXO_TRANSFER_DIR",
+        default_value = "transfers",
+        help = "Directory (inside storage) receiving one-shot transfer uploads"
+    )]
+    pub transfer_dir: String,
+    #[arg(
+        long,
+        env = "AXO_TRANSFER_DEFAULT_DAYS",
+        default_value_t = DEFAULT_TRANSFER_DAYS,
+        help = "Days a transfer link stays valid when the upload does not ask for less"
+    )]
+    pub transfer_default_days: u64,
+}
+
+/// 离线维护子命令。
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Re-encrypt all stored files with the current encryption key.
+    Reencrypt {
+        #[arg(long, help = "Only report files that would be re-encrypted")]
+        dry_run: bool,
+    },
 }
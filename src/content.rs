@@ -0,0 +1,571 @@
+//! 存储内容读写：在磁盘格式与客户端可见内容之间透明转换。
+//!
+//! 写入端按目录配置先进行分帧压缩，再在配置了密钥时分段加密；读取端
+//! 根据文件头识别加密与压缩格式并逐层还原，普通文件按原样读取。只有配置
+//! 了密钥或启用了压缩的存储才识别对应格式，均未启用时读取端不做任何探测。
+
+use bytes::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use tracing::{info, warn};
+
+use crate::atomic::AtomicFile;
+use crate::compression::{self, ENTRY_LEN, FOOTER_LEN, FrameCompressor, FrameIndex, MARKER_LEN};
+use crate::crypto::{self, DecryptingReader, Keyring, SegmentEncryptor};
+use crate::storage::{ResolvedPath, Storage};
+
+/// 流式读取时单次读取的最大字节数。
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 磁盘上的存储字节（必要时已解密）。
+enum StoredSource {
+    /// 未加密文件。
+    Plain { file: File, len: u64 },
+    /// 分段加密文件。
+    Encrypted(Box<DecryptingReader>),
+}
+
+impl StoredSource {
+    fn len(&self) -> u64 {
+        match self {
+            Self::Plain { len, .. } => *len,
+            Self::Encrypted(reader) => reader.len(),
+        }
+    }
+
+    async fn read_at(&mut self, offset: u64, max: usize) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Plain { file, len } => {
+                if offset >= *len || max == 0 {
+                    return Ok(Vec::new());
+                }
+                let mut buffer = vec![0u8; max.min((*len - offset) as usize)];
+                file.seek(SeekFrom::Start(offset)).await?;
+                let read = file.read(&mut buffer).await?;
+                buffer.truncate(read);
+                Ok(buffer)
+            }
+            Self::Encrypted(reader) => reader.read_at(offset, max).await,
+        }
+    }
+
+    async fn read_exact_at(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(len as usize);
+        while (output.len() as u64) < len {
+            let position = offset + output.len() as u64;
+            let chunk = self
+                .read_at(position, (len - output.len() as u64) as usize)
+                .await?;
+            if chunk.is_empty() {
+                return Err(io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "unexpected end of stored content",
+                ));
+            }
+            output.extend(chunk);
+        }
+        Ok(output)
+    }
+}
+
+/// 读取时需要识别的存储格式。
+#[derive(Clone, Copy, Default)]
+pub struct ContentFormat<'a> {
+    /// 静态加密密钥环。
+    pub keyring: Option<&'a Keyring>,
+    /// 是否识别压缩格式。文件可能在目录间移动，因此按存储而非按目录判断。
+    pub compressed: bool,
+}
+
+impl ContentFormat<'_> {
+    /// 判断是否无需打开文件即可按原样读取。
+    pub fn is_plain(&self) -> bool {
+        self.keyring.is_none() && !self.compressed
+    }
+}
+
+/// 压缩文件的帧索引状态。
+struct CompressedState {
+    frames: u64,
+    frame_size: u64,
+    len: u64,
+    index: Option<Vec<FrameIndex>>,
+    cached: Option<(usize, Vec<u8>)>,
+}
+
+/// 按客户端可见内容读取存储文件（透明解密、解压）。
+pub struct ContentReader {
+    source: StoredSource,
+    compressed: Option<CompressedState>,
+}
+
+impl ContentReader {
+    /// 打开文件并根据头部识别存储格式。
+    pub async fn open(path: &Path, format: ContentFormat<'_>) -> io::Result<Self> {
+        Self::new(File::open(path).await?, format).await
+    }
+
+    /// 从已打开的文件句柄读取头部并识别存储格式。
+    pub async fn new(mut file: File, format: ContentFormat<'_>) -> io::Result<Self> {
+        let stored_len = file.metadata().await?.len();
+        // 未配置密钥时不探测加密头，避免明文文件恰好以加密魔数开头时被误判。
+        let header = match format.keyring {
+            Some(keyring) => crypto::read_header(&mut file)
+                .await?
+                .map(|header| (header, keyring)),
+            None => None,
+        };
+        let mut source = match header {
+            Some((header, keyring)) => StoredSource::Encrypted(Box::new(DecryptingReader::new(
+                file, header, stored_len, keyring,
+            )?)),
+            None => StoredSource::Plain {
+                file,
+                len: stored_len,
+            },
+        };
+        let compressed = if format.compressed {
+            read_compressed_state(&mut source).await?
+        } else {
+            None
+        };
+        Ok(Self { source, compressed })
+    }
+
+    /// 返回客户端可见的内容长度。
+    pub fn len(&self) -> u64 {
+        match &self.compressed {
+            Some(state) => state.len,
+            None => self.source.len(),
+        }
+    }
+
+    /// 返回加密该文件所用密钥的标识（未加密时为 None）。
+    pub fn key_id(&self) -> Option<&str> {
+        match &self.source {
+            StoredSource::Encrypted(reader) => Some(reader.key_id()),
+            StoredSource::Plain { .. } => None,
+        }
+    }
+
+    /// 从偏移处读取最多 `max` 字节，到达末尾时返回空。
+    pub async fn read_at(&mut self, offset: u64, max: usize) -> io::Result<Vec<u8>> {
+        let Some(state) = self.compressed.as_mut() else {
+            return self.source.read_at(offset, max).await;
+        };
+        if offset >= state.len || max == 0 {
+            return Ok(Vec::new());
+        }
+        if state.index.is_none() {
+            let table_start = self.source.len() - FOOTER_LEN - state.frames * ENTRY_LEN;
+            let entries = self
+                .source
+                .read_exact_at(table_start, state.frames * ENTRY_LEN)
+                .await?;
+            let index = compression::build_index(&entries, state.frame_size, table_start - 8)?;
+            let total: u64 = index.iter().map(|frame| frame.len).sum();
+            if total != state.len {
+                return Err(io::Error::new(ErrorKind::InvalidData, "invalid seek table"));
+            }
+            state.index = Some(index);
+        }
+        let index = state.index.as_deref().unwrap_or_default();
+        let position = index.partition_point(|frame| frame.offset + frame.len <= offset);
+        let frame = index
+            .get(position)
+            .copied()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "invalid seek table"))?;
+        if !matches!(&state.cached, Some((cached, _)) if *cached == position) {
+            let stored = self
+                .source
+                .read_exact_at(frame.stored_offset, frame.stored_len)
+                .await?;
+            let data = tokio::task::spawn_blocking(move || {
+                compression::decompress_frame(&stored, frame.len)
+            })
+            .await
+            .map_err(|err| io::Error::other(err.to_string()))??;
+            state.cached = Some((position, data));
+        }
+        let data = state
+            .cached
+            .as_ref()
+            .map(|(_, data)| data.as_slice())
+            .unwrap_or_default();
+        let skip = (offset - frame.offset) as usize;
+        let end = data.len().min(skip + max);
+        Ok(data[skip.min(end)..end].to_vec())
+    }
+
+    /// 读取全部内容。
+    pub async fn read_to_end(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(usize::try_from(self.len()).unwrap_or_default());
+        loop {
+            let chunk = self.read_at(data.len() as u64, READ_CHUNK_SIZE).await?;
+            if chunk.is_empty() {
+                return Ok(data);
+            }
+            data.extend_from_slice(&chunk);
+        }
+    }
+
+    /// 将指定区间转换为字节流。
+    pub async fn into_stream(
+        self,
+        start: u64,
+        length: u64,
+    ) -> io::Result<BoxStream<'static, io::Result<Bytes>>> {
+        let reader = match (self.source, self.compressed) {
+            (StoredSource::Plain { mut file, .. }, None) => {
+                file.seek(SeekFrom::Start(start)).await?;
+                return Ok(ReaderStream::new(file.take(length)).boxed());
+            }
+            (source, compressed) => Self { source, compressed },
+        };
+        Ok(stream::try_unfold(
+            (reader, start, start + length),
+            |(mut reader, offset, end)| async move {
+                if offset >= end {
+                    return Ok(None);
+                }
+                let max = (end - offset).min(READ_CHUNK_SIZE as u64) as usize;
+                let chunk = reader.read_at(offset, max).await?;
+                if chunk.is_empty() {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "unexpected end of content",
+                    ));
+                }
+                let next = offset + chunk.len() as u64;
+                Ok(Some((Bytes::from(chunk), (reader, next, end))))
+            },
+        )
+        .boxed())
+    }
+
+    /// 将固定字节与内容区间依次拼接为字节流（用于 `multipart/byteranges` 响应）。
+    pub fn into_parts_stream(self, parts: Vec<BodyPart>) -> BoxStream<'static, io::Result<Bytes>> {
+        stream::try_unfold(
+            (self, VecDeque::from(parts)),
+            |(mut reader, mut parts)| async move {
+                let chunk = match parts.pop_front() {
+                    None => return Ok(None),
+                    Some(BodyPart::Bytes(bytes)) => bytes,
+                    Some(BodyPart::Range { start, length }) => {
+                        let max = length.min(READ_CHUNK_SIZE as u64) as usize;
+                        let chunk = reader.read_at(start, max).await?;
+                        if chunk.is_empty() {
+                            return Err(io::Error::new(
+                                ErrorKind::UnexpectedEof,
+                                "unexpected end of content",
+                            ));
+                        }
+                        let read = chunk.len() as u64;
+                        if read < length {
+                            parts.push_front(BodyPart::Range {
+                                start: start + read,
+                                length: length - read,
+                            });
+                        }
+                        Bytes::from(chunk)
+                    }
+                };
+                Ok(Some((chunk, (reader, parts))))
+            },
+        )
+        .boxed()
+    }
+}
+
+/// 多段响应体的组成部分。
+pub enum BodyPart {
+    /// 原样输出的字节（分段头、分隔符）。
+    Bytes(Bytes),
+    /// 客户端可见内容中的区间。
+    Range { start: u64, length: u64 },
+}
+
+/// 识别压缩标记帧，并通过 seek table 尾部计算原始长度。
+async fn read_compressed_state(source: &mut StoredSource) -> io::Result<Option<CompressedState>> {
+    let stored_len = source.len();
+    if stored_len < MARKER_LEN + 8 + FOOTER_LEN {
+        return Ok(None);
+    }
+    let head = source.read_exact_at(0, MARKER_LEN).await?;
+    let Some(frame_size) = compression::parse_marker(&head) else {
+        return Ok(None);
+    };
+    let footer = source
+        .read_exact_at(stored_len - FOOTER_LEN, FOOTER_LEN)
+        .await?;
+    let frames = compression::parse_footer(&footer)?;
+    let table_len = frames * ENTRY_LEN + FOOTER_LEN + 8;
+    if table_len > stored_len - MARKER_LEN {
+        return Err(io::Error::new(ErrorKind::InvalidData, "invalid seek table"));
+    }
+    let len = if frames == 0 {
+        0
+    } else {
+        let last = source
+            .read_exact_at(stored_len - FOOTER_LEN - ENTRY_LEN, ENTRY_LEN)
+            .await?;
+        let (_, last_len) = compression::parse_entry(&last);
+        if last_len == 0 || last_len > frame_size {
+            return Err(io::Error::new(ErrorKind::InvalidData, "invalid seek table"));
+        }
+        (frames - 1) * frame_size + last_len
+    };
+    Ok(Some(CompressedState {
+        frames,
+        frame_size,
+        len,
+        index: None,
+        cached: None,
+    }))
+}
+
+/// 按存储格式写入内容（必要时压缩、加密）。
+pub struct ContentWriter {
+    file: File,
+    stored: u64,
+    compressor: Option<FrameCompressor>,
+    encryptor: Option<SegmentEncryptor>,
+}
+
+impl ContentWriter {
+    /// 基于已打开的文件创建写入器。
+    pub fn new(file: File, keyring: Option<&Keyring>, compression_level: Option<i32>) -> Self {
+        Self {
+            file,
+            stored: 0,
+            compressor: compression_level.map(FrameCompressor::new),
+            encryptor: keyring.map(SegmentEncryptor::new),
+        }
+    }
+
+    /// 写入全部数据。
+    pub async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        match self.compressor.as_mut() {
+            Some(compressor) => {
+                let compressed = compressor.update(data)?;
+                self.write_stored(&compressed).await
+            }
+            None => self.write_stored(data).await,
+        }
+    }
+
+    async fn write_stored(&mut self, data: &[u8]) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        match self.encryptor.as_mut() {
+            Some(encryptor) => {
+                let sealed = encryptor.update(data)?;
+                if !sealed.is_empty() {
+                    self.write_file(&sealed).await?;
+                }
+                Ok(())
+            }
+            None => self.write_file(data).await,
+        }
+    }
+
+    async fn write_file(&mut self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all(data).await?;
+        self.stored += data.len() as u64;
+        Ok(())
+    }
+
+    /// 返回已写入磁盘的存储字节数（压缩、加密后）。
+    pub fn stored_len(&self) -> u64 {
+        self.stored
+    }
+
+    /// 从内容读取器复制全部数据，返回复制的字节数。
+    pub async fn copy_from(&mut self, reader: &mut ContentReader) -> io::Result<u64> {
+        let mut offset = 0;
+        loop {
+            let chunk = reader.read_at(offset, READ_CHUNK_SIZE).await?;
+            if chunk.is_empty() {
+                return Ok(offset);
+            }
+            self.write_all(&chunk).await?;
+            offset += chunk.len() as u64;
+        }
+    }
+
+    /// 写入剩余数据并返回底层文件。
+    pub async fn finish(mut self) -> io::Result<File> {
+        if let Some(compressor) = self.compressor.take() {
+            let tail = compressor.finish()?;
+            self.write_stored(&tail).await?;
+        }
+        if let Some(encryptor) = self.encryptor.take() {
+            let sealed = encryptor.finish()?;
+            self.write_file(&sealed).await?;
+        }
+        self.file.flush().await?;
+        Ok(self.file)
+    }
+}
+
+/// 离线重加密统计信息。
+#[derive(Debug, Default)]
+pub struct ReencryptSummary {
+    pub scanned: u64,
+    pub reencrypted: u64,
+    pub failed: u64,
+}
+
+/// 使用当前密钥重新加密存储中所有未加密或使用旧密钥加密的文件。
+pub async fn reencrypt_storage(storage: &Storage, dry_run: bool) -> io::Result<ReencryptSummary> {
+    let keyring = storage
+        .keyring()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "encryption key is required"))?;
+    let current_id = keyring.current_id();
+    let mut summary = ReencryptSummary::default();
+    let mut pending: Vec<PathBuf> = vec![storage.root_path().to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                if dir != storage.root_path()
+                    || storage.internal_dir_name() != Some(entry.file_name().as_os_str())
+                {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+            summary.scanned += 1;
+            let relative = path
+                .strip_prefix(storage.root_path())
+                .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "path outside storage"))?
+                .to_string_lossy()
+                .to_string();
+            let opened = match storage.resolve_path_checked(&relative, false).await {
+                Ok(target) => match target.open_file().await {
+                    Ok(file) => ContentReader::new(file, storage.content_format())
+                        .await
+                        .map(|reader| (target, reader)),
+                    Err(err) => Err(err),
+                },
+                Err(_) => Err(io::Error::new(ErrorKind::InvalidInput, "invalid path")),
+            };
+            let (target, reader) = match opened {
+                Ok(opened) => opened,
+                Err(err) => {
+                    warn!(path = ?path, error = %err, "failed to open file for re-encryption");
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+            if reader.key_id() == Some(current_id.as_str()) {
+                continue;
+            }
+            if dry_run {
+                info!(path = ?path, "would re-encrypt file");
+                summary.reencrypted += 1;
+                continue;
+            }
+            match reencrypt_file(storage, &target, reader).await {
+                Ok(()) => {
+                    info!(path = ?path, "re-encrypted file");
+                    summary.reencrypted += 1;
+                }
+                Err(err) => {
+                    warn!(path = ?path, error = %err, "failed to re-encrypt file");
+                    summary.failed += 1;
+                }
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+async fn reencrypt_file(
+    storage: &Storage,
+    target: &ResolvedPath,
+    mut reader: ContentReader,
+) -> io::Result<()> {
+    // 保留原修改时间，避免同步客户端将重加密视为内容变更。
+    let modified = target.metadata().await?.modified()?;
+    let mut atomic = AtomicFile::new(storage, target)
+        .await
+        .map_err(|_| io::Error::other("failed to create temp file"))?
+        .with_modified(modified);
+    if let Err(err) = atomic.writer_mut().copy_from(&mut reader).await {
+        atomic.cleanup().await;
+        return Err(err);
+    }
+    drop(reader);
+    atomic
+        .finalize()
+        .await
+        .map_err(|_| io::Error::other("failed to replace file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn reencrypt_migrates_plain_and_old_key_files() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(root.join("nested")).expect("create dirs");
+        std::fs::write(root.join("plain.txt"), b"hello").expect("write plain");
+
+        let old = Storage::new(root.clone()).with_keyring(Some(Keyring::new(&[1u8; 32], &[])));
+        let target = old
+            .resolve_path_checked("nested/old.txt", false)
+            .await
+            .unwrap_or_else(|_| panic!("resolve"));
+        let mut atomic = AtomicFile::new(&old, &target)
+            .await
+            .unwrap_or_else(|_| panic!("atomic file"));
+        atomic
+            .writer_mut()
+            .write_all(b"secret")
+            .await
+            .expect("write");
+        atomic
+            .finalize()
+            .await
+            .unwrap_or_else(|_| panic!("finalize"));
+
+        let storage =
+            Storage::new(root.clone()).with_keyring(Some(Keyring::new(&[2u8; 32], &[[1u8; 32]])));
+        let summary = reencrypt_storage(&storage, false).await.expect("reencrypt");
+        assert_eq!(summary.scanned, 2);
+        assert_eq!(summary.reencrypted, 2);
+
+        let current_only = Keyring::new(&[2u8; 32], &[]);
+        for (name, expected) in [("plain.txt", &b"hello"[..]), ("nested/old.txt", b"secret")] {
+            let format = ContentFormat {
+                keyring: Some(&current_only),
+                compressed: false,
+            };
+            let mut reader = ContentReader::open(&root.join(name), format)
+                .await
+                .expect("open");
+            assert_eq!(reader.key_id(), Some(current_only.current_id().as_str()));
+            assert_eq!(reader.read_at(0, 64).await.expect("read"), expected);
+        }
+
+        let again = reencrypt_storage(&storage, false).await.expect("reencrypt");
+        assert_eq!(again.reencrypted, 0);
+    }
+}
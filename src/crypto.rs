@@ -0,0 +1,442 @@
+//! 静态加密：分段认证加密的文件格式与密钥环。
+//!
+//! 加密文件由固定长度的头部与若干 AES-256-GCM 分段组成。每个分段的 nonce
+//! 由头部中的随机前缀、分段序号与末段标记拼接而成，并以整个头部作为 AAD，
+//! 从而可以检测分段的截断、重排以及头部篡改，同时支持按分段随机读取。
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::{self, ErrorKind, SeekFrom};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::config::{Args, ENCRYPTION_SEGMENT_SIZE};
+
+/// 加密文件魔数（含版本号与保留字节）。
+const MAGIC: [u8; 8] = *b"AXOE\x01\x00\x00\x00";
+/// 加密文件头部长度。
+pub const HEADER_LEN: u64 = 32;
+/// 每个分段附带的认证标签长度。
+pub const TAG_LEN: u64 = 16;
+const KEY_ID_LEN: usize = 8;
+const NONCE_PREFIX_LEN: usize = 7;
+
+/// 单个加密密钥及其标识。
+#[derive(Clone)]
+struct KeyEntry {
+    id: [u8; KEY_ID_LEN],
+    cipher: Aes256Gcm,
+}
+
+impl KeyEntry {
+    fn new(key: &[u8; 32]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"axo-drive key id");
+        hasher.update(key);
+        let digest = hasher.finalize();
+        let mut id = [0u8; KEY_ID_LEN];
+        id.copy_from_slice(&digest[..KEY_ID_LEN]);
+        Self {
+            id,
+            cipher: Aes256Gcm::new(key.into()),
+        }
+    }
+}
+
+/// 当前加密密钥与用于解密旧文件的历史密钥。
+#[derive(Clone)]
+pub struct Keyring {
+    current: KeyEntry,
+    previous: Vec<KeyEntry>,
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("current", &hex_encode(&self.current.id))
+            .field("previous", &self.previous.len())
+            .finish()
+    }
+}
+
+impl Keyring {
+    /// 使用当前密钥与历史密钥创建密钥环。
+    pub fn new(current: &[u8; 32], previous: &[[u8; 32]]) -> Self {
+        Self {
+            current: KeyEntry::new(current),
+            previous: previous.iter().map(KeyEntry::new).collect(),
+        }
+    }
+
+    /// 根据 CLI 参数加载密钥环，未配置密钥时返回 None。
+    pub fn from_args(args: &Args) -> io::Result<Option<Self>> {
+        let current = match (&args.encryption_key, &args.encryption_key_file) {
+            (Some(_), Some(_)) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "encryption key and key file are mutually exclusive",
+                ));
+            }
+            (Some(value), None) => parse_key(value)?,
+            (None, Some(path)) => parse_key(&std::fs::read_to_string(Path::new(path))?)?,
+            (None, None) => {
+                if args.encryption_previous_keys.is_some() {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "previous encryption keys require a current key",
+                    ));
+                }
+                return Ok(None);
+            }
+        };
+        let previous = args
+            .encryption_previous_keys
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(parse_key)
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Some(Self::new(&current, &previous)))
+    }
+
+    /// 返回当前密钥的十六进制标识。
+    pub fn current_id(&self) -> String {
+        hex_encode(&self.current.id)
+    }
+
+    fn find(&self, id: &[u8]) -> Option<&KeyEntry> {
+        std::iter::once(&self.current)
+            .chain(self.previous.iter())
+            .find(|entry| entry.id == id)
+    }
+}
+
+/// 解析 64 位十六进制字符串形式的 256 位密钥。
+fn parse_key(value: &str) -> io::Result<[u8; 32]> {
+    let value = value.trim();
+    let invalid = || {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "encryption key must be 64 hex chars",
+        )
+    };
+    if value.len() != 64 || !value.is_ascii() {
+        return Err(invalid());
+    }
+    let mut key = [0u8; 32];
+    for (index, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(key)
+}
+
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn segment_nonce(prefix: &[u8], index: u64, last: bool) -> io::Result<[u8; 12]> {
+    let index = u32::try_from(index)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "too many segments"))?;
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = u8::from(last);
+    Ok(nonce)
+}
+
+/// 按分段加密写入数据的流式加密器。
+pub struct SegmentEncryptor {
+    cipher: Aes256Gcm,
+    header: [u8; HEADER_LEN as usize],
+    segment_size: usize,
+    index: u64,
+    pending: Vec<u8>,
+    header_written: bool,
+}
+
+impl SegmentEncryptor {
+    /// 使用密钥环中的当前密钥创建加密器。
+    pub fn new(keyring: &Keyring) -> Self {
+        let mut header = [0u8; HEADER_LEN as usize];
+        header[..8].copy_from_slice(&MAGIC);
+        header[8..16].copy_from_slice(&keyring.current.id);
+        header[16..20].copy_from_slice(&(ENCRYPTION_SEGMENT_SIZE as u32).to_le_bytes());
+        OsRng.fill_bytes(&mut header[20..20 + NONCE_PREFIX_LEN]);
+        Self {
+            cipher: keyring.current.cipher.clone(),
+            header,
+            segment_size: ENCRYPTION_SEGMENT_SIZE,
+            index: 0,
+            pending: Vec::with_capacity(ENCRYPTION_SEGMENT_SIZE),
+            header_written: false,
+        }
+    }
+
+    /// 追加明文，返回可写入磁盘的密文（可能为空）。
+    pub fn update(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = self.take_header();
+        self.pending.extend_from_slice(data);
+        // 末段需要在 finish 时才能确定，因此至少保留一个完整分段。
+        while self.pending.len() > self.segment_size {
+            let rest = self.pending.split_off(self.segment_size);
+            let segment = std::mem::replace(&mut self.pending, rest);
+            output.extend(self.seal(&segment, false)?);
+        }
+        Ok(output)
+    }
+
+    /// 加密剩余数据作为末段并结束加密。
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        let mut output = self.take_header();
+        let segment = std::mem::take(&mut self.pending);
+        output.extend(self.seal(&segment, true)?);
+        Ok(output)
+    }
+
+    fn take_header(&mut self) -> Vec<u8> {
+        if self.header_written {
+            return Vec::new();
+        }
+        self.header_written = true;
+        self.header.to_vec()
+    }
+
+    fn seal(&mut self, plaintext: &[u8], last: bool) -> io::Result<Vec<u8>> {
+        let nonce = segment_nonce(&self.header[20..20 + NONCE_PREFIX_LEN], self.index, last)?;
+        self.index += 1;
+        self.cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &self.header,
+                },
+            )
+            .map_err(|_| io::Error::other("segment encryption failed"))
+    }
+}
+
+/// 读取文件头部，若为加密文件则返回头部内容。
+pub async fn read_header(file: &mut File) -> io::Result<Option<[u8; HEADER_LEN as usize]>> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    file.seek(SeekFrom::Start(0)).await?;
+    let mut filled = 0;
+    while filled < header.len() {
+        let read = file.read(&mut header[filled..]).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        filled += read;
+    }
+    if header[..8] != MAGIC {
+        return Ok(None);
+    }
+    Ok(Some(header))
+}
+
+/// 根据密文长度与头部计算明文长度。
+pub fn plaintext_len(header: &[u8], stored_len: u64) -> io::Result<u64> {
+    let segment_size = header_segment_size(header)?;
+    let body = stored_len
+        .checked_sub(HEADER_LEN)
+        .filter(|body| *body >= TAG_LEN)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "truncated encrypted file"))?;
+    let segments = body.div_ceil(segment_size + TAG_LEN);
+    Ok(body - segments * TAG_LEN)
+}
+
+fn header_segment_size(header: &[u8]) -> io::Result<u64> {
+    let mut raw = [0u8; 4];
+    raw.copy_from_slice(&header[16..20]);
+    let size = u32::from_le_bytes(raw) as u64;
+    if size == 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "invalid segment size",
+        ));
+    }
+    Ok(size)
+}
+
+/// 支持按明文偏移随机读取的解密读取器。
+pub struct DecryptingReader {
+    file: File,
+    cipher: Aes256Gcm,
+    header: [u8; HEADER_LEN as usize],
+    key_id: String,
+    segment_size: u64,
+    segments: u64,
+    plain_len: u64,
+    cached: Option<(u64, Vec<u8>)>,
+}
+
+impl DecryptingReader {
+    /// 基于已读取的头部创建解密读取器。
+    pub fn new(
+        file: File,
+        header: [u8; HEADER_LEN as usize],
+        stored_len: u64,
+        keyring: &Keyring,
+    ) -> io::Result<Self> {
+        let key = keyring
+            .find(&header[8..16])
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "unknown encryption key id"))?;
+        let segment_size = header_segment_size(&header)?;
+        let plain_len = plaintext_len(&header, stored_len)?;
+        let segments = (stored_len - HEADER_LEN).div_ceil(segment_size + TAG_LEN);
+        Ok(Self {
+            file,
+            cipher: key.cipher.clone(),
+            header,
+            key_id: hex_encode(&key.id),
+            segment_size,
+            segments,
+            plain_len,
+            cached: None,
+        })
+    }
+
+    /// 返回明文总长度。
+    pub fn len(&self) -> u64 {
+        self.plain_len
+    }
+
+    /// 返回加密该文件所用密钥的十六进制标识。
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// 从明文偏移处读取最多 `max` 字节（不跨越分段边界），到达末尾时返回空。
+    pub async fn read_at(&mut self, offset: u64, max: usize) -> io::Result<Vec<u8>> {
+        if offset >= self.plain_len || max == 0 {
+            return Ok(Vec::new());
+        }
+        let index = offset / self.segment_size;
+        let skip = (offset % self.segment_size) as usize;
+        let segment = self.segment(index).await?;
+        let end = segment.len().min(skip + max);
+        Ok(segment[skip.min(end)..end].to_vec())
+    }
+
+    async fn segment(&mut self, index: u64) -> io::Result<&[u8]> {
+        let cached = matches!(&self.cached, Some((cached, _)) if *cached == index);
+        if !cached {
+            let stored_segment = self.segment_size + TAG_LEN;
+            let start = HEADER_LEN + index * stored_segment;
+            let last = index + 1 == self.segments;
+            let expected = stored_segment as usize;
+            let mut buffer = vec![0u8; expected];
+            self.file.seek(SeekFrom::Start(start)).await?;
+            let mut filled = 0;
+            while filled < buffer.len() {
+                let read = self.file.read(&mut buffer[filled..]).await?;
+                if read == 0 {
+                    break;
+                }
+                filled += read;
+            }
+            buffer.truncate(filled);
+            if (last && filled < TAG_LEN as usize) || (!last && filled != expected) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "truncated encrypted segment",
+                ));
+            }
+            let nonce = segment_nonce(&self.header[20..20 + NONCE_PREFIX_LEN], index, last)?;
+            let plaintext = self
+                .cipher
+                .decrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &buffer,
+                        aad: &self.header,
+                    },
+                )
+                .map_err(|_| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        "encrypted segment authentication failed",
+                    )
+                })?;
+            self.cached = Some((index, plaintext));
+        }
+        Ok(self
+            .cached
+            .as_ref()
+            .map(|(_, data)| data.as_slice())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio::io::AsyncWriteExt;
+
+    async fn encrypt_to_file(path: &Path, keyring: &Keyring, data: &[u8]) {
+        let mut encryptor = SegmentEncryptor::new(keyring);
+        let mut file = File::create(path).await.expect("create");
+        for chunk in data.chunks(1000) {
+            let out = encryptor.update(chunk).expect("update");
+            file.write_all(&out).await.expect("write");
+        }
+        file.write_all(&encryptor.finish().expect("finish"))
+            .await
+            .expect("write");
+    }
+
+    async fn open_reader(path: &Path, keyring: &Keyring) -> io::Result<DecryptingReader> {
+        let mut file = File::open(path).await?;
+        let len = file.metadata().await?.len();
+        let header = read_header(&mut file).await?.expect("encrypted header");
+        DecryptingReader::new(file, header, len, keyring)
+    }
+
+    #[tokio::test]
+    async fn round_trip_reads_ranges_across_segments() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("data.bin");
+        let keyring = Keyring::new(&[7u8; 32], &[]);
+        let data: Vec<u8> = (0..ENCRYPTION_SEGMENT_SIZE * 2 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        encrypt_to_file(&path, &keyring, &data).await;
+
+        let mut reader = open_reader(&path, &keyring).await.expect("reader");
+        assert_eq!(reader.len(), data.len() as u64);
+        let offset = ENCRYPTION_SEGMENT_SIZE as u64 - 10;
+        let chunk = reader.read_at(offset, 100).await.expect("read");
+        assert_eq!(chunk, data[offset as usize..offset as usize + 10]);
+        let tail = reader
+            .read_at(data.len() as u64 - 3, 100)
+            .await
+            .expect("read tail");
+        assert_eq!(tail, data[data.len() - 3..]);
+    }
+
+    #[tokio::test]
+    async fn previous_key_decrypts_and_truncation_is_detected() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("data.bin");
+        let old = Keyring::new(&[1u8; 32], &[]);
+        let data = vec![42u8; ENCRYPTION_SEGMENT_SIZE + 5];
+        encrypt_to_file(&path, &old, &data).await;
+
+        let rotated = Keyring::new(&[2u8; 32], &[[1u8; 32]]);
+        let mut reader = open_reader(&path, &rotated).await.expect("reader");
+        assert_eq!(reader.read_at(0, 4).await.expect("read"), vec![42u8; 4]);
+
+        let stored = std::fs::read(&path).expect("read file");
+        let cut = HEADER_LEN as usize + ENCRYPTION_SEGMENT_SIZE + TAG_LEN as usize;
+        std::fs::write(&path, &stored[..cut]).expect("truncate");
+        let mut reader = open_reader(&path, &rotated).await.expect("reader");
+        assert!(reader.read_at(0, 4).await.is_err());
+    }
+}
@@ -0,0 +1,820 @@
+//! WebDAV 文件系统封装：基于目录句柄操作存储并透明处理存储内容格式。
+//!
+//! 每个操作先按存储的符号链接策略把路径解析为目录句柄，之后的读写、
+//! 创建、删除与移动都相对于该句柄进行；文件读写与长度元数据经由
+//! `content` 模块转换，使 WebDAV 客户端看到的始终是原始内容。
+//! 配置命名卷时，虚拟根目录列出各卷并按路径首段路由到对应卷。
+
+use bytes::{Buf, Bytes};
+use dav_server::davpath::DavPath;
+use dav_server::fs::{
+    DavDirEntry, DavFile, DavFileSystem, DavMetaData, FsError, FsFuture, FsResult, FsStream,
+    OpenOptions, ReadDirMeta,
+};
+use futures_util::stream;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::Metadata;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::Component;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::atomic::AtomicFile;
+use crate::beneath::WriteOptions;
+use crate::content::ContentReader;
+use crate::error::ApiError;
+use crate::storage::{ResolvedPath, Storage, StorageError};
+use crate::volumes::{Volume, Volumes};
+
+/// 按卷配置创建 WebDAV 文件系统：单卷直接挂载，命名卷以虚拟根目录列出。
+pub fn build_dav_filesystem(volumes: &Volumes) -> Box<dyn dav_server::fs::GuardedFileSystem<()>> {
+    match volumes.single_volume() {
+        Some(volume) => AxoDavFs::new(volume.clone()),
+        None => Box::new(VolumesDavFs {
+            volumes: volumes
+                .iter()
+                .map(|volume| (volume.name().to_string(), AxoDavFs::new(volume.clone())))
+                .collect(),
+            created: SystemTime::now(),
+        }),
+    }
+}
+
+/// 基于目录句柄、按存储配置转换内容的单卷 WebDAV 文件系统。
+///
+/// 路径先按符号链接策略解析为目录句柄与末端名称，随后的读写、创建、
+/// 删除与重命名都相对于该句柄进行，不再按路径重新打开。
+#[derive(Clone)]
+pub struct AxoDavFs {
+    volume: Arc<Volume>,
+}
+
+impl AxoDavFs {
+    /// 创建以卷根目录为基准的 WebDAV 文件系统。
+    pub fn new(volume: Arc<Volume>) -> Box<Self> {
+        Box::new(Self { volume })
+    }
+
+    fn storage(&self) -> &Storage {
+        self.volume.storage()
+    }
+
+    /// 拒绝访问卷内的上传临时目录。
+    fn check_visible(&self, path: &DavPath) -> FsResult<()> {
+        let first = path.as_rel_ospath().components().next();
+        if let (Some(Component::Normal(first)), Some(internal)) =
+            (first, self.storage().internal_dir_name())
+            && first == internal
+        {
+            return Err(FsError::NotFound);
+        }
+        Ok(())
+    }
+
+    fn check_writable(&self, path: &DavPath) -> FsResult<()> {
+        self.check_visible(path)?;
+        if self.volume.is_read_only() {
+            return Err(FsError::Forbidden);
+        }
+        Ok(())
+    }
+
+    /// 按存储的符号链接策略解析路径（跟随策略下包括末端链接），返回解析
+    /// 结果与末端元数据（不存在时为 None）。越界或被拒绝的链接返回
+    /// Forbidden；`allow_link` 为真时允许末端是 `show` 策略下展示的链接本身。
+    async fn lookup(
+        &self,
+        path: &DavPath,
+        allow_link: bool,
+    ) -> FsResult<(ResolvedPath, Option<Metadata>)> {
+        let resolved = self
+            .storage()
+            .resolve_path_checked(&relative_path(path), false)
+            .await
+            .map_err(storage_error)?;
+        entry_metadata(resolved, allow_link).await
+    }
+
+    /// 解析路径但不跟随末端链接，用于删除、重命名等作用于目录项本身的操作。
+    async fn lookup_entry(&self, path: &DavPath) -> FsResult<(ResolvedPath, Option<Metadata>)> {
+        let resolved = self
+            .storage()
+            .resolve_link_checked(&relative_path(path))
+            .await
+            .map_err(storage_error)?;
+        entry_metadata(resolved, true).await
+    }
+
+    /// 生成客户端可见的元数据：`show` 策略下的链接长度为 0，文件为内容长度。
+    async fn content_metadata(
+        &self,
+        target: &ResolvedPath,
+        metadata: Metadata,
+    ) -> Box<dyn DavMetaData> {
+        let len = if metadata.file_type().is_symlink() {
+            0
+        } else {
+            self.storage().content_len(target, &metadata).await
+        };
+        Box::new(DiskMetaData { metadata, len })
+    }
+
+    /// 校验写入 `added` 字节（替换释放 `released` 字节）后卷不超出配额。
+    async fn check_quota(&self, added: u64, released: u64) -> FsResult<()> {
+        self.volume
+            .check_quota(added, released)
+            .await
+            .map_err(storage_error)
+    }
+}
+
+fn relative_path(path: &DavPath) -> String {
+    path.as_rel_ospath().to_string_lossy().to_string()
+}
+
+async fn entry_metadata(
+    resolved: ResolvedPath,
+    allow_link: bool,
+) -> FsResult<(ResolvedPath, Option<Metadata>)> {
+    match resolved.metadata().await {
+        Ok(metadata) if metadata.file_type().is_symlink() && !allow_link => Err(FsError::Forbidden),
+        Ok(metadata) => Ok((resolved, Some(metadata))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok((resolved, None)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn entry_name(target: &ResolvedPath) -> FsResult<&OsStr> {
+    target.name().ok_or(FsError::Forbidden)
+}
+
+impl DavFileSystem for AxoDavFs {
+    fn open<'a>(
+        &'a self,
+        path: &'a DavPath,
+        options: OpenOptions,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        Box::pin(async move {
+            let storage = self.storage();
+            if options.write {
+                self.check_writable(path)?;
+                let (target, existing) = self.lookup(path, false).await?;
+                let released = existing
+                    .as_ref()
+                    .filter(|metadata| metadata.is_file())
+                    .map_or(0, |metadata| metadata.len());
+                self.check_quota(0, released).await?;
+                let compression_level = storage.compression_level(target.path());
+                let quota = self.volume.quota().is_some();
+                if storage.keyring().is_none() && compression_level.is_none() && !quota {
+                    let options = WriteOptions {
+                        append: options.append,
+                        truncate: options.truncate,
+                        create: options.create,
+                        create_new: options.create_new,
+                    };
+                    let file = target
+                        .dir()
+                        .open_write_file(entry_name(&target)?, options)
+                        .await?;
+                    return Ok(Box::new(DiskDavFile { file }) as Box<dyn DavFile>);
+                }
+                // 压缩、分段加密与配额校验只支持从头顺序写入完整文件。
+                if options.append || !options.truncate {
+                    return Err(FsError::NotImplemented);
+                }
+                match existing {
+                    Some(_) if options.create_new => return Err(FsError::Exists),
+                    Some(metadata) if metadata.is_dir() => return Err(FsError::Forbidden),
+                    None if !options.create && !options.create_new => {
+                        return Err(FsError::NotFound);
+                    }
+                    _ => {}
+                }
+                // 写入临时文件，flush 时再原子替换，中途失败不会截断原文件。
+                let atomic = AtomicFile::new(storage, &target)
+                    .await
+                    .map_err(fs_error)?
+                    .with_volume(&self.volume);
+                return Ok(Box::new(ContentDavWriter {
+                    target,
+                    volume: self.volume.clone(),
+                    atomic: Some(atomic),
+                    released,
+                    written: 0,
+                }) as Box<dyn DavFile>);
+            }
+            self.check_visible(path)?;
+            let (target, metadata) = self.lookup(path, false).await?;
+            let metadata = metadata.ok_or(FsError::NotFound)?;
+            if !metadata.is_file() {
+                return Err(FsError::Forbidden);
+            }
+            let reader =
+                ContentReader::new(target.open_file().await?, storage.content_format()).await?;
+            Ok(Box::new(ContentDavReader {
+                metadata,
+                reader,
+                position: 0,
+            }) as Box<dyn DavFile>)
+        })
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        _meta: ReadDirMeta,
+    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        Box::pin(async move {
+            self.check_visible(path)?;
+            let dir = self
+                .storage()
+                .open_dir(Some(&relative_path(path)), false)
+                .await
+                .map_err(|err| match err {
+                    StorageError::InvalidPath => FsError::Forbidden,
+                    _ => FsError::NotFound,
+                })?;
+            let at_root = dir.path() == self.storage().root_path();
+            let mut entries = Vec::new();
+            for name in dir.read_dir().await? {
+                if self.storage().is_hidden(at_root, &name) {
+                    continue;
+                }
+                // 按符号链接策略隐藏链接条目或替换为解析后的目标。
+                let Ok(Some((target, metadata))) = self.storage().resolve_entry(&dir, &name).await
+                else {
+                    continue;
+                };
+                entries.push(Ok(Box::new(ContentDirEntry {
+                    fs: self.clone(),
+                    name,
+                    target,
+                    metadata,
+                }) as Box<dyn DavDirEntry>));
+            }
+            let stream = stream::iter(entries);
+            Ok(Box::pin(stream) as FsStream<Box<dyn DavDirEntry>>)
+        })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        Box::pin(async move {
+            self.check_visible(path)?;
+            let (target, metadata) = self.lookup(path, true).await?;
+            let metadata = metadata.ok_or(FsError::NotFound)?;
+            Ok(self.content_metadata(&target, metadata).await)
+        })
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            self.check_writable(path)?;
+            let (target, existing) = self.lookup_entry(path).await?;
+            if existing.is_some() {
+                return Err(FsError::Exists);
+            }
+            Ok(target.dir().create_dir(entry_name(&target)?).await?)
+        })
+    }
+
+    fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            self.check_writable(path)?;
+            let (target, existing) = self.lookup_entry(path).await?;
+            existing.ok_or(FsError::NotFound)?;
+            Ok(target.dir().remove_dir(entry_name(&target)?).await?)
+        })
+    }
+
+    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            self.check_writable(path)?;
+            let (target, existing) = self.lookup_entry(path).await?;
+            existing.ok_or(FsError::NotFound)?;
+            Ok(target.dir().remove_file(entry_name(&target)?).await?)
+        })
+    }
+
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            self.check_writable(from)?;
+            self.check_writable(to)?;
+            let (source, existing) = self.lookup_entry(from).await?;
+            existing.ok_or(FsError::NotFound)?;
+            let (target, _) = self.lookup_entry(to).await?;
+            Ok(source
+                .dir()
+                .rename_to(entry_name(&source)?, target.dir(), entry_name(&target)?)
+                .await?)
+        })
+    }
+
+    fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            self.check_visible(from)?;
+            self.check_writable(to)?;
+            let (source, metadata) = self.lookup(from, false).await?;
+            let metadata = metadata.ok_or(FsError::NotFound)?;
+            let (target, existing) = self.lookup(to, false).await?;
+            if !metadata.is_file() || existing.as_ref().is_some_and(|metadata| metadata.is_dir()) {
+                return Err(FsError::Forbidden);
+            }
+            let released = existing.map_or(0, |metadata| metadata.len());
+            self.check_quota(metadata.len(), released).await?;
+            // 按目标位置的存储配置重新编码，复制结果与直接写入一致。
+            let storage = self.storage();
+            let mut reader =
+                ContentReader::new(source.open_file().await?, storage.content_format()).await?;
+            let mut atomic = AtomicFile::new(storage, &target)
+                .await
+                .map_err(fs_error)?
+                .with_volume(&self.volume);
+            if let Err(err) = atomic.writer_mut().copy_from(&mut reader).await {
+                atomic.cleanup().await;
+                return Err(err.into());
+            }
+            atomic.finalize().await.map_err(fs_error)
+        })
+    }
+
+    fn get_quota(&self) -> FsFuture<'_, (u64, Option<u64>)> {
+        Box::pin(async move {
+            let quota = self.volume.quota().ok_or(FsError::NotImplemented)?;
+            let used = self
+                .volume
+                .used_bytes()
+                .await
+                .map_err(|_| FsError::GeneralFailure)?;
+            Ok((used, Some(quota)))
+        })
+    }
+}
+
+/// 以虚拟根目录挂载多个命名卷的 WebDAV 文件系统。
+#[derive(Clone)]
+struct VolumesDavFs {
+    volumes: Vec<(String, Box<AxoDavFs>)>,
+    created: SystemTime,
+}
+
+impl VolumesDavFs {
+    /// 将路径拆分为所属卷与卷内路径，根目录返回 None。
+    fn route(&self, path: &DavPath) -> FsResult<Option<(&AxoDavFs, DavPath)>> {
+        let url = path.as_url_string();
+        let trimmed = url.trim_start_matches('/');
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+        let (name, rest) = match trimmed.find('/') {
+            Some(index) => trimmed.split_at(index),
+            None => (trimmed, "/"),
+        };
+        let fs = self
+            .volumes
+            .iter()
+            .find(|(volume, _)| volume == name)
+            .map(|(_, fs)| fs.as_ref())
+            .ok_or(FsError::NotFound)?;
+        let inner = DavPath::new(rest).map_err(|_| FsError::GeneralFailure)?;
+        Ok(Some((fs, inner)))
+    }
+
+    /// 路由到卷内且不是卷根目录本身的路径，用于修改操作。
+    fn route_entry(&self, path: &DavPath) -> FsResult<(&AxoDavFs, DavPath)> {
+        match self.route(path)? {
+            Some((fs, inner)) if !inner.as_rel_ospath().as_os_str().is_empty() => Ok((fs, inner)),
+            _ => Err(FsError::Forbidden),
+        }
+    }
+
+    fn root_metadata(&self) -> Box<dyn DavMetaData> {
+        Box::new(VirtualDirMetaData {
+            modified: self.created,
+        })
+    }
+}
+
+impl DavFileSystem for VolumesDavFs {
+    fn open<'a>(
+        &'a self,
+        path: &'a DavPath,
+        options: OpenOptions,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        Box::pin(async move {
+            let (fs, inner) = self.route_entry(path)?;
+            fs.open(&inner, options).await
+        })
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        meta: ReadDirMeta,
+    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        Box::pin(async move {
+            if let Some((fs, inner)) = self.route(path)? {
+                return fs.read_dir(&inner, meta).await;
+            }
+            let entries: Vec<FsResult<Box<dyn DavDirEntry>>> = self
+                .volumes
+                .iter()
+                .map(|(name, fs)| {
+                    Ok(Box::new(VolumeDirEntry {
+                        name: name.clone(),
+                        fs: fs.clone(),
+                    }) as Box<dyn DavDirEntry>)
+                })
+                .collect();
+            Ok(Box::pin(stream::iter(entries)) as FsStream<Box<dyn DavDirEntry>>)
+        })
+    }
+
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        Box::pin(async move {
+            match self.route(path)? {
+                Some((fs, inner)) => fs.metadata(&inner).await,
+                None => Ok(self.root_metadata()),
+            }
+        })
+    }
+
+    fn symlink_metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        Box::pin(async move {
+            match self.route(path)? {
+                Some((fs, inner)) => fs.symlink_metadata(&inner).await,
+                None => Ok(self.root_metadata()),
+            }
+        })
+    }
+
+    fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            let (fs, inner) = self.route_entry(path)?;
+            fs.create_dir(&inner).await
+        })
+    }
+
+    fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            let (fs, inner) = self.route_entry(path)?;
+            fs.remove_dir(&inner).await
+        })
+    }
+
+    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            let (fs, inner) = self.route_entry(path)?;
+            fs.remove_file(&inner).await
+        })
+    }
+
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            let (from_fs, from) = self.route_entry(from)?;
+            let (to_fs, to) = self.route_entry(to)?;
+            // 跨卷移动无法保证原子性。
+            if !Arc::ptr_eq(&from_fs.volume, &to_fs.volume) {
+                return Err(FsError::NotImplemented);
+            }
+            from_fs.rename(&from, &to).await
+        })
+    }
+
+    fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        Box::pin(async move {
+            let (from_fs, from) = self.route_entry(from)?;
+            let (to_fs, to) = self.route_entry(to)?;
+            if !Arc::ptr_eq(&from_fs.volume, &to_fs.volume) {
+                return Err(FsError::NotImplemented);
+            }
+            from_fs.copy(&from, &to).await
+        })
+    }
+}
+
+/// 虚拟根目录下代表一个卷的目录项。
+struct VolumeDirEntry {
+    name: String,
+    fs: Box<AxoDavFs>,
+}
+
+impl DavDirEntry for VolumeDirEntry {
+    fn name(&self) -> Vec<u8> {
+        self.name.as_bytes().to_vec()
+    }
+
+    fn metadata(&self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        Box::pin(async move {
+            let root = DavPath::new("/").map_err(|_| FsError::GeneralFailure)?;
+            self.fs.metadata(&root).await
+        })
+    }
+
+    fn is_dir(&self) -> FsFuture<'_, bool> {
+        Box::pin(async { Ok(true) })
+    }
+}
+
+/// 虚拟根目录的元数据。
+#[derive(Clone, Debug)]
+struct VirtualDirMetaData {
+    modified: SystemTime,
+}
+
+impl DavMetaData for VirtualDirMetaData {
+    fn len(&self) -> u64 {
+        0
+    }
+
+    fn modified(&self) -> FsResult<SystemTime> {
+        Ok(self.modified)
+    }
+
+    fn is_dir(&self) -> bool {
+        true
+    }
+}
+
+/// 按符号链接策略解析后的目录项（链接呈现为跟随后的目标或 `show` 下的链接自身）。
+struct ContentDirEntry {
+    fs: AxoDavFs,
+    name: OsString,
+    target: ResolvedPath,
+    metadata: Metadata,
+}
+
+impl DavDirEntry for ContentDirEntry {
+    fn name(&self) -> Vec<u8> {
+        self.name.as_encoded_bytes().to_vec()
+    }
+
+    fn metadata(&self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        Box::pin(async move {
+            Ok(self
+                .fs
+                .content_metadata(&self.target, self.metadata.clone())
+                .await)
+        })
+    }
+
+    fn is_dir(&self) -> FsFuture<'_, bool> {
+        let is_dir = self.metadata.is_dir();
+        Box::pin(async move { Ok(is_dir) })
+    }
+
+    fn is_file(&self) -> FsFuture<'_, bool> {
+        let is_dir = self.metadata.is_dir();
+        Box::pin(async move { Ok(!is_dir) })
+    }
+
+    fn is_symlink(&self) -> FsFuture<'_, bool> {
+        // 链接条目以解析后的形态呈现，避免 PROPFIND 将其跳过。
+        Box::pin(async { Ok(false) })
+    }
+}
+
+/// 基于磁盘元数据、长度替换为内容长度的文件元数据。
+#[derive(Clone, Debug)]
+struct DiskMetaData {
+    metadata: std::fs::Metadata,
+    len: u64,
+}
+
+impl DavMetaData for DiskMetaData {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn modified(&self) -> FsResult<SystemTime> {
+        Ok(self.metadata.modified()?)
+    }
+
+    fn is_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+
+    fn accessed(&self) -> FsResult<SystemTime> {
+        Ok(self.metadata.accessed()?)
+    }
+
+    fn created(&self) -> FsResult<SystemTime> {
+        Ok(self.metadata.created()?)
+    }
+}
+
+/// 读取并解码存储内容的 WebDAV 文件句柄。
+struct ContentDavReader {
+    metadata: Metadata,
+    reader: ContentReader,
+    position: u64,
+}
+
+impl fmt::Debug for ContentDavReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContentDavReader")
+            .field("len", &self.reader.len())
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+impl DavFile for ContentDavReader {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let metadata = DiskMetaData {
+            metadata: self.metadata.clone(),
+            len: self.reader.len(),
+        };
+        Box::pin(async move { Ok(Box::new(metadata) as Box<dyn DavMetaData>) })
+    }
+
+    fn write_buf(&mut self, _buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        Box::pin(async { Err(FsError::Forbidden) })
+    }
+
+    fn write_bytes(&mut self, _buf: Bytes) -> FsFuture<'_, ()> {
+        Box::pin(async { Err(FsError::Forbidden) })
+    }
+
+    fn read_bytes(&mut self, count: usize) -> FsFuture<'_, Bytes> {
+        Box::pin(async move {
+            let chunk = self.reader.read_at(self.position, count).await?;
+            self.position += chunk.len() as u64;
+            Ok(Bytes::from(chunk))
+        })
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+        Box::pin(async move {
+            let target = match pos {
+                SeekFrom::Start(offset) => Some(offset),
+                SeekFrom::End(delta) => self.reader.len().checked_add_signed(delta),
+                SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            };
+            self.position = target.ok_or(FsError::GeneralFailure)?;
+            Ok(self.position)
+        })
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// 将存储错误映射为 WebDAV 文件系统错误。
+fn storage_error(err: StorageError) -> FsError {
+    match err {
+        StorageError::InvalidPath | StorageError::ReadOnly => FsError::Forbidden,
+        StorageError::QuotaExceeded => FsError::InsufficientStorage,
+        StorageError::Io(err) if err.kind() == ErrorKind::NotFound => FsError::NotFound,
+        _ => FsError::GeneralFailure,
+    }
+}
+
+/// 将接口错误映射为 WebDAV 文件系统错误。
+fn fs_error(err: ApiError) -> FsError {
+    match err {
+        ApiError::InsufficientStorage(_) => FsError::InsufficientStorage,
+        ApiError::Forbidden(_) => FsError::Forbidden,
+        _ => FsError::GeneralFailure,
+    }
+}
+
+/// 按存储格式顺序写入临时文件、flush 时原子替换目标的 WebDAV 文件句柄。
+///
+/// 未 flush 即被丢弃（如请求体中断）时目标保持不变，遗留的临时文件由
+/// 定期清理任务删除。卷配置了配额时写入过程中即拒绝超出配额的内容，
+/// 替换前再在锁内最终校验。
+struct ContentDavWriter {
+    target: ResolvedPath,
+    volume: Arc<Volume>,
+    atomic: Option<AtomicFile>,
+    /// 将被替换的原文件大小。
+    released: u64,
+    written: u64,
+}
+
+impl fmt::Debug for ContentDavWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContentDavWriter")
+            .field("target", &self.target.path())
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
+impl ContentDavWriter {
+    async fn write(&mut self, data: &[u8]) -> FsResult<()> {
+        let atomic = self.atomic.as_mut().ok_or(FsError::GeneralFailure)?;
+        atomic.writer_mut().write_all(data).await?;
+        self.written += data.len() as u64;
+        let stored = atomic.writer_mut().stored_len();
+        if let Err(err) = self.volume.check_quota(stored, self.released).await {
+            if let Some(atomic) = self.atomic.take() {
+                atomic.cleanup().await;
+            }
+            return Err(storage_error(err));
+        }
+        Ok(())
+    }
+}
+
+impl DavFile for ContentDavWriter {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        Box::pin(async move {
+            let metadata = self.target.metadata().await?;
+            Ok(Box::new(DiskMetaData {
+                metadata,
+                len: self.written,
+            }) as Box<dyn DavMetaData>)
+        })
+    }
+
+    fn write_buf(&mut self, mut buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        Box::pin(async move {
+            while buf.has_remaining() {
+                let chunk = buf.chunk().to_vec();
+                self.write(&chunk).await?;
+                buf.advance(chunk.len());
+            }
+            Ok(())
+        })
+    }
+
+    fn write_bytes(&mut self, buf: Bytes) -> FsFuture<'_, ()> {
+        Box::pin(async move { self.write(&buf).await })
+    }
+
+    fn read_bytes(&mut self, _count: usize) -> FsFuture<'_, Bytes> {
+        Box::pin(async { Err(FsError::Forbidden) })
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+        Box::pin(async move {
+            match pos {
+                SeekFrom::Start(offset) if offset == self.written => Ok(self.written),
+                SeekFrom::Current(0) => Ok(self.written),
+                _ => Err(FsError::NotImplemented),
+            }
+        })
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        Box::pin(async move {
+            match self.atomic.take() {
+                Some(atomic) => atomic.finalize().await.map_err(fs_error),
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+/// 未启用内容转换与配额时按原样读写磁盘文件的 WebDAV 文件句柄。
+#[derive(Debug)]
+struct DiskDavFile {
+    file: File,
+}
+
+impl DavFile for DiskDavFile {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        Box::pin(async move {
+            let metadata = self.file.metadata().await?;
+            let len = metadata.len();
+            Ok(Box::new(DiskMetaData { metadata, len }) as Box<dyn DavMetaData>)
+        })
+    }
+
+    fn write_buf(&mut self, mut buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+        Box::pin(async move {
+            while buf.has_remaining() {
+                let written = self.file.write(buf.chunk()).await?;
+                buf.advance(written);
+            }
+            Ok(())
+        })
+    }
+
+    fn write_bytes(&mut self, buf: Bytes) -> FsFuture<'_, ()> {
+        Box::pin(async move { Ok(self.file.write_all(&buf).await?) })
+    }
+
+    fn read_bytes(&mut self, count: usize) -> FsFuture<'_, Bytes> {
+        Box::pin(async move {
+            let mut buffer = vec![0u8; count];
+            let read = self.file.read(&mut buffer).await?;
+            buffer.truncate(read);
+            Ok(Bytes::from(buffer))
+        })
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+        Box::pin(async move { Ok(self.file.seek(pos).await?) })
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        Box::pin(async move { Ok(self.file.flush().await?) })
+    }
+}
@@ -15,6 +15,8 @@ pub enum ApiError {
     PreconditionFailed(String),
     Conflict(String),
     TooManyRequests(u64),
+    Forbidden(String),
+    InsufficientStorage(String),
 }
 
 impl IntoResponse for ApiError {
@@ -42,6 +44,10 @@ impl IntoResponse for ApiError {
                 (StatusCode::PRECONDITION_FAILED, msg).into_response()
             }
             ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg).into_response(),
+            ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg).into_response(),
+            ApiError::InsufficientStorage(msg) => {
+                (StatusCode::INSUFFICIENT_STORAGE, msg).into_response()
+            }
             ApiError::TooManyRequests(retry_after) => {
                 let mut headers = HeaderMap::new();
                 if retry_after > 0
@@ -59,6 +65,10 @@ impl From<StorageError> for ApiError {
     fn from(error: StorageError) -> Self {
         match error {
             StorageError::InvalidPath => ApiError::BadRequest("invalid path".into()),
+            StorageError::ReadOnly => ApiError::Forbidden("volume is read-only".into()),
+            StorageError::QuotaExceeded => {
+                ApiError::InsufficientStorage("volume quota exceeded".into())
+            }
             StorageError::Io(err) => match err.kind() {
                 ErrorKind::NotFound => ApiError::NotFound(err.to_string()),
                 _ => ApiError::Internal(err.to_string()),
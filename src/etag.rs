@@ -1,20 +1,23 @@
 //! ETag 计算与条件请求校验。
 
 use axum::http::{HeaderMap, header};
+use httpdate::parse_http_date;
 use std::fs::Metadata;
-use std::time::UNIX_EPOCH;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::error::ApiError;
 
-/// 根据文件元数据生成弱 ETag。
-pub fn etag_from_metadata(metadata: &Metadata) -> String {
-    let size = metadata.len();
+/// 根据文件元数据与客户端可见内容长度生成 ETag。
+///
+/// 写入总会更新纳秒级修改时间，长度与修改时间均未变时内容视为未变，因此生成强 ETag，
+/// 可用于 If-Range 续传；无法取得修改时间时退回仅含长度的弱 ETag。
+pub fn etag_from_metadata(metadata: &Metadata, size: u64) -> String {
     let modified = metadata.modified().ok();
     if let Some(modified) = modified
         && let Ok(duration) = modified.duration_since(UNIX_EPOCH)
     {
         return format!(
-            "W/\"{}-{}-{}\"",
+            "\"{}-{}-{}\"",
             size,
             duration.as_secs(),
             duration.subsec_nanos()
@@ -29,32 +32,136 @@ pub fn check_preconditions(
     current_etag: Option<&str>,
     exists: bool,
 ) -> Result<(), ApiError> {
-    if let Some(value) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) {
+    check_if_match(headers, current_etag, exists)?;
+
+    if let Some(value) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+    {
         if value.trim() == "*" {
-            if !exists {
+            if exists {
                 return Err(ApiError::PreconditionFailed("precondition failed".into()));
             }
-        } else if !etag_matches(value, current_etag) {
+        } else if etag_matches(value, current_etag) {
             return Err(ApiError::PreconditionFailed("precondition failed".into()));
         }
     }
 
-    if let Some(value) = headers
+    Ok(())
+}
+
+/// 读取请求的条件校验结果。
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReadCondition {
+    /// 需要返回内容。
+    Modified,
+    /// 客户端缓存仍然有效，应返回 304。
+    NotModified,
+}
+
+/// 按 RFC 9110 13.2.2 的顺序校验 GET/HEAD 的条件请求头。
+///
+/// If-Match / If-Unmodified-Since 不满足时返回 412；If-None-Match 命中，
+/// 或未携带 If-None-Match 且 If-Modified-Since 之后未修改时返回 `NotModified`。
+pub fn check_read_preconditions(
+    headers: &HeaderMap,
+    current_etag: &str,
+    modified: Option<SystemTime>,
+) -> Result<ReadCondition, ApiError> {
+    check_if_match(headers, Some(current_etag), true)?;
+    if !headers.contains_key(header::IF_MATCH)
+        && let Some(date) = header_date(headers, header::IF_UNMODIFIED_SINCE)
+        && modified.is_some_and(|modified| unix_secs(modified) > unix_secs(date))
+    {
+        return Err(ApiError::PreconditionFailed("precondition failed".into()));
+    }
+
+    if headers.contains_key(header::IF_NONE_MATCH) {
+        return Ok(if none_match(headers, current_etag) {
+            ReadCondition::Modified
+        } else {
+            ReadCondition::NotModified
+        });
+    }
+    if let Some(date) = header_date(headers, header::IF_MODIFIED_SINCE)
+        && modified.is_some_and(|modified| unix_secs(modified) <= unix_secs(date))
+    {
+        return Ok(ReadCondition::NotModified);
+    }
+    Ok(ReadCondition::Modified)
+}
+
+/// 判断 If-Range 是否仍然有效（未携带时视为有效）。
+///
+/// 取值可以是 ETag 或 HTTP 日期；按 RFC 9110 13.1.5 使用强比较，弱 ETag
+/// 一律视为不匹配（返回完整内容），强 ETag 需与当前值完全一致，日期需不早于
+/// 修改时间。
+pub fn if_range_matches(
+    headers: &HeaderMap,
+    current_etag: &str,
+    modified: Option<SystemTime>,
+) -> bool {
+    let Some(value) = headers
+        .get(header::IF_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+    else {
+        return true;
+    };
+    if value.starts_with("W/") {
+        return false;
+    }
+    if value.starts_with('"') {
+        return !current_etag.starts_with("W/") && value == current_etag;
+    }
+    match parse_http_date(value) {
+        Ok(date) => modified.is_some_and(|modified| unix_secs(modified) <= unix_secs(date)),
+        Err(_) => false,
+    }
+}
+
+/// 判断 If-None-Match 是否不匹配当前 ETag（即需要返回完整内容）。
+pub fn none_match(headers: &HeaderMap, current_etag: &str) -> bool {
+    match headers
         .get(header::IF_NONE_MATCH)
         .and_then(|v| v.to_str().ok())
     {
+        Some(value) => value.trim() != "*" && !etag_matches(value, Some(current_etag)),
+        None => true,
+    }
+}
+
+fn check_if_match(
+    headers: &HeaderMap,
+    current_etag: Option<&str>,
+    exists: bool,
+) -> Result<(), ApiError> {
+    if let Some(value) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) {
         if value.trim() == "*" {
-            if exists {
+            if !exists {
                 return Err(ApiError::PreconditionFailed("precondition failed".into()));
             }
-        } else if etag_matches(value, current_etag) {
+        } else if !etag_matches(value, current_etag) {
             return Err(ApiError::PreconditionFailed("precondition failed".into()));
         }
     }
-
     Ok(())
 }
 
+fn header_date(headers: &HeaderMap, name: header::HeaderName) -> Option<SystemTime> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|value| parse_http_date(value).ok())
+}
+
+/// HTTP 日期只有秒级精度，比较前截断到秒。
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
 fn etag_matches(header_value: &str, current: Option<&str>) -> bool {
     let current = match current {
         Some(value) => value,
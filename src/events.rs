@@ -0,0 +1,520 @@
+//! 存储目录的文件系统监听与 `/api/events` 变更事件推送（Server-Sent Events）。
+
+use axum::extract::{Extension, Query};
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use futures_util::stream::{self, Stream};
+use notify::event::{AccessKind, AccessMode, CreateKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+use crate::config::{
+    EVENT_CHANNEL_CAPACITY, EVENT_COALESCE_MS, EVENT_KEEP_ALIVE_SECS, EVENT_MAX_DELAY_MS,
+};
+use crate::volumes::{Volume, Volumes};
+
+/// 变更事件类型。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    Moved,
+    /// 事件已丢失（内核队列溢出或订阅者处理过慢），客户端应重新列出目录。
+    Rescan,
+}
+
+impl ChangeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Moved => "moved",
+            ChangeKind::Rescan => "rescan",
+        }
+    }
+}
+
+/// 规范化后的存储变更事件，路径与 `/api/files/list` 返回的路径格式一致。
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// 事件类型。
+    pub kind: ChangeKind,
+    /// 变更后的路径（`rescan` 为空）。
+    pub path: String,
+    /// 移动前的路径（仅 `moved`）。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// 是否为目录（删除事件中无法确定时为 false）。
+    pub is_dir: bool,
+}
+
+impl ChangeEvent {
+    pub fn new(kind: ChangeKind, path: String, is_dir: bool) -> Self {
+        Self {
+            kind,
+            path,
+            from: None,
+            is_dir,
+        }
+    }
+
+    /// 创建移动事件。
+    pub fn moved(from: String, path: String, is_dir: bool) -> Self {
+        Self {
+            kind: ChangeKind::Moved,
+            path,
+            from: Some(from),
+            is_dir,
+        }
+    }
+
+    pub fn rescan() -> Self {
+        Self::new(ChangeKind::Rescan, String::new(), false)
+    }
+
+    /// 判断事件是否落在订阅的目录内：默认仅匹配直接子项（及目录本身），
+    /// `recursive` 时匹配整个子树；移动事件的新旧路径任一匹配即可。
+    fn matches(&self, dir: &str, recursive: bool) -> bool {
+        if self.kind == ChangeKind::Rescan {
+            return true;
+        }
+        let in_dir = |path: &str| {
+            if path == dir {
+                return true;
+            }
+            let rest = if dir.is_empty() {
+                path
+            } else {
+                match path
+                    .strip_prefix(dir)
+                    .and_then(|rest| rest.strip_prefix('/'))
+                {
+                    Some(rest) => rest,
+                    None => return false,
+                }
+            };
+            recursive || !rest.contains('/')
+        };
+        in_dir(&self.path) || self.from.as_deref().is_some_and(in_dir)
+    }
+}
+
+/// 变更事件的广播中心。
+pub struct EventHub {
+    sender: broadcast::Sender<ChangeEvent>,
+    /// 已由文件系统监听覆盖的卷名。
+    watched: RwLock<HashSet<String>>,
+}
+
+impl EventHub {
+    /// 创建广播中心。
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            sender,
+            watched: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// 订阅后续事件。
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.sender.subscribe()
+    }
+
+    /// 广播事件；没有订阅者时直接丢弃。
+    pub fn publish(&self, event: ChangeEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// 记录经由 HTTP API 或 WebDAV 完成的修改。卷已被监听时由监听产生事件，
+    /// 此处忽略以免重复；未启用监听时直接广播。
+    pub fn record_local(&self, volume: &Volume, event: ChangeEvent) {
+        let watched = self
+            .watched
+            .read()
+            .is_ok_and(|watched| watched.contains(volume.name()));
+        if !watched {
+            self.publish(event);
+        }
+    }
+
+    fn mark_watched(&self, volume: &Volume) {
+        if let Ok(mut watched) = self.watched.write() {
+            watched.insert(volume.name().to_string());
+        }
+    }
+}
+
+/// 将请求中的路径规范化为 `/` 分隔、无首尾分隔符的对外路径。
+pub fn normalize_path(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 为每个卷启动递归监听任务；监听失败仅记录警告，不影响服务启动。
+pub fn spawn_watchers(volumes: Arc<Volumes>, hub: Arc<EventHub>) {
+    for volume in volumes.iter() {
+        let root = match std::fs::canonicalize(volume.storage().root_path()) {
+            Ok(root) => root,
+            Err(err) => {
+                warn!(volume = volume.name(), error = %err, "failed to resolve watch root");
+                continue;
+            }
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        let watcher = notify::recommended_watcher(move |result| {
+            let _ = tx.send(result);
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        let watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                warn!(volume = volume.name(), error = %err, "failed to watch storage");
+                continue;
+            }
+        };
+        info!(volume = volume.name(), root = ?root, "watching storage for changes");
+        hub.mark_watched(volume);
+        let translator = Translator::new(volumes.clone(), volume.clone(), root);
+        tokio::spawn(run_watcher(watcher, rx, translator, hub.clone()));
+    }
+}
+
+async fn run_watcher(
+    _watcher: notify::RecommendedWatcher,
+    mut rx: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    mut translator: Translator,
+    hub: Arc<EventHub>,
+) {
+    let window = Duration::from_millis(EVENT_COALESCE_MS);
+    let max_delay = Duration::from_millis(EVENT_MAX_DELAY_MS);
+    loop {
+        // 有未发布的事件时仅等待一个合并窗口，空闲后统一发布。
+        let next = if translator.is_idle() {
+            rx.recv().await
+        } else {
+            match tokio::time::timeout(window, rx.recv()).await {
+                Ok(next) => next,
+                Err(_) => {
+                    translator.flush().into_iter().for_each(|e| hub.publish(e));
+                    continue;
+                }
+            }
+        };
+        match next {
+            Some(Ok(event)) => {
+                translator.push(event);
+                if translator.is_overdue(max_delay) {
+                    translator.flush().into_iter().for_each(|e| hub.publish(e));
+                }
+            }
+            Some(Err(err)) => {
+                warn!(volume = translator.volume.name(), error = %err, "storage watch error");
+            }
+            None => break,
+        }
+    }
+}
+
+/// 将原始文件系统事件转换为存储相对路径的变更事件：
+/// 配对重命名、跳过隐藏名称，并在合并窗口内去重连续的修改事件。
+struct Translator {
+    volumes: Arc<Volumes>,
+    volume: Arc<Volume>,
+    root: PathBuf,
+    /// 等待配对的重命名源（按 tracker）。
+    renames: Vec<(usize, PathBuf)>,
+    pending: Vec<ChangeEvent>,
+    since: Option<Instant>,
+}
+
+impl Translator {
+    fn new(volumes: Arc<Volumes>, volume: Arc<Volume>, root: PathBuf) -> Self {
+        Self {
+            volumes,
+            volume,
+            root,
+            renames: Vec::new(),
+            pending: Vec::new(),
+            since: None,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.pending.is_empty() && self.renames.is_empty()
+    }
+
+    fn is_overdue(&self, max_delay: Duration) -> bool {
+        self.since.is_some_and(|since| since.elapsed() >= max_delay)
+    }
+
+    fn push(&mut self, event: Event) {
+        if event.need_rescan() {
+            self.record(ChangeEvent::rescan());
+        }
+        let Some(path) = event.paths.first() else {
+            return;
+        };
+        match event.kind {
+            EventKind::Create(kind) => {
+                let is_dir = match kind {
+                    CreateKind::Folder => true,
+                    CreateKind::File => false,
+                    _ => path.is_dir(),
+                };
+                self.record_path(ChangeKind::Created, path, is_dir);
+                if is_dir {
+                    self.record_contents(path);
+                }
+            }
+            EventKind::Remove(kind) => {
+                self.record_path(ChangeKind::Deleted, path, kind == RemoveKind::Folder);
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => match event.attrs.tracker() {
+                Some(tracker) => self.renames.push((tracker, path.clone())),
+                None => self.record_path(ChangeKind::Deleted, path, false),
+            },
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                // 已配对的移动由随后的 `Both` 事件处理。
+                let paired = event
+                    .attrs
+                    .tracker()
+                    .is_some_and(|tracker| self.renames.iter().any(|(t, _)| *t == tracker));
+                if !paired {
+                    self.record_path(ChangeKind::Created, path, path.is_dir());
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let Some(tracker) = event.attrs.tracker() {
+                    self.renames.retain(|(t, _)| *t != tracker);
+                }
+                let Some(to) = event.paths.get(1) else {
+                    return;
+                };
+                self.record_rename(path, to);
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {}
+            EventKind::Modify(_) | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                self.record_path(ChangeKind::Modified, path, path.is_dir());
+            }
+            _ => {}
+        }
+    }
+
+    fn record_rename(&mut self, from: &Path, to: &Path) {
+        let is_dir = to.is_dir();
+        match (self.relative(from), self.relative(to)) {
+            (Some(from), Some(to)) => self.record(ChangeEvent::moved(from, to, is_dir)),
+            // 隐藏的临时文件替换到可见名称，即原子写入完成。
+            (None, Some(to)) => self.record(ChangeEvent::new(ChangeKind::Modified, to, is_dir)),
+            (Some(from), None) => self.record(ChangeEvent::new(ChangeKind::Deleted, from, is_dir)),
+            (None, None) => {}
+        }
+    }
+
+    /// 新目录的监听在创建事件之后才建立，其间写入的内容通过扫描补发创建事件。
+    fn record_contents(&mut self, dir: &Path) {
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Some(relative) = self.relative(&path) else {
+                    continue;
+                };
+                let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+                self.record(ChangeEvent::new(ChangeKind::Created, relative, is_dir));
+                if is_dir {
+                    pending.push(path);
+                }
+            }
+        }
+    }
+
+    fn record_path(&mut self, kind: ChangeKind, path: &Path, is_dir: bool) {
+        if let Some(path) = self.relative(path) {
+            self.record(ChangeEvent::new(kind, path, is_dir));
+        }
+    }
+
+    fn record(&mut self, event: ChangeEvent) {
+        let duplicate = self.pending.iter().any(|pending| match event.kind {
+            ChangeKind::Created => pending.kind == event.kind && pending.path == event.path,
+            ChangeKind::Modified => {
+                matches!(pending.kind, ChangeKind::Created | ChangeKind::Modified)
+                    && pending.path == event.path
+            }
+            ChangeKind::Deleted => {
+                (pending.kind == ChangeKind::Deleted && pending.path == event.path)
+                    || pending.from.as_deref() == Some(event.path.as_str())
+            }
+            ChangeKind::Rescan => pending.kind == ChangeKind::Rescan,
+            ChangeKind::Moved => false,
+        });
+        if !duplicate {
+            self.pending.push(event);
+            self.since.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// 返回待发布的事件；未配对的重命名源视为移出存储，即删除。
+    fn flush(&mut self) -> Vec<ChangeEvent> {
+        for (_, path) in std::mem::take(&mut self.renames) {
+            self.record_path(ChangeKind::Deleted, &path, false);
+        }
+        self.since = None;
+        std::mem::take(&mut self.pending)
+    }
+
+    /// 转换为对外路径；根目录本身、非常规路径或任一层级被隐藏时返回 None。
+    fn relative(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let storage = self.volume.storage();
+        let mut parts = Vec::new();
+        for (index, component) in relative.components().enumerate() {
+            let Component::Normal(name) = component else {
+                return None;
+            };
+            if storage.is_hidden(index == 0, name) {
+                return None;
+            }
+            parts.push(name.to_str()?);
+        }
+        if parts.is_empty() {
+            return None;
+        }
+        Some(self.volumes.public_path(&self.volume, &parts.join("/")))
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct EventsQuery {
+    path: Option<String>,
+    #[serde(default)]
+    recursive: bool,
+}
+
+/// 以 Server-Sent Events 推送订阅目录内的变更，事件名为变更类型。
+pub async fn events_stream(
+    Query(query): Query<EventsQuery>,
+    Extension(hub): Extension<Arc<EventHub>>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let dir = normalize_path(query.path.as_deref().unwrap_or_default());
+    let recursive = query.recursive;
+    info!(path = dir.as_str(), recursive, "subscribe events");
+    let stream = stream::unfold(hub.subscribe(), move |mut rx| {
+        let dir = dir.clone();
+        async move {
+            loop {
+                let event = match rx.recv().await {
+                    Ok(event) if event.matches(&dir, recursive) => event,
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(_)) => ChangeEvent::rescan(),
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                let Ok(sse) = SseEvent::default()
+                    .event(event.kind.as_str())
+                    .json_data(&event)
+                else {
+                    continue;
+                };
+                return Some((Ok(sse), rx));
+            }
+        }
+    });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::new().interval(Duration::from_secs(EVENT_KEEP_ALIVE_SECS)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hidden::HiddenNames;
+    use crate::storage::Storage;
+    use notify::event::{DataChange, EventAttributes};
+
+    fn event(kind: EventKind, paths: &[&Path], tracker: Option<usize>) -> Event {
+        let mut attrs = EventAttributes::new();
+        if let Some(tracker) = tracker {
+            attrs.set_tracker(tracker);
+        }
+        Event {
+            kind,
+            paths: paths.iter().map(|path| path.to_path_buf()).collect(),
+            attrs,
+        }
+    }
+
+    #[test]
+    fn raw_events_become_storage_relative_changes() {
+        let root = PathBuf::from("/srv/storage");
+        let storage = Storage::new(root.clone()).with_hidden(HiddenNames::from_patterns("*.part"));
+        let volumes = Arc::new(Volumes::single(Arc::new(storage)));
+        let volume = volumes.single_volume().expect("volume").clone();
+        let mut translator = Translator::new(volumes, volume, root.clone());
+        let rename = |mode| EventKind::Modify(ModifyKind::Name(mode));
+        let (a, b) = (root.join("docs/a.txt"), root.join("docs/b.txt"));
+        let temp = root.join(format!("docs/.c.txt.tmp.{}", uuid::Uuid::new_v4()));
+        let (c, part) = (root.join("docs/c.txt"), root.join("docs/d.part"));
+
+        translator.push(event(EventKind::Create(CreateKind::File), &[&a], None));
+        let write = EventKind::Modify(ModifyKind::Data(DataChange::Any));
+        translator.push(event(write, &[&a], None));
+        translator.push(event(write, &[&a], None));
+        translator.push(event(rename(RenameMode::From), &[&a], Some(7)));
+        translator.push(event(rename(RenameMode::To), &[&b], Some(7)));
+        translator.push(event(rename(RenameMode::Both), &[&a, &b], Some(7)));
+        translator.push(event(EventKind::Create(CreateKind::File), &[&temp], None));
+        translator.push(event(rename(RenameMode::From), &[&temp], Some(8)));
+        translator.push(event(rename(RenameMode::To), &[&c], Some(8)));
+        translator.push(event(rename(RenameMode::Both), &[&temp, &c], Some(8)));
+        translator.push(event(EventKind::Create(CreateKind::File), &[&part], None));
+        translator.push(event(rename(RenameMode::From), &[&b], Some(9)));
+        assert!(!translator.is_idle());
+
+        let created = |path: &str| ChangeEvent::new(ChangeKind::Created, path.into(), false);
+        assert_eq!(
+            translator.flush(),
+            vec![
+                created("docs/a.txt"),
+                ChangeEvent {
+                    kind: ChangeKind::Moved,
+                    path: "docs/b.txt".into(),
+                    from: Some("docs/a.txt".into()),
+                    is_dir: false,
+                },
+                ChangeEvent::new(ChangeKind::Modified, "docs/c.txt".into(), false),
+                ChangeEvent::new(ChangeKind::Deleted, "docs/b.txt".into(), false),
+            ]
+        );
+        assert!(translator.is_idle());
+
+        let moved = ChangeEvent {
+            kind: ChangeKind::Moved,
+            path: "docs/sub/x".into(),
+            from: Some("other/x".into()),
+            is_dir: false,
+        };
+        assert!(moved.matches("other", false));
+        assert!(moved.matches("docs", true));
+        assert!(!moved.matches("docs", false));
+        assert!(moved.matches("", true));
+        assert!(!created("docsx/a").matches("docs", true));
+    }
+}
@@ -0,0 +1,591 @@
+//! 文件请求链接：免登录向指定目录上传文件，上传者无法查看目录内容。
+//!
+//! 上传沿用分片上传与表单上传的流程，但目标目录由链接决定、文件名只取最后一段，
+//! 同名文件始终自动重命名，并按链接的文件数与总字节数限额计数。
+
+use axum::body::Body as AxumBody;
+use axum::extract::{Extension, Json, Multipart, Path, Query};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{Html, IntoResponse, Json as JsonResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::config::{DEFAULT_LOCK_WAIT_TIMEOUT_SECS, FILE_REQUEST_MAX_PENDING_UPLOADS};
+use crate::error::ApiError;
+use crate::events::EventHub;
+use crate::files::ConflictPolicy;
+use crate::http::{RequestScheme, link_base_url};
+use crate::links::{Link, LinkConfig, LinkKind, LinkStore, new_token, unix_now};
+use crate::locking::LockManager;
+use crate::shares::LinkInfo;
+use crate::upload::{
+    UploadChunkQuery, UploadConfig, UploadInitResponse, UploadMetadata, discard_upload,
+    finish_upload, form_file_name, join_relative, multipart_error, open_upload,
+    pending_link_uploads, start_upload, store_chunk, store_form_file,
+};
+use crate::volumes::Volumes;
+
+/// 浏览器访问文件请求链接时的上传页面，表单提交到同一地址。
+const UPLOAD_PAGE: &str = "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Upload files</title></head>\n\
+<body><h1>Upload files</h1>\n\
+<form method=\"post\" enctype=\"multipart/form-data\">\
+<input type=\"file\" name=\"file\" multiple required> <button type=\"submit\">Upload</button>\
+</form></body></html>\n";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileRequestCreate {
+    path: String,
+    /// 有效时长（秒），None 表示不过期。
+    expires_in: Option<u64>,
+    max_files: Option<u64>,
+    max_bytes: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileRequestInit {
+    name: String,
+    total_size: u64,
+    /// 客户端文件的修改时间（Unix 毫秒）。
+    last_modified: Option<u64>,
+}
+
+/// 合并或中止文件请求上传会话的请求体。
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FileRequestUpload {
+    upload_id: String,
+}
+
+/// 上传者可见的链接状态，不包含目标目录。
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRequestStatus {
+    pub expires_at: Option<u64>,
+    /// 剩余可上传的文件数，None 表示不限制。
+    pub remaining_files: Option<u64>,
+    /// 剩余可上传的字节数，None 表示不限制。
+    pub remaining_bytes: Option<u64>,
+}
+
+/// 上传者看到的写入结果：只返回最终文件名（同名时为重命名后的名称）。
+#[derive(Serialize)]
+pub struct ReceivedFile {
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct ReceivedFiles {
+    pub files: Vec<ReceivedFile>,
+}
+
+/// 为已有目录创建文件请求链接。
+pub async fn create_file_request(
+    headers: HeaderMap,
+    Extension(scheme): Extension<RequestScheme>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    Extension(link_config): Extension<Arc<LinkConfig>>,
+    Json(payload): Json<FileRequestCreate>,
+) -> Result<Response, ApiError> {
+    let path = payload.path.trim().trim_matches(['/', '\\']).to_string();
+    if payload.expires_in == Some(0) {
+        return Err(ApiError::BadRequest("expiresIn must be positive".into()));
+    }
+    if payload.max_files == Some(0) {
+        return Err(ApiError::BadRequest("maxFiles must be positive".into()));
+    }
+    if payload.max_bytes == Some(0) {
+        return Err(ApiError::BadRequest("maxBytes must be positive".into()));
+    }
+    let (volume, relative) = volumes.resolve_writable(&path)?;
+    let target = volume
+        .storage()
+        .resolve_path_checked(relative, false)
+        .await?;
+    let metadata = target
+        .metadata()
+        .await
+        .map_err(|_| ApiError::NotFound("path not found".into()))?;
+    if !metadata.is_dir() {
+        return Err(ApiError::BadRequest("path is not a directory".into()));
+    }
+
+    let now = unix_now();
+    let link = Link {
+        token: new_token(),
+        kind: LinkKind::Request,
+        path,
+        created_at: now,
+        expires_at: payload.expires_in.map(|secs| now.saturating_add(secs)),
+        max_downloads: None,
+        downloads: 0,
+        password_hash: None,
+        max_files: payload.max_files,
+        max_bytes: payload.max_bytes,
+        files: 0,
+        bytes: 0,
+    };
+    links
+        .insert(link.clone())
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    info!(token = link.token, path = link.path, "create file request");
+    let base =
+        link_base_url(link_config.public_url.as_deref(), &headers, scheme).unwrap_or_default();
+    Ok((
+        StatusCode::CREATED,
+        JsonResponse(LinkInfo::new(link, &base)),
+    )
+        .into_response())
+}
+
+/// 返回文件请求的剩余限额；浏览器访问时返回上传页面。
+pub async fn request_status(
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    Extension(links): Extension<Arc<LinkStore>>,
+) -> Result<Response, ApiError> {
+    let link = active_request(&links, &token).await?;
+    let wants_html = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"));
+    if wants_html {
+        return Ok(Html(UPLOAD_PAGE).into_response());
+    }
+    Ok(JsonResponse(FileRequestStatus {
+        expires_at: link.expires_at,
+        remaining_files: link
+            .max_files
+            .map(|max_files| max_files.saturating_sub(link.files)),
+        remaining_bytes: link.remaining_bytes(),
+    })
+    .into_response())
+}
+
+/// 以 `multipart/form-data` 表单向文件请求上传一个或多个文件。
+pub async fn request_form_upload(
+    Path(token): Path<String>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    mut multipart: Multipart,
+) -> Result<Response, ApiError> {
+    let mut received: u64 = 0;
+    let mut files = Vec::new();
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        let Some(file_name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        if file_name.is_empty() {
+            continue;
+        }
+        let link = active_request(&links, &token).await?;
+        let path = request_target(&volumes, &link, &file_name)?;
+        let total_remaining =
+            (upload.max_total_size > 0).then(|| upload.max_total_size.saturating_sub(received));
+        let limit = match (link.remaining_bytes(), total_remaining) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let mut claimed = None;
+        // 请求头不参与条件判断，避免上传者借此探测目录中已有的文件。
+        let result = store_form_file(
+            &volumes,
+            &lock_manager,
+            &hub,
+            &HeaderMap::new(),
+            &path,
+            ConflictPolicy::Rename,
+            &mut field,
+            limit,
+            async |written| {
+                claim_upload(&links, &token, written).await?;
+                claimed = Some(written);
+                Ok(())
+            },
+        )
+        .await;
+        let (path, written) = match result {
+            Ok(stored) => stored,
+            Err(err) => {
+                if let Some(written) = claimed {
+                    release_upload(&links, &token, written).await;
+                }
+                return Err(err);
+            }
+        };
+        received += written;
+        info!(token, path, "file request upload");
+        files.push(ReceivedFile {
+            name: file_name_of(&path),
+        });
+    }
+
+    if files.is_empty() {
+        return Err(ApiError::BadRequest("no files in form".into()));
+    }
+    Ok((StatusCode::CREATED, JsonResponse(ReceivedFiles { files })).into_response())
+}
+
+/// 为文件请求创建分片上传会话。
+pub async fn request_upload_init(
+    Path(token): Path<String>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    Json(payload): Json<FileRequestInit>,
+) -> Result<JsonResponse<UploadInitResponse>, ApiError> {
+    // 同一链接的会话创建串行执行，使会话数与预留字节数的检查不被并发绕过。
+    let _guard = lock_manager
+        .lock_path_with_timeout(
+            &format!("\0file-request/{token}"),
+            Duration::from_secs(DEFAULT_LOCK_WAIT_TIMEOUT_SECS),
+        )
+        .await
+        .map_err(|_| ApiError::Conflict("link locked".into()))?;
+    let link = active_request(&links, &token).await?;
+    // 未合并的会话按声明大小预留链接额度，中止或过期删除会话后自动归还。
+    let (pending, reserved) = pending_link_uploads(&volumes, &token).await?;
+    if pending >= FILE_REQUEST_MAX_PENDING_UPLOADS {
+        return Err(ApiError::TooManyRequests(60));
+    }
+    if link
+        .max_files
+        .is_some_and(|max_files| link.files + pending >= max_files)
+    {
+        return Err(ApiError::BadRequest("file request limit reached".into()));
+    }
+    if link
+        .remaining_bytes()
+        .is_some_and(|remaining| payload.total_size > remaining.saturating_sub(reserved))
+    {
+        return Err(ApiError::BadRequest("upload size exceeds limit".into()));
+    }
+    let name = request_target(&volumes, &link, &payload.name)?;
+    let upload_id = start_upload(
+        &volumes,
+        &upload,
+        UploadMetadata {
+            name,
+            total_size: payload.total_size,
+            last_modified: payload.last_modified,
+            on_conflict: ConflictPolicy::Rename,
+            link: Some(token),
+        },
+    )
+    .await?;
+    Ok(JsonResponse(UploadInitResponse { upload_id }))
+}
+
+/// 上传文件请求会话的单个分片。
+pub async fn request_upload_chunk(
+    Path(token): Path<String>,
+    Query(query): Query<UploadChunkQuery>,
+    headers: HeaderMap,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    body: AxumBody,
+) -> Result<StatusCode, ApiError> {
+    active_request(&links, &token).await?;
+    store_chunk(
+        &volumes,
+        &upload,
+        &query.upload_id,
+        &headers,
+        body,
+        Some(&token),
+    )
+    .await
+}
+
+/// 合并文件请求会话的分片；同名文件始终重命名。
+pub async fn request_upload_complete(
+    Path(token): Path<String>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    Json(payload): Json<FileRequestUpload>,
+) -> Result<Response, ApiError> {
+    active_request(&links, &token).await?;
+    let pending = open_upload(&volumes, &upload, &payload.upload_id, Some(&token)).await?;
+    let size = pending.metadata.total_size;
+    claim_upload(&links, &token, size).await?;
+    let result = finish_upload(
+        &volumes,
+        &lock_manager,
+        &hub,
+        &HeaderMap::new(),
+        pending,
+        ConflictPolicy::Rename,
+    )
+    .await;
+    match result {
+        Ok((path, _)) => {
+            info!(token, path, "file request upload");
+            Ok((
+                StatusCode::CREATED,
+                JsonResponse(ReceivedFile {
+                    name: file_name_of(&path),
+                }),
+            )
+                .into_response())
+        }
+        Err(err) => {
+            release_upload(&links, &token, size).await;
+            Err(err)
+        }
+    }
+}
+
+/// 中止文件请求的分片上传会话。
+pub async fn request_upload_abort(
+    Path(token): Path<String>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    Json(payload): Json<FileRequestUpload>,
+) -> Result<StatusCode, ApiError> {
+    active_request(&links, &token).await?;
+    discard_upload(&volumes, &payload.upload_id, Some(&token)).await
+}
+
+async fn active_request(links: &LinkStore, token: &str) -> Result<Link, ApiError> {
+    links
+        .get(token)
+        .await
+        .filter(|link| link.kind == LinkKind::Request)
+        .ok_or_else(|| ApiError::NotFound("link not found or expired".into()))
+}
+
+/// 返回上传文件在链接目标目录中的对外路径；文件名只取最后一段且不能是隐藏名称。
+fn request_target(volumes: &Volumes, link: &Link, file_name: &str) -> Result<String, ApiError> {
+    let name = form_file_name(file_name)?;
+    let (volume, relative) = volumes.resolve_writable(&link.path)?;
+    if volume
+        .storage()
+        .is_hidden(relative.trim_matches('/').is_empty(), name.as_ref())
+    {
+        return Err(ApiError::BadRequest(format!(
+            "invalid file name: {file_name}"
+        )));
+    }
+    Ok(join_relative(&link.path, name))
+}
+
+async fn claim_upload(links: &LinkStore, token: &str, size: u64) -> Result<(), ApiError> {
+    links
+        .claim_upload(token, size)
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?
+        .map(|_| ())
+        .ok_or_else(|| ApiError::BadRequest("file request limit reached".into()))
+}
+
+async fn release_upload(links: &LinkStore, token: &str, size: u64) {
+    if let Err(err) = links.release_upload(token, size).await {
+        warn!(token, error = %err, "failed to release file request upload");
+    }
+}
+
+fn file_name_of(path: &str) -> String {
+    path.rsplit('/').next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::FromRequest;
+    use axum::http::{HeaderValue, Request};
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    use crate::config::{
+        DEFAULT_UPLOAD_MAX_CHUNKS, DEFAULT_UPLOAD_MAX_CONCURRENT, DEFAULT_UPLOAD_MAX_SIZE,
+        DEFAULT_UPLOAD_TEMP_TTL_SECS,
+    };
+    use crate::storage::Storage;
+
+    #[tokio::test]
+    async fn file_request_renames_uploads_and_enforces_link_limits() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(root.join("inbox")).expect("create inbox");
+        std::fs::write(root.join("inbox/a.txt"), "old").expect("write existing");
+        let volumes = Arc::new(Volumes::single(Arc::new(Storage::new(root.clone()))));
+        let links = Arc::new(
+            LinkStore::open(&temp.path().join("links.json"))
+                .await
+                .expect("links"),
+        );
+        let upload = Arc::new(UploadConfig {
+            max_total_size: DEFAULT_UPLOAD_MAX_SIZE,
+            max_chunks: DEFAULT_UPLOAD_MAX_CHUNKS,
+            max_concurrent: DEFAULT_UPLOAD_MAX_CONCURRENT,
+            temp_ttl: Duration::from_secs(DEFAULT_UPLOAD_TEMP_TTL_SECS),
+        });
+        let lock_manager = Arc::new(LockManager::new());
+        let hub = Arc::new(EventHub::new());
+
+        let response = create_file_request(
+            HeaderMap::new(),
+            Extension(RequestScheme::Https),
+            Extension(volumes.clone()),
+            Extension(links.clone()),
+            Extension(Arc::new(LinkConfig {
+                public_url: Some("https://drive.example".into()),
+            })),
+            Json(FileRequestCreate {
+                path: "inbox".into(),
+                expires_in: None,
+                max_files: Some(2),
+                max_bytes: Some(10),
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("create file request failed"));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = links.list().await[0].token.clone();
+
+        let init = |name: &str, total_size: u64| {
+            request_upload_init(
+                Path(token.clone()),
+                Extension(volumes.clone()),
+                Extension(lock_manager.clone()),
+                Extension(upload.clone()),
+                Extension(links.clone()),
+                Json(FileRequestInit {
+                    name: name.into(),
+                    total_size,
+                    last_modified: None,
+                }),
+            )
+        };
+        assert!(matches!(
+            init("big.bin", 11).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        let JsonResponse(started) = init("../../a.txt", 5)
+            .await
+            .unwrap_or_else(|_| panic!("init failed"));
+        // 未合并的会话预留额度：字节数与文件数都计入，中止后归还。
+        assert!(matches!(
+            init("c.bin", 6).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        let JsonResponse(reserved) = init("d.bin", 5)
+            .await
+            .unwrap_or_else(|_| panic!("init failed"));
+        assert!(matches!(
+            init("e.bin", 0).await,
+            Err(ApiError::BadRequest(_))
+        ));
+        request_upload_abort(
+            Path(token.clone()),
+            Extension(volumes.clone()),
+            Extension(links.clone()),
+            Json(FileRequestUpload {
+                upload_id: reserved.upload_id,
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("abort failed"));
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Chunk-Index", HeaderValue::from_static("0"));
+        let chunk = request_upload_chunk(
+            Path(token.clone()),
+            Query(UploadChunkQuery {
+                upload_id: started.upload_id.clone(),
+            }),
+            headers,
+            Extension(volumes.clone()),
+            Extension(upload.clone()),
+            Extension(links.clone()),
+            AxumBody::from("hello"),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("chunk failed"));
+        assert_eq!(chunk, StatusCode::CREATED);
+        let complete = request_upload_complete(
+            Path(token.clone()),
+            Extension(volumes.clone()),
+            Extension(lock_manager.clone()),
+            Extension(upload.clone()),
+            Extension(hub.clone()),
+            Extension(links.clone()),
+            Json(FileRequestUpload {
+                upload_id: started.upload_id,
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("complete failed"));
+        let body = axum::body::to_bytes(complete.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(&body[..], br#"{"name":"a (1).txt"}"#);
+        assert_eq!(
+            std::fs::read_to_string(root.join("inbox/a.txt")).expect("read"),
+            "old"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("inbox/a (1).txt")).expect("read"),
+            "hello"
+        );
+
+        let form = |content: &'static str| {
+            let volumes = volumes.clone();
+            let token = token.clone();
+            let links = links.clone();
+            let upload = upload.clone();
+            let lock_manager = lock_manager.clone();
+            let hub = hub.clone();
+            async move {
+                let body = format!(
+                    "--XX\r\n\
+                    Content-Disposition: form-data; name=\"file\"; filename=\"b.txt\"\r\n\r\n\
+                    {content}\r\n\
+                    --XX--\r\n"
+                );
+                let request = Request::builder()
+                    .header(header::CONTENT_TYPE, "multipart/form-data; boundary=XX")
+                    .body(AxumBody::from(body))
+                    .expect("request");
+                let multipart = Multipart::from_request(request, &())
+                    .await
+                    .unwrap_or_else(|_| panic!("multipart"));
+                request_form_upload(
+                    Path(token),
+                    Extension(volumes),
+                    Extension(lock_manager),
+                    Extension(upload),
+                    Extension(hub),
+                    Extension(links),
+                    multipart,
+                )
+                .await
+            }
+        };
+        assert!(matches!(
+            form("too large").await,
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(!root.join("inbox/b.txt").exists());
+        let response = form("abc")
+            .await
+            .unwrap_or_else(|_| panic!("form upload failed"));
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let link = links.list().await.remove(0);
+        assert_eq!((link.files, link.bytes), (2, 8));
+        assert!(matches!(form("x").await, Err(ApiError::NotFound(_))));
+    }
+}
@@ -3,29 +3,70 @@
 use axum::Error as AxumError;
 use axum::body::Body as AxumBody;
 use axum::extract::{Extension, Json, Query};
-use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, header};
 use axum::response::{IntoResponse, Json as JsonResponse, Response};
-use futures_util::stream::StreamExt;
+use bytes::Bytes;
+use futures_util::future::join_all;
+use futures_util::stream::{self, StreamExt};
 use http_body_util::BodyExt;
-use httpdate::{fmt_http_date, parse_http_date};
-use serde::Deserialize;
-use std::io::{ErrorKind, SeekFrom};
+use httpdate::fmt_http_date;
+use serde::{Deserialize, Serialize};
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::path::Path;
 use std::sync::Arc;
-use tokio::fs::{self, File};
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
-use tokio_util::io::ReaderStream;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::OwnedMutexGuard;
 use tracing::{debug, info};
+use uuid::Uuid;
 
 use crate::atomic::AtomicFile;
-use crate::config::DEFAULT_LOCK_WAIT_TIMEOUT_SECS;
+use crate::beneath::WriteOptions;
+use crate::config::{
+    CONFLICT_RENAME_MAX_ATTEMPTS, DEFAULT_LOCK_WAIT_TIMEOUT_SECS, DOWNLOAD_MAX_RANGES,
+    LIST_DETAIL_MAX_CONCURRENT, MAX_CLIENT_MTIME_SECS, PATCH_COPY_CHUNK_SIZE,
+};
+use crate::content::{BodyPart, ContentReader, ContentWriter};
 use crate::error::ApiError;
-use crate::etag::{check_preconditions, etag_from_metadata};
+use crate::etag::{
+    ReadCondition, check_preconditions, check_read_preconditions, etag_from_metadata,
+    if_range_matches,
+};
+use crate::events::{ChangeEvent, ChangeKind, EventHub, normalize_path};
+use crate::http::{ACTIVE_CONTENT_CSP, Disposition, content_disposition, is_active_content};
 use crate::locking::LockManager;
-use crate::storage::{FileEntry, Storage};
+use crate::metadata::{MetadataExtractor, has_capture_time};
+use crate::stat::stat_entry;
+use crate::storage::{FileEntry, ResolvedPath, Storage, StorageError};
+use crate::thumbnail::{Thumbnailer, TransformQuery, image_response};
+use crate::volumes::{Volume, Volumes};
+
+/// 客户端指定文件修改时间的请求头（兼容 ownCloud / Nextcloud 客户端）。
+pub(crate) const MTIME_HEADER: &str = "x-oc-mtime";
+
+/// 文件下载的响应配置。
+#[derive(Debug)]
+pub struct DownloadConfig {
+    pub cache_control: HeaderValue,
+}
 
 #[derive(Deserialize)]
-pub(crate) struct OptionalPathQuery {
+pub(crate) struct ListQuery {
     path: Option<String>,
+    sort: Option<ListSort>,
+    /// 为每个条目附加 stat 接口的详细字段。
+    #[serde(default)]
+    detail: bool,
+}
+
+/// 目录列表的排序方式，默认目录在前、按名称排序。
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ListSort {
+    Name,
+    /// 目录在前，文件按拍摄时间从早到晚排列，没有拍摄时间的文件排在最后。
+    Captured,
 }
 
 #[derive(Deserialize)]
@@ -33,17 +74,102 @@ pub(crate) struct RequiredPathQuery {
     path: String,
 }
 
+#[derive(Deserialize)]
+pub(crate) struct WriteQuery {
+    path: String,
+    #[serde(default, rename = "onConflict")]
+    on_conflict: ConflictPolicy,
+}
+
+/// 写入成功的响应体：冲突重命名后实际写入的路径。
+#[derive(Serialize)]
+pub(crate) struct WriteResponse {
+    pub path: String,
+}
+
+/// 写入目标已存在时的处理方式。
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// 覆盖已有文件（默认，可配合 If-Match / If-None-Match 条件写入）。
+    #[default]
+    Overwrite,
+    /// 保留已有文件，在同目录选择 `name (n).ext` 形式的空闲名称。
+    Rename,
+}
+
+/// 已按冲突策略确定并锁定的写入路径，锁在值被丢弃时释放。
+pub(crate) struct WriteTarget {
+    pub path: String,
+    _guards: Vec<OwnedMutexGuard<()>>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DownloadQuery {
+    pub path: String,
+    #[serde(default)]
+    pub disposition: Disposition,
+}
+
 #[derive(Deserialize)]
 pub(crate) struct DirCreateBody {
     path: String,
 }
 
-/// 列出目录内容。
+/// 列出目录内容，可按拍摄时间排序并附加详细字段。
 pub async fn list_files(
-    Query(query): Query<OptionalPathQuery>,
-    Extension(storage): Extension<Arc<Storage>>,
+    Query(query): Query<ListQuery>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(extractor): Extension<Arc<MetadataExtractor>>,
 ) -> Result<JsonResponse<Vec<FileEntry>>, ApiError> {
-    let entries = storage.list_dir(query.path.as_deref()).await?;
+    let mut entries = volumes.list_dir(query.path.as_deref()).await?;
+    if matches!(query.sort, Some(ListSort::Captured)) {
+        let captured = join_all(entries.iter().map(|entry| {
+            let volumes = &volumes;
+            let extractor = &extractor;
+            async move {
+                if entry.is_dir || !has_capture_time(&entry.path) {
+                    return None;
+                }
+                let (volume, relative) = volumes.resolve(&entry.path).ok()?;
+                let (metadata, _) = extractor.extract(volume, relative).await.ok()?;
+                metadata.captured
+            }
+        }))
+        .await;
+        for (entry, captured) in entries.iter_mut().zip(captured) {
+            entry.captured = captured;
+        }
+        entries.sort_by(|a, b| {
+            (!a.is_dir, a.captured.is_none(), &a.captured).cmp(&(
+                !b.is_dir,
+                b.captured.is_none(),
+                &b.captured,
+            ))
+        });
+    }
+    if query.detail {
+        // 限制同时进行的 stat 数量，避免大目录一次性占满阻塞线程池。
+        let paths: Vec<String> = entries.iter().map(|entry| entry.path.clone()).collect();
+        let details: Vec<_> = stream::iter(paths.into_iter().enumerate())
+            .map(|(index, path)| {
+                let volumes = volumes.clone();
+                async move {
+                    let details = async {
+                        let (volume, relative) = volumes.resolve(&path).ok()?;
+                        stat_entry(volume.storage(), relative).await.ok()?.details
+                    }
+                    .await;
+                    (index, details)
+                }
+            })
+            .buffer_unordered(LIST_DETAIL_MAX_CONCURRENT)
+            .collect()
+            .await;
+        for (index, details) in details {
+            entries[index].details = details;
+        }
+    }
     info!(
         path = query.path.as_deref().unwrap_or(""),
         count = entries.len(),
@@ -52,31 +178,45 @@ pub async fn list_files(
     Ok(JsonResponse(entries))
 }
 
-/// 下载文件，支持 Range 请求与缓存相关头。
+/// 下载文件，支持 Range 请求与缓存相关头；带 `w`/`h`/`format`/`quality` 参数时返回变换后的图片。
 pub async fn download_file(
-    Query(RequiredPathQuery { path }): Query<RequiredPathQuery>,
+    method: Method,
+    Query(DownloadQuery { path, disposition }): Query<DownloadQuery>,
+    Query(transform): Query<TransformQuery>,
     request_headers: HeaderMap,
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(thumbnailer): Extension<Arc<Thumbnailer>>,
+    Extension(config): Extension<Arc<DownloadConfig>>,
 ) -> Result<Response, ApiError> {
-    let target = storage.resolve_path_checked(&path, false).await?;
-    let metadata = fs::metadata(&target)
-        .await
-        .map_err(|err| ApiError::Internal(err.to_string()))?;
-    if metadata.is_dir() {
-        return Err(ApiError::BadRequest("path is not a file".into()));
+    let (volume, relative) = volumes.resolve(&path)?;
+    if transform.is_requested() {
+        let image = thumbnailer.transform(volume, relative, &transform).await?;
+        info!(path, size = image.data.len(), "download transformed image");
+        let filename = Path::new(file_name(&path))
+            .with_extension(image.format.extension())
+            .to_string_lossy()
+            .into_owned();
+        let mut response = image_response(image, &request_headers)?;
+        if response.status() == StatusCode::OK {
+            response.headers_mut().insert(
+                header::CONTENT_DISPOSITION,
+                content_disposition(disposition, &filename),
+            );
+        }
+        return Ok(response);
     }
-    let file_size = metadata.len();
+    let (reader, metadata) = open_content(volume, relative).await?;
+    let file_size = reader.len();
     let modified = metadata.modified().ok();
     let last_modified = modified.map(fmt_http_date);
     let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let etag = etag_from_metadata(&metadata, file_size);
 
     let mut response_headers = HeaderMap::new();
     response_headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_str(mime.essence_str())
-            .map_err(|_| ApiError::Internal("无效的 MIME 类型".into()))?,
+        header::ETAG,
+        HeaderValue::from_str(&etag).map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
     );
-    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
     if let Some(value) = last_modified.as_deref() {
         response_headers.insert(
             header::LAST_MODIFIED,
@@ -84,57 +224,92 @@ pub async fn download_file(
                 .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
         );
     }
-    let etag = etag_from_metadata(&metadata);
+    response_headers.insert(header::CACHE_CONTROL, config.cache_control.clone());
+
+    if check_read_preconditions(&request_headers, &etag, modified)? == ReadCondition::NotModified {
+        debug!(path, "download not modified");
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
     response_headers.insert(
-        header::ETAG,
-        HeaderValue::from_str(&etag).map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(mime.essence_str())
+            .map_err(|_| ApiError::Internal("无效的 MIME 类型".into()))?,
     );
-
-    let if_range_matches = match request_headers
-        .get(header::IF_RANGE)
-        .and_then(|value| value.to_str().ok())
-    {
-        Some(value) => match parse_http_date(value) {
-            Ok(date) => modified.map(|ts| ts <= date).unwrap_or(false),
-            Err(_) => false,
-        },
-        None => true,
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    let disposition = if is_active_content(mime.essence_str()) {
+        response_headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(ACTIVE_CONTENT_CSP),
+        );
+        Disposition::Attachment
+    } else {
+        disposition
     };
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        content_disposition(disposition, file_name(&path)),
+    );
 
-    let range = if if_range_matches {
+    let ranges = if if_range_matches(&request_headers, &etag, modified) {
         parse_range(request_headers.get(header::RANGE), file_size)?
     } else {
         None
     };
+    let head_only = method == Method::HEAD;
 
-    let file = File::open(&target)
-        .await
-        .map_err(|err| ApiError::Internal(err.to_string()))?;
-
-    if let Some((start, end)) = range {
-        let length = end - start + 1;
-        debug!(path, start, end, length, "download range request accepted");
-        let mut file = file;
-        file.seek(SeekFrom::Start(start))
-            .await
-            .map_err(|err| ApiError::Internal(err.to_string()))?;
-        let stream = ReaderStream::new(file.take(length));
-        response_headers.insert(
-            header::CONTENT_RANGE,
-            HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, file_size))
-                .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
-        );
-        response_headers.insert(
-            header::CONTENT_LENGTH,
-            HeaderValue::from_str(&length.to_string())
-                .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
-        );
-        return Ok((
-            StatusCode::PARTIAL_CONTENT,
-            response_headers,
-            AxumBody::from_stream(stream),
-        )
-            .into_response());
+    match ranges.as_deref() {
+        Some(&[(start, end)]) => {
+            let length = end - start + 1;
+            debug!(path, start, end, length, "download range request accepted");
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, file_size))
+                    .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
+            );
+            response_headers.insert(
+                header::CONTENT_LENGTH,
+                HeaderValue::from_str(&length.to_string())
+                    .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
+            );
+            let body = if head_only {
+                AxumBody::empty()
+            } else {
+                let stream = reader
+                    .into_stream(start, length)
+                    .await
+                    .map_err(|err| ApiError::Internal(err.to_string()))?;
+                AxumBody::from_stream(stream)
+            };
+            return Ok((StatusCode::PARTIAL_CONTENT, response_headers, body).into_response());
+        }
+        Some(ranges) => {
+            debug!(
+                path,
+                count = ranges.len(),
+                "download multi-range request accepted"
+            );
+            let boundary = Uuid::new_v4().simple().to_string();
+            let (parts, length) =
+                byteranges_parts(ranges, &boundary, mime.essence_str(), file_size);
+            response_headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(&format!("multipart/byteranges; boundary={boundary}"))
+                    .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
+            );
+            response_headers.insert(
+                header::CONTENT_LENGTH,
+                HeaderValue::from_str(&length.to_string())
+                    .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
+            );
+            let body = if head_only {
+                AxumBody::empty()
+            } else {
+                AxumBody::from_stream(reader.into_parts_stream(parts))
+            };
+            return Ok((StatusCode::PARTIAL_CONTENT, response_headers, body).into_response());
+        }
+        None => {}
     }
 
     response_headers.insert(
@@ -142,8 +317,14 @@ pub async fn download_file(
         HeaderValue::from_str(&file_size.to_string())
             .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
     );
+    if head_only {
+        return Ok((StatusCode::OK, response_headers, AxumBody::empty()).into_response());
+    }
     info!(path, size = file_size, "download full file");
-    let stream = ReaderStream::new(file);
+    let stream = reader
+        .into_stream(0, file_size)
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
     Ok((
         StatusCode::OK,
         response_headers,
@@ -152,18 +333,207 @@ pub async fn download_file(
         .into_response())
 }
 
-/// 写入文件内容，支持条件写入与原子替换。
+/// 取路径的最后一段作为下载文件名。
+fn file_name(path: &str) -> &str {
+    path.trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(path)
+}
+
+/// 打开文件用于按区间读取客户端可见内容，返回读取器与文件元数据。
+pub(crate) async fn open_content(
+    volume: &Volume,
+    relative: &str,
+) -> Result<(ContentReader, Metadata), ApiError> {
+    let storage = volume.storage();
+    let target = storage.resolve_path_checked(relative, false).await?;
+    let file = target.open_file().await.map_err(StorageError::from)?;
+    let metadata = file
+        .metadata()
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    if metadata.is_dir() {
+        return Err(ApiError::BadRequest("path is not a file".into()));
+    }
+    let reader = ContentReader::new(file, storage.content_format())
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    Ok((reader, metadata))
+}
+
+/// 写入文件内容，支持条件写入、冲突重命名与原子替换。
 pub async fn write_file(
-    Query(RequiredPathQuery { path }): Query<RequiredPathQuery>,
+    Query(WriteQuery { path, on_conflict }): Query<WriteQuery>,
     headers: HeaderMap,
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
     Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(hub): Extension<Arc<EventHub>>,
     body: AxumBody,
 ) -> Result<Response, ApiError> {
     if path.is_empty() {
         return Err(ApiError::BadRequest("path is required".into()));
     }
+    let (path, response_headers) = store_body(
+        &volumes,
+        &lock_manager,
+        &hub,
+        &headers,
+        &path,
+        on_conflict,
+        body,
+    )
+    .await?;
+    Ok((
+        StatusCode::CREATED,
+        response_headers,
+        JsonResponse(WriteResponse { path }),
+    )
+        .into_response())
+}
+
+/// 按冲突策略将请求体原子写入目标路径，返回实际写入的对外路径与写入响应头。
+pub(crate) async fn store_body(
+    volumes: &Volumes,
+    lock_manager: &LockManager,
+    hub: &EventHub,
+    headers: &HeaderMap,
+    path: &str,
+    on_conflict: ConflictPolicy,
+    body: AxumBody,
+) -> Result<(String, HeaderMap), ApiError> {
+    let locked = lock_write_target(volumes, lock_manager, path, on_conflict).await?;
+    let path = locked.path.as_str();
     info!(path, "write file");
+    let (volume, relative) = volumes.resolve_writable(path)?;
+    let storage = volume.storage();
+    let target = storage.resolve_path_checked(relative, true).await?;
+    let metadata = match target.metadata().await {
+        Ok(metadata) => Some(metadata),
+        Err(err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => return Err(ApiError::Internal(err.to_string())),
+    };
+    let exists = metadata.is_some();
+    let etag = match metadata.as_ref() {
+        Some(metadata) => Some(storage.content_etag(&target, metadata).await),
+        None => None,
+    };
+    check_preconditions(headers, etag.as_deref(), exists)?;
+
+    let client_mtime = parse_mtime_header(headers)?;
+    let mut atomic = AtomicFile::new(storage, &target).await?.with_volume(volume);
+    if let Some(modified) = client_mtime {
+        atomic = atomic.with_modified(modified);
+    }
+    if let Err(err) = write_body(atomic.writer_mut(), body).await {
+        atomic.cleanup().await;
+        return Err(err);
+    }
+    atomic.finalize().await?;
+    let kind = if exists {
+        ChangeKind::Modified
+    } else {
+        ChangeKind::Created
+    };
+    hub.record_local(volume, ChangeEvent::new(kind, normalize_path(path), false));
+
+    let mut response_headers = written_headers(storage, &target).await?;
+    if client_mtime.is_some() {
+        response_headers.insert(MTIME_HEADER, HeaderValue::from_static("accepted"));
+    }
+    Ok((
+        volumes.public_path(volume, relative.trim_matches('/')),
+        response_headers,
+    ))
+}
+
+/// 按冲突策略确定并锁定写入路径。
+///
+/// 始终先锁定请求的路径；`rename` 策略下目标已存在时，在持有该锁的情况下依次尝试
+/// `name (1).ext`、`name (2).ext`……并同时锁定选中的名称，因此同名的并发写入会
+/// 串行地选到不同的名称。
+pub(crate) async fn lock_write_target(
+    volumes: &Volumes,
+    lock_manager: &LockManager,
+    path: &str,
+    policy: ConflictPolicy,
+) -> Result<WriteTarget, ApiError> {
+    let lock = |path: String| async move {
+        lock_manager
+            .lock_path_with_timeout(&path, Duration::from_secs(DEFAULT_LOCK_WAIT_TIMEOUT_SECS))
+            .await
+            .map_err(|_| ApiError::Conflict("path locked".into()))
+    };
+    let mut guards = vec![lock(path.to_string()).await?];
+    if policy == ConflictPolicy::Overwrite || !path_exists(volumes, path).await? {
+        return Ok(WriteTarget {
+            path: path.to_string(),
+            _guards: guards,
+        });
+    }
+    for index in 1..=CONFLICT_RENAME_MAX_ATTEMPTS {
+        let candidate = numbered_name(path, index);
+        let guard = lock(candidate.clone()).await?;
+        if !path_exists(volumes, &candidate).await? {
+            guards.push(guard);
+            return Ok(WriteTarget {
+                path: candidate,
+                _guards: guards,
+            });
+        }
+    }
+    Err(ApiError::Conflict("no free name available".into()))
+}
+
+async fn path_exists(volumes: &Volumes, path: &str) -> Result<bool, ApiError> {
+    let (volume, relative) = volumes.resolve_writable(path)?;
+    let target = match volume.storage().resolve_path_checked(relative, false).await {
+        Ok(target) => target,
+        // 父目录不存在时目标同样不存在。
+        Err(StorageError::Io(err)) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    match target.metadata().await {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(ApiError::Internal(err.to_string())),
+    }
+}
+
+/// 在文件名的扩展名前插入序号：`docs/report.pdf` → `docs/report (1).pdf`。
+fn numbered_name(path: &str, index: u32) -> String {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (Some(dir), name),
+        None => (None, path),
+    };
+    let numbered = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{stem} ({index}).{ext}"),
+        _ => format!("{name} ({index})"),
+    };
+    match dir {
+        Some(dir) => format!("{dir}/{numbered}"),
+        None => numbered,
+    }
+}
+
+/// 局部写入文件：携带 `Content-Range: bytes start-end/total` 时从 `start` 起覆盖写入，
+/// 未携带时追加到文件末尾。
+///
+/// 明文存储（未启用加密与压缩）的追加直接写入原文件末尾，失败时截断回原长度；
+/// 其他情况复制原内容到临时文件后原子替换，耗时与文件大小成正比。起始位置超过当前长度时返回 416。
+pub async fn patch_file(
+    Query(RequiredPathQuery { path }): Query<RequiredPathQuery>,
+    headers: HeaderMap,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    body: AxumBody,
+) -> Result<Response, ApiError> {
+    if path.is_empty() {
+        return Err(ApiError::BadRequest("path is required".into()));
+    }
+    let (volume, relative) = volumes.resolve_writable(&path)?;
+    let storage = volume.storage();
 
     let _guard = lock_manager
         .lock_path_with_timeout(
@@ -172,49 +542,245 @@ pub async fn write_file(
         )
         .await
         .map_err(|_| ApiError::Conflict("path locked".into()))?;
-    let target = storage.resolve_path_checked(&path, true).await?;
-    let metadata = match fs::metadata(&target).await {
-        Ok(metadata) => Some(metadata),
+    let target = storage.resolve_path_checked(relative, true).await?;
+    let mut existing = match target.metadata().await {
+        Ok(metadata) if metadata.is_dir() => {
+            return Err(ApiError::BadRequest("path is not a file".into()));
+        }
+        Ok(metadata) => {
+            let file = target.open_file().await.map_err(StorageError::from)?;
+            let reader = ContentReader::new(file, storage.content_format())
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+            Some((reader, metadata))
+        }
         Err(err) if err.kind() == ErrorKind::NotFound => None,
         Err(err) => return Err(ApiError::Internal(err.to_string())),
     };
-    let exists = metadata.is_some();
-    let etag = metadata.as_ref().map(etag_from_metadata);
-    check_preconditions(&headers, etag.as_deref(), exists)?;
+    let current_len = existing.as_ref().map_or(0, |(reader, _)| reader.len());
+    let etag = existing
+        .as_ref()
+        .map(|(_, metadata)| etag_from_metadata(metadata, current_len));
+    check_preconditions(&headers, etag.as_deref(), existing.is_some())?;
 
-    if let Some(parent) = target.parent() {
-        fs::create_dir_all(parent)
-            .await
-            .map_err(|err| ApiError::Internal(err.to_string()))?;
+    let (start, expected_len) = match headers.get(header::CONTENT_RANGE) {
+        Some(value) => {
+            let (start, end, total) = parse_content_range(value)?;
+            let length = end - start + 1;
+            if total.is_some_and(|total| total != current_len.max(end + 1)) {
+                return Err(ApiError::BadRequest(
+                    "Content-Range total does not match resulting size".into(),
+                ));
+            }
+            (start, Some(length))
+        }
+        None => (current_len, None),
+    };
+    if start > current_len {
+        return Err(ApiError::RangeNotSatisfiable(current_len));
+    }
+    info!(path, start, length = ?expected_len, "patch file");
+
+    let client_mtime = parse_mtime_header(&headers)?;
+    let append_in_place = start == current_len
+        && storage.content_format().is_plain()
+        && existing
+            .as_ref()
+            .is_some_and(|(_, metadata)| metadata.is_file());
+    if append_in_place {
+        append_file(
+            volume,
+            &target,
+            current_len,
+            expected_len,
+            client_mtime,
+            body,
+        )
+        .await?;
+    } else {
+        let mut atomic = AtomicFile::new(storage, &target).await?.with_volume(volume);
+        if let Some(modified) = client_mtime {
+            atomic = atomic.with_modified(modified);
+        }
+        let write_result: Result<(), ApiError> = async {
+            let writer = atomic.writer_mut();
+            if let Some((reader, _)) = existing.as_mut() {
+                copy_content(reader, writer, 0, start).await?;
+            }
+            let written = write_body(writer, body).await?;
+            if expected_len.is_some_and(|length| length != written) {
+                return Err(ApiError::BadRequest(
+                    "body length does not match Content-Range".into(),
+                ));
+            }
+            if let Some((reader, _)) = existing.as_mut() {
+                copy_content(reader, writer, start + written, current_len).await?;
+            }
+            Ok(())
+        }
+        .await;
+        if let Err(err) = write_result {
+            atomic.cleanup().await;
+            return Err(err);
+        }
+        atomic.finalize().await?;
     }
+    let (kind, status) = if existing.is_some() {
+        (ChangeKind::Modified, StatusCode::NO_CONTENT)
+    } else {
+        (ChangeKind::Created, StatusCode::CREATED)
+    };
+    hub.record_local(volume, ChangeEvent::new(kind, normalize_path(&path), false));
+
+    let mut response_headers = written_headers(storage, &target).await?;
+    if client_mtime.is_some() {
+        response_headers.insert(MTIME_HEADER, HeaderValue::from_static("accepted"));
+    }
+    Ok((status, response_headers).into_response())
+}
+
+/// 解析客户端通过 `X-OC-Mtime` 指定的修改时间（Unix 秒，可带小数）。
+pub(crate) fn parse_mtime_header(headers: &HeaderMap) -> Result<Option<SystemTime>, ApiError> {
+    let Some(value) = headers.get(MTIME_HEADER) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .filter(|seconds| (0.0..=MAX_CLIENT_MTIME_SECS as f64).contains(seconds))
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .and_then(|duration| UNIX_EPOCH.checked_add(duration))
+        .map(Some)
+        .ok_or_else(|| ApiError::BadRequest("invalid X-OC-Mtime".into()))
+}
 
-    let mut atomic = AtomicFile::new(&target).await?;
-    let write_result: Result<(), ApiError> = async {
+/// 将客户端提供的修改时间（Unix 毫秒）转换为时间点，超出 [`MAX_CLIENT_MTIME_SECS`] 时返回 None。
+pub(crate) fn mtime_from_millis(millis: u64) -> Option<SystemTime> {
+    if millis / 1000 > MAX_CLIENT_MTIME_SECS {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::from_millis(millis))
+}
+
+/// 将请求体写入内容写入器，返回写入的字节数。
+async fn write_body(writer: &mut ContentWriter, body: AxumBody) -> Result<u64, ApiError> {
+    let mut written = 0;
+    let mut data_stream = BodyExt::into_data_stream(body);
+    while let Some(chunk) = data_stream.next().await {
+        let chunk = chunk.map_err(|err: AxumError| ApiError::Internal(err.to_string()))?;
+        if !chunk.is_empty() {
+            writer
+                .write_all(&chunk)
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+            written += chunk.len() as u64;
+        }
+    }
+    Ok(written)
+}
+
+/// 将请求体直接追加到明文存储的已有文件末尾，追加的字节按卷配额校验并计入占用；
+/// 写入失败、长度与 `Content-Range` 不符或超出配额时截断回原长度。
+async fn append_file(
+    volume: &Arc<Volume>,
+    target: &ResolvedPath,
+    original_len: u64,
+    expected_len: Option<u64>,
+    modified: Option<SystemTime>,
+    body: AxumBody,
+) -> Result<(), ApiError> {
+    let name = target
+        .name()
+        .ok_or_else(|| ApiError::BadRequest("invalid target path".into()))?;
+    let options = WriteOptions {
+        append: true,
+        ..WriteOptions::default()
+    };
+    let mut file = target
+        .dir()
+        .open_write_file(name, options)
+        .await
+        .map_err(StorageError::from)?;
+    let result: Result<(), ApiError> = async {
+        let mut written = 0;
         let mut data_stream = BodyExt::into_data_stream(body);
         while let Some(chunk) = data_stream.next().await {
             let chunk = chunk.map_err(|err: AxumError| ApiError::Internal(err.to_string()))?;
-            if !chunk.is_empty() {
-                atomic
-                    .file_mut()
-                    .write_all(&chunk)
-                    .await
-                    .map_err(|err| ApiError::Internal(err.to_string()))?;
-            }
+            file.write_all(&chunk)
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+            written += chunk.len() as u64;
+            volume.check_quota(written, 0).await?;
+        }
+        if expected_len.is_some_and(|length| length != written) {
+            return Err(ApiError::BadRequest(
+                "body length does not match Content-Range".into(),
+            ));
         }
+        let std_file = file
+            .try_clone()
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?
+            .into_std()
+            .await;
+        tokio::task::spawn_blocking(move || {
+            if let Some(modified) = modified {
+                std_file.set_modified(modified)?;
+            }
+            std_file.sync_all()
+        })
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+        volume.charge_quota(written, 0).await?;
         Ok(())
     }
     .await;
-    if let Err(err) = write_result {
-        atomic.cleanup().await;
+    if let Err(err) = result {
+        let _ = file.set_len(original_len).await;
         return Err(err);
     }
-    atomic.finalize().await?;
+    Ok(())
+}
 
-    let metadata = fs::metadata(&target)
+/// 将原文件 `[start, end)` 区间的内容复制到写入器；`end` 不超过原文件长度。
+async fn copy_content(
+    reader: &mut ContentReader,
+    writer: &mut ContentWriter,
+    start: u64,
+    end: u64,
+) -> Result<(), ApiError> {
+    let mut offset = start;
+    while offset < end {
+        let max = (end - offset).min(PATCH_COPY_CHUNK_SIZE as u64) as usize;
+        let chunk = reader
+            .read_at(offset, max)
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        if chunk.is_empty() {
+            return Err(ApiError::Internal("unexpected end of file".into()));
+        }
+        writer
+            .write_all(&chunk)
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        offset += chunk.len() as u64;
+    }
+    Ok(())
+}
+
+/// 构建写入成功响应的 ETag 与 Last-Modified 头（按应用后的修改时间计算）。
+pub(crate) async fn written_headers(
+    storage: &Storage,
+    target: &ResolvedPath,
+) -> Result<HeaderMap, ApiError> {
+    let metadata = target
+        .metadata()
         .await
         .map_err(|err| ApiError::Internal(err.to_string()))?;
     let mut response_headers = HeaderMap::new();
-    let etag = etag_from_metadata(&metadata);
+    let etag = storage.content_etag(target, &metadata).await;
     response_headers.insert(
         header::ETAG,
         HeaderValue::from_str(&etag).map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
@@ -227,18 +793,20 @@ pub async fn write_file(
                 .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
         );
     }
-    Ok((StatusCode::CREATED, response_headers).into_response())
+    Ok(response_headers)
 }
 
 /// 删除文件或目录。
 pub async fn delete_entry(
     Query(RequiredPathQuery { path }): Query<RequiredPathQuery>,
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
     Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(hub): Extension<Arc<EventHub>>,
 ) -> Result<StatusCode, ApiError> {
     if path.is_empty() {
         return Err(ApiError::BadRequest("path is required".into()));
     }
+    let (volume, relative) = volumes.resolve_writable(&path)?;
     let _guard = lock_manager
         .lock_path_with_timeout(
             &path,
@@ -246,15 +814,20 @@ pub async fn delete_entry(
         )
         .await
         .map_err(|_| ApiError::Conflict("path locked".into()))?;
-    storage.delete_path(&path).await?;
+    let is_dir = volume.storage().delete_path(relative).await?;
+    hub.record_local(
+        volume,
+        ChangeEvent::new(ChangeKind::Deleted, normalize_path(&path), is_dir),
+    );
     info!(path, "delete entry");
     Ok(StatusCode::NO_CONTENT)
 }
 
 /// 创建目录（含父级）。
 pub async fn create_directory(
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
     Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(hub): Extension<Arc<EventHub>>,
     payload: Json<DirCreateBody>,
 ) -> Result<StatusCode, ApiError> {
     let DirCreateBody { path } = payload.0;
@@ -262,6 +835,7 @@ pub async fn create_directory(
     if path.is_empty() {
         return Err(ApiError::BadRequest("path is required".into()));
     }
+    let (volume, relative) = volumes.resolve_writable(&path)?;
 
     let _guard = lock_manager
         .lock_path_with_timeout(
@@ -270,64 +844,123 @@ pub async fn create_directory(
         )
         .await
         .map_err(|_| ApiError::Conflict("path locked".into()))?;
-    storage.create_dir(&path).await?;
+    volume.storage().create_dir(relative).await?;
+    hub.record_local(
+        volume,
+        ChangeEvent::new(ChangeKind::Created, normalize_path(&path), true),
+    );
     info!(path, "create directory");
     Ok(StatusCode::CREATED)
 }
 
-/// 解析 Range 头，返回可读取的范围。
+/// 构建 `multipart/byteranges` 响应体的各部分，返回部分列表与总长度。
+fn byteranges_parts(
+    ranges: &[(u64, u64)],
+    boundary: &str,
+    content_type: &str,
+    file_size: u64,
+) -> (Vec<BodyPart>, u64) {
+    let mut parts = Vec::with_capacity(ranges.len() * 2 + 1);
+    let mut length = 0;
+    for &(start, end) in ranges {
+        let head = format!(
+            "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: bytes {start}-{end}/{file_size}\r\n\r\n"
+        );
+        length += head.len() as u64 + (end - start + 1);
+        parts.push(BodyPart::Bytes(Bytes::from(head)));
+        parts.push(BodyPart::Range {
+            start,
+            length: end - start + 1,
+        });
+    }
+    let tail = format!("\r\n--{boundary}--\r\n");
+    length += tail.len() as u64;
+    parts.push(BodyPart::Bytes(Bytes::from(tail)));
+    (parts, length)
+}
+
+/// 解析 PATCH 请求的 `Content-Range: bytes start-end/total`，`total` 可为 `*`。
+fn parse_content_range(value: &HeaderValue) -> Result<(u64, u64, Option<u64>), ApiError> {
+    let invalid = || ApiError::BadRequest("invalid Content-Range".into());
+    let value = value.to_str().map_err(|_| invalid())?.trim();
+    let spec = value.strip_prefix("bytes ").ok_or_else(invalid)?;
+    let (range, total) = spec.split_once('/').ok_or_else(invalid)?;
+    let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+    let start: u64 = start.trim().parse().map_err(|_| invalid())?;
+    let end: u64 = end.trim().parse().map_err(|_| invalid())?;
+    if end < start {
+        return Err(invalid());
+    }
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().map_err(|_| invalid())?),
+    };
+    Ok((start, end, total))
+}
+
+/// 解析 Range 请求头，返回按起点排序、合并重叠或相邻区间后的闭区间列表。
+/// 区间数超过 [`DOWNLOAD_MAX_RANGES`] 时忽略 Range；所有区间均无法满足时返回 416。
 fn parse_range(
     value: Option<&HeaderValue>,
     file_size: u64,
-) -> Result<Option<(u64, u64)>, ApiError> {
+) -> Result<Option<Vec<(u64, u64)>>, ApiError> {
     let Some(value) = value else {
         return Ok(None);
     };
-    if file_size == 0 {
-        return Err(ApiError::RangeNotSatisfiable(file_size));
-    }
-    let value = value
-        .to_str()
-        .map_err(|_| ApiError::BadRequest("invalid Range header".into()))?;
-    let Some(range) = value.strip_prefix("bytes=") else {
-        return Err(ApiError::BadRequest("invalid Range header".into()));
+    let invalid = || ApiError::BadRequest("invalid Range header".into());
+    let value = value.to_str().map_err(|_| invalid())?;
+    let Some(specs) = value.strip_prefix("bytes=") else {
+        return Err(invalid());
     };
-    if range.contains(',') {
-        return Err(ApiError::BadRequest("multiple ranges not supported".into()));
+    let specs: Vec<&str> = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .collect();
+    if specs.is_empty() {
+        return Err(invalid());
+    }
+    if specs.len() > DOWNLOAD_MAX_RANGES {
+        debug!(count = specs.len(), "too many ranges, serving full content");
+        return Ok(None);
     }
 
-    let mut parts = range.splitn(2, '-');
-    let start_part = parts.next().unwrap_or_default();
-    let end_part = parts.next().unwrap_or_default();
-
-    let (start, end) = if start_part.is_empty() {
-        let suffix: u64 = end_part
-            .parse()
-            .map_err(|_| ApiError::BadRequest("invalid Range header".into()))?;
-        if suffix == 0 {
-            return Ok(None);
+    let mut ranges = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let (start_part, end_part) = spec.split_once('-').ok_or_else(invalid)?;
+        if start_part.is_empty() {
+            let suffix: u64 = end_part.parse().map_err(|_| invalid())?;
+            if suffix > 0 && file_size > 0 {
+                ranges.push((file_size.saturating_sub(suffix), file_size - 1));
+            }
+            continue;
         }
-        let start = file_size.saturating_sub(suffix);
-        (start, file_size.saturating_sub(1))
-    } else {
-        let start: u64 = start_part
-            .parse()
-            .map_err(|_| ApiError::BadRequest("invalid Range header".into()))?;
+        let start: u64 = start_part.parse().map_err(|_| invalid())?;
         let end: u64 = if end_part.is_empty() {
-            file_size.saturating_sub(1)
+            u64::MAX
         } else {
-            end_part
-                .parse()
-                .map_err(|_| ApiError::BadRequest("invalid Range header".into()))?
+            end_part.parse().map_err(|_| invalid())?
         };
-        (start, end)
-    };
-
-    if start > end || start >= file_size || end >= file_size {
+        if start > end {
+            return Err(ApiError::RangeNotSatisfiable(file_size));
+        }
+        if start < file_size {
+            ranges.push((start, end.min(file_size - 1)));
+        }
+    }
+    if ranges.is_empty() {
         return Err(ApiError::RangeNotSatisfiable(file_size));
     }
 
-    Ok(Some((start, end.min(file_size.saturating_sub(1)))))
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    Ok(Some(merged))
 }
 
 #[cfg(test)]
@@ -338,30 +971,679 @@ mod tests {
     use std::sync::Arc;
     use tempfile::tempdir;
 
+    use crate::compression::CompressionConfig;
+    use crate::crypto::Keyring;
     use crate::locking::LockManager;
+    use crate::storage::Storage;
+    use crate::volumes::Volume;
 
-    fn make_storage() -> (tempfile::TempDir, Arc<Storage>) {
+    fn make_volumes() -> (tempfile::TempDir, Arc<Volumes>) {
         let temp = tempdir().expect("tempdir");
         let root = temp.path().join("storage");
         std::fs::create_dir_all(&root).expect("create storage root");
-        (temp, Arc::new(Storage::new(root)))
+        (
+            temp,
+            Arc::new(Volumes::single(Arc::new(Storage::new(root)))),
+        )
+    }
+
+    fn download_config() -> Extension<Arc<DownloadConfig>> {
+        Extension(Arc::new(DownloadConfig {
+            cache_control: HeaderValue::from_static("private, no-cache"),
+        }))
     }
 
     #[tokio::test]
     async fn write_file_rejects_traversal_path() {
-        let (_temp, storage) = make_storage();
+        let (_temp, volumes) = make_volumes();
         let lock_manager = Arc::new(LockManager::new());
         let result = write_file(
-            Query(RequiredPathQuery {
+            Query(WriteQuery {
                 path: "../secret.txt".to_string(),
+                on_conflict: ConflictPolicy::Overwrite,
             }),
             HeaderMap::new(),
-            Extension(storage),
+            Extension(volumes),
             Extension(lock_manager),
+            Extension(Arc::new(EventHub::new())),
             AxumBody::from("data"),
         )
         .await;
 
         assert!(matches!(result, Err(ApiError::BadRequest(_))));
     }
+
+    #[tokio::test]
+    async fn encrypted_write_round_trips_with_range() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create storage root");
+        let storage = Storage::new(root.clone()).with_keyring(Some(Keyring::new(&[9u8; 32], &[])));
+        let volumes = Arc::new(Volumes::single(Arc::new(storage)));
+        let lock_manager = Arc::new(LockManager::new());
+        write_file(
+            Query(WriteQuery {
+                path: "secret.txt".to_string(),
+                on_conflict: ConflictPolicy::Overwrite,
+            }),
+            HeaderMap::new(),
+            Extension(volumes.clone()),
+            Extension(lock_manager),
+            Extension(Arc::new(EventHub::new())),
+            AxumBody::from("hello encrypted world"),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("write failed"));
+
+        let stored = std::fs::read(root.join("secret.txt")).expect("read stored");
+        assert!(!stored.windows(5).any(|window| window == b"hello"));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=6-14"));
+        let response = download_file(
+            Method::GET,
+            Query(DownloadQuery {
+                path: "secret.txt".to_string(),
+                disposition: Disposition::Inline,
+            }),
+            Query(TransformQuery::default()),
+            headers,
+            Extension(volumes),
+            Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+            download_config(),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes 6-14/21"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(&body[..], b"encrypted");
+    }
+
+    #[tokio::test]
+    async fn multiple_ranges_are_merged_into_multipart_response() {
+        let (temp, volumes) = make_volumes();
+        std::fs::write(
+            temp.path().join("storage/digits.txt"),
+            "0123456789abcdefghij",
+        )
+        .expect("write file");
+        let download = |range: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::RANGE, HeaderValue::from_static(range));
+            download_file(
+                Method::GET,
+                Query(DownloadQuery {
+                    path: "digits.txt".to_string(),
+                    disposition: Disposition::Inline,
+                }),
+                Query(TransformQuery::default()),
+                headers,
+                Extension(volumes.clone()),
+                Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+                download_config(),
+            )
+        };
+
+        let response = download("bytes=0-1, 15-, 4-5, 5-7, 30-40")
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .expect("content type")
+            .to_string();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .expect("multipart content type");
+        let length: usize = response.headers()[header::CONTENT_LENGTH]
+            .to_str()
+            .expect("content length")
+            .parse()
+            .expect("length");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(body.len(), length);
+        let expected = format!(
+            "\r\n--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/20\r\n\r\n01\
+             \r\n--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 4-7/20\r\n\r\n4567\
+             \r\n--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 15-19/20\r\n\r\nfghij\
+             \r\n--{boundary}--\r\n"
+        );
+        assert_eq!(std::str::from_utf8(&body).expect("utf8"), expected);
+
+        let merged = download("bytes=2-4,3-6")
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(merged.headers()[header::CONTENT_RANGE], "bytes 2-6/20");
+
+        let too_many = (0..=DOWNLOAD_MAX_RANGES)
+            .map(|index| format!("{}-{}", index, index))
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(
+            parse_range(
+                Some(&HeaderValue::from_str(&format!("bytes={too_many}")).expect("header")),
+                20
+            )
+            .unwrap_or_else(|_| panic!("parse failed")),
+            None
+        );
+        assert!(matches!(
+            download("bytes=30-40,50-").await,
+            Err(ApiError::RangeNotSatisfiable(20))
+        ));
+    }
+
+    #[tokio::test]
+    async fn conditional_requests_revalidate_downloads() {
+        let (temp, volumes) = make_volumes();
+        std::fs::write(temp.path().join("storage/report.txt"), "0123456789").expect("write file");
+        let download = |method: Method, headers: &[(header::HeaderName, String)]| {
+            let mut request_headers = HeaderMap::new();
+            for (name, value) in headers {
+                request_headers.insert(name, HeaderValue::from_str(value).expect("header"));
+            }
+            download_file(
+                method,
+                Query(DownloadQuery {
+                    path: "report.txt".to_string(),
+                    disposition: Disposition::Inline,
+                }),
+                Query(TransformQuery::default()),
+                request_headers,
+                Extension(volumes.clone()),
+                Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+                download_config(),
+            )
+        };
+
+        let full = download(Method::GET, &[])
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(full.headers()[header::CACHE_CONTROL], "private, no-cache");
+        let etag = full.headers()[header::ETAG]
+            .to_str()
+            .expect("etag")
+            .to_string();
+        let last_modified = full.headers()[header::LAST_MODIFIED]
+            .to_str()
+            .expect("last modified")
+            .to_string();
+
+        let cached = download(Method::GET, &[(header::IF_NONE_MATCH, etag.clone())])
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(cached.headers()[header::ETAG], etag.as_str());
+        assert!(!cached.headers().contains_key(header::CONTENT_LENGTH));
+
+        let by_date = download(
+            Method::GET,
+            &[(header::IF_MODIFIED_SINCE, last_modified.clone())],
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(by_date.status(), StatusCode::NOT_MODIFIED);
+
+        // If-None-Match 优先于 If-Modified-Since。
+        let stale = download(
+            Method::GET,
+            &[
+                (header::IF_NONE_MATCH, "W/\"other\"".to_string()),
+                (header::IF_MODIFIED_SINCE, last_modified.clone()),
+            ],
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(stale.status(), StatusCode::OK);
+
+        assert!(matches!(
+            download(
+                Method::GET,
+                &[(header::IF_MATCH, "W/\"other\"".to_string())]
+            )
+            .await,
+            Err(ApiError::PreconditionFailed(_))
+        ));
+
+        // 下载返回强 ETag，可直接用于 If-Range 续传；弱 ETag 一律视为不匹配。
+        let range = (header::RANGE, "bytes=2-4".to_string());
+        let resumed = download(
+            Method::GET,
+            &[range.clone(), (header::IF_RANGE, etag.clone())],
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(resumed.status(), StatusCode::PARTIAL_CONTENT);
+        let weak = download(
+            Method::GET,
+            &[range.clone(), (header::IF_RANGE, format!("W/{etag}"))],
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(weak.status(), StatusCode::OK);
+        let changed = download(
+            Method::GET,
+            &[range.clone(), (header::IF_RANGE, "W/\"other\"".to_string())],
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(changed.status(), StatusCode::OK);
+        let dated = download(
+            Method::GET,
+            &[range.clone(), (header::IF_RANGE, last_modified)],
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(dated.status(), StatusCode::PARTIAL_CONTENT);
+
+        let head = download(Method::HEAD, &[range])
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(head.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(head.headers()[header::CONTENT_LENGTH], "3");
+        let body = axum::body::to_bytes(head.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn active_content_downloads_are_forced_to_attachment() {
+        let (temp, volumes) = make_volumes();
+        std::fs::write(temp.path().join("storage/页面.html"), "<script></script>")
+            .expect("write html");
+        std::fs::write(temp.path().join("storage/notes.txt"), "notes").expect("write text");
+        let download = |path: &str| {
+            download_file(
+                Method::GET,
+                Query(DownloadQuery {
+                    path: path.to_string(),
+                    disposition: Disposition::Inline,
+                }),
+                Query(TransformQuery::default()),
+                HeaderMap::new(),
+                Extension(volumes.clone()),
+                Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+                download_config(),
+            )
+        };
+
+        let html = download("页面.html")
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(
+            html.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"__.html\"; filename*=UTF-8''%E9%A1%B5%E9%9D%A2.html"
+        );
+        assert_eq!(
+            html.headers()[header::CONTENT_SECURITY_POLICY],
+            ACTIVE_CONTENT_CSP
+        );
+
+        let text = download("notes.txt")
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(
+            text.headers()[header::CONTENT_DISPOSITION],
+            "inline; filename=\"notes.txt\"; filename*=UTF-8''notes.txt"
+        );
+        assert!(!text.headers().contains_key(header::CONTENT_SECURITY_POLICY));
+    }
+
+    #[tokio::test]
+    async fn conflicting_writes_are_renamed_to_free_names() {
+        let (temp, volumes) = make_volumes();
+        std::fs::create_dir_all(temp.path().join("storage/docs")).expect("create docs");
+        std::fs::write(temp.path().join("storage/docs/report.pdf"), "original")
+            .expect("write original");
+        let lock_manager = Arc::new(LockManager::new());
+        let write = |body: &'static str| {
+            write_file(
+                Query(WriteQuery {
+                    path: "docs/report.pdf".to_string(),
+                    on_conflict: ConflictPolicy::Rename,
+                }),
+                HeaderMap::new(),
+                Extension(volumes.clone()),
+                Extension(lock_manager.clone()),
+                Extension(Arc::new(EventHub::new())),
+                AxumBody::from(body),
+            )
+        };
+
+        let responses = join_all([write("a"), write("b"), write("c")]).await;
+        let mut paths = Vec::new();
+        for response in responses {
+            let response = response.unwrap_or_else(|_| panic!("write failed"));
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            let body: serde_json::Value = serde_json::from_slice(&body).expect("json");
+            paths.push(body["path"].as_str().expect("path").to_string());
+        }
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "docs/report (1).pdf",
+                "docs/report (2).pdf",
+                "docs/report (3).pdf"
+            ]
+        );
+        assert_eq!(
+            std::fs::read(temp.path().join("storage/docs/report.pdf")).expect("read"),
+            b"original"
+        );
+
+        assert_eq!(numbered_name(".bashrc", 2), ".bashrc (2)");
+        assert_eq!(numbered_name("notes", 1), "notes (1)");
+    }
+
+    #[tokio::test]
+    async fn client_mtime_header_is_applied_to_writes() {
+        let (temp, volumes) = make_volumes();
+        let write = |mtime: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(MTIME_HEADER, HeaderValue::from_static(mtime));
+            write_file(
+                Query(WriteQuery {
+                    path: "synced.txt".to_string(),
+                    on_conflict: ConflictPolicy::Overwrite,
+                }),
+                headers,
+                Extension(volumes.clone()),
+                Extension(Arc::new(LockManager::new())),
+                Extension(Arc::new(EventHub::new())),
+                AxumBody::from("data"),
+            )
+        };
+
+        let response = write("1500000000.5")
+            .await
+            .unwrap_or_else(|_| panic!("write failed"));
+        assert_eq!(response.headers()[MTIME_HEADER], "accepted");
+        assert_eq!(
+            response.headers()[header::ETAG],
+            "\"4-1500000000-500000000\""
+        );
+        let modified = std::fs::metadata(temp.path().join("storage/synced.txt"))
+            .expect("metadata")
+            .modified()
+            .expect("modified");
+        assert_eq!(
+            modified,
+            UNIX_EPOCH + Duration::from_millis(1_500_000_000_500)
+        );
+        for invalid in ["yesterday", "NaN", "-1", "1e19", "1e300"] {
+            assert!(matches!(write(invalid).await, Err(ApiError::BadRequest(_))));
+        }
+        assert!(mtime_from_millis(MAX_CLIENT_MTIME_SECS * 1000).is_some());
+        assert!(mtime_from_millis(u64::MAX).is_none());
+    }
+
+    #[tokio::test]
+    async fn patch_writes_ranges_and_appends_to_encrypted_files() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create storage root");
+        let storage = Storage::new(root.clone()).with_keyring(Some(Keyring::new(&[7u8; 32], &[])));
+        let volumes = Arc::new(Volumes::single(Arc::new(storage)));
+        let lock_manager = Arc::new(LockManager::new());
+        let patch = |headers: &[(header::HeaderName, String)], body: &'static str| {
+            let mut request_headers = HeaderMap::new();
+            for (name, value) in headers {
+                request_headers.insert(name, HeaderValue::from_str(value).expect("header"));
+            }
+            patch_file(
+                Query(RequiredPathQuery {
+                    path: "app.log".to_string(),
+                }),
+                request_headers,
+                Extension(volumes.clone()),
+                Extension(lock_manager.clone()),
+                Extension(Arc::new(EventHub::new())),
+                AxumBody::from(body),
+            )
+        };
+        let read = || async {
+            let (volume, relative) = volumes.resolve("app.log").expect("resolve");
+            let (mut reader, _) = open_content(volume, relative)
+                .await
+                .unwrap_or_else(|_| panic!("open failed"));
+            String::from_utf8(reader.read_to_end().await.expect("read")).expect("utf8")
+        };
+
+        let created = patch(&[], "hello ")
+            .await
+            .unwrap_or_else(|_| panic!("append failed"));
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let appended = patch(&[], "world")
+            .await
+            .unwrap_or_else(|_| panic!("append failed"));
+        assert_eq!(appended.status(), StatusCode::NO_CONTENT);
+        assert_eq!(read().await, "hello world");
+        let stored = std::fs::read(root.join("app.log")).expect("read stored");
+        assert!(!stored.windows(5).any(|window| window == b"hello"));
+
+        let etag = appended.headers()[header::ETAG]
+            .to_str()
+            .expect("etag")
+            .to_string();
+        patch(
+            &[
+                (header::CONTENT_RANGE, "bytes 6-10/*".to_string()),
+                (header::IF_MATCH, etag.clone()),
+            ],
+            "WORLD",
+        )
+        .await
+        .unwrap_or_else(|_| panic!("range write failed"));
+        assert_eq!(read().await, "hello WORLD");
+        patch(
+            &[(header::CONTENT_RANGE, "bytes 8-13/14".to_string())],
+            "RLD!!!",
+        )
+        .await
+        .unwrap_or_else(|_| panic!("extending write failed"));
+        assert_eq!(read().await, "hello WORLD!!!");
+
+        assert!(matches!(
+            patch(&[(header::IF_MATCH, etag)], "stale").await,
+            Err(ApiError::PreconditionFailed(_))
+        ));
+        assert!(matches!(
+            patch(
+                &[(header::CONTENT_RANGE, "bytes 20-21/*".to_string())],
+                "xx"
+            )
+            .await,
+            Err(ApiError::RangeNotSatisfiable(14))
+        ));
+        assert!(matches!(
+            patch(
+                &[(header::CONTENT_RANGE, "bytes 0-9/*".to_string())],
+                "short"
+            )
+            .await,
+            Err(ApiError::BadRequest(_))
+        ));
+        assert_eq!(read().await, "hello WORLD!!!");
+        let leftovers = std::fs::read_dir(&root).expect("read dir").count();
+        assert_eq!(leftovers, 1);
+    }
+
+    #[tokio::test]
+    async fn patch_appends_to_plain_files_in_place() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create storage root");
+        std::fs::write(root.join("app.log"), "hello ").expect("write");
+        let volumes = Arc::new(Volumes::single(Arc::new(Storage::new(root.clone()))));
+        let lock_manager = Arc::new(LockManager::new());
+        let patch = |range: Option<&str>, body: &'static str| {
+            let mut headers = HeaderMap::new();
+            if let Some(range) = range {
+                headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(range).expect("header"),
+                );
+            }
+            patch_file(
+                Query(RequiredPathQuery {
+                    path: "app.log".to_string(),
+                }),
+                headers,
+                Extension(volumes.clone()),
+                Extension(lock_manager.clone()),
+                Extension(Arc::new(EventHub::new())),
+                AxumBody::from(body),
+            )
+        };
+        #[cfg(unix)]
+        let inode = || {
+            use std::os::unix::fs::MetadataExt;
+            std::fs::metadata(root.join("app.log"))
+                .expect("metadata")
+                .ino()
+        };
+        #[cfg(unix)]
+        let before = inode();
+
+        let appended = patch(None, "world")
+            .await
+            .unwrap_or_else(|_| panic!("append failed"));
+        assert_eq!(appended.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            std::fs::read_to_string(root.join("app.log")).expect("read"),
+            "hello world"
+        );
+        #[cfg(unix)]
+        assert_eq!(inode(), before);
+
+        // 长度与 Content-Range 不符时截断回原长度。
+        assert!(matches!(
+            patch(Some("bytes 11-20/*"), "short").await,
+            Err(ApiError::BadRequest(_))
+        ));
+        assert_eq!(
+            std::fs::read_to_string(root.join("app.log")).expect("read"),
+            "hello world"
+        );
+    }
+
+    #[tokio::test]
+    async fn compressed_directory_write_lists_original_size_and_serves_range() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(root.join("logs")).expect("create logs dir");
+        let storage =
+            Storage::new(root.clone()).with_compression(CompressionConfig::from_dirs("logs", 3));
+        let volumes = Arc::new(Volumes::single(Arc::new(storage)));
+        let lock_manager = Arc::new(LockManager::new());
+        let data: Vec<u8> = (0..300_000).map(|i| b'a' + (i % 13) as u8).collect();
+        write_file(
+            Query(WriteQuery {
+                path: "logs/app.log".to_string(),
+                on_conflict: ConflictPolicy::Overwrite,
+            }),
+            HeaderMap::new(),
+            Extension(volumes.clone()),
+            Extension(lock_manager),
+            Extension(Arc::new(EventHub::new())),
+            AxumBody::from(data.clone()),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("write failed"));
+
+        let stored_len = std::fs::metadata(root.join("logs/app.log"))
+            .expect("stored metadata")
+            .len();
+        assert!(stored_len < data.len() as u64);
+        let entries = volumes.list_dir(Some("logs")).await.expect("list");
+        assert_eq!(entries[0].size, data.len() as u64);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::RANGE,
+            HeaderValue::from_static("bytes=131000-131099"),
+        );
+        let response = download_file(
+            Method::GET,
+            Query(DownloadQuery {
+                path: "logs/app.log".to_string(),
+                disposition: Disposition::Inline,
+            }),
+            Query(TransformQuery::default()),
+            headers,
+            Extension(volumes),
+            Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+            download_config(),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("download failed"));
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(&body[..], &data[131_000..131_100]);
+    }
+
+    #[tokio::test]
+    async fn named_volumes_route_writes_and_enforce_flags() {
+        let temp = tempdir().expect("tempdir");
+        let volume = |name: &str| {
+            let root = temp.path().join(name);
+            std::fs::create_dir_all(&root).expect("create volume root");
+            Volume::new(name, Arc::new(Storage::new(root)))
+        };
+        let volumes = Arc::new(Volumes::named(vec![
+            volume("projects").with_quota(Some(8)),
+            volume("media").with_read_only(true),
+        ]));
+        let lock_manager = Arc::new(LockManager::new());
+        let write = |path: &str, body: &'static str| {
+            write_file(
+                Query(WriteQuery {
+                    path: path.to_string(),
+                    on_conflict: ConflictPolicy::Overwrite,
+                }),
+                HeaderMap::new(),
+                Extension(volumes.clone()),
+                Extension(lock_manager.clone()),
+                Extension(Arc::new(EventHub::new())),
+                AxumBody::from(body),
+            )
+        };
+
+        assert!(write("projects/a.txt", "12345").await.is_ok());
+        assert_eq!(
+            std::fs::read(temp.path().join("projects/a.txt")).expect("read"),
+            b"12345"
+        );
+        assert!(write("projects/a.txt", "1234567").await.is_ok());
+        assert!(matches!(
+            write("projects/b.txt", "12").await,
+            Err(ApiError::InsufficientStorage(_))
+        ));
+        assert!(!temp.path().join("projects/b.txt").exists());
+        assert!(matches!(
+            write("media/a.txt", "x").await,
+            Err(ApiError::Forbidden(_))
+        ));
+
+        let roots = volumes.list_dir(None).await.expect("list roots");
+        let names: Vec<_> = roots.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["projects", "media"]);
+        let entries = volumes.list_dir(Some("projects")).await.expect("list");
+        assert_eq!(entries[0].path, "projects/a.txt");
+    }
 }
@@ -0,0 +1,78 @@
+//! 文件列表与 WebDAV 中隐藏的名称模式。
+
+/// 按名称隐藏目录项的模式集合，支持 `*`（任意字符序列）与 `?`（单个字符）通配。
+#[derive(Clone, Debug)]
+pub struct HiddenNames {
+    patterns: Vec<Vec<char>>,
+}
+
+impl HiddenNames {
+    /// 根据逗号分隔的模式列表创建集合，列表为空时返回 None。
+    pub fn from_patterns(patterns: &str) -> Option<Self> {
+        let patterns: Vec<Vec<char>> = patterns
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| pattern.chars().collect())
+            .collect();
+        if patterns.is_empty() {
+            return None;
+        }
+        Some(Self { patterns })
+    }
+
+    /// 判断名称是否匹配任一模式。
+    pub fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, &name))
+    }
+}
+
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // 最近一次 `*` 的位置及其当前匹配到的名称位置，用于回溯。
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HiddenNames;
+
+    #[test]
+    fn patterns_match_whole_names_with_wildcards() {
+        let hidden = HiddenNames::from_patterns(" .DS_Store, Thumbs.db ,*.part,~$??.docx,")
+            .expect("patterns");
+        assert!(hidden.matches(".DS_Store"));
+        assert!(hidden.matches("Thumbs.db"));
+        assert!(hidden.matches("movie.mkv.part"));
+        assert!(hidden.matches(".part"));
+        assert!(hidden.matches("~$ab.docx"));
+        assert!(!hidden.matches("~$abc.docx"));
+        assert!(!hidden.matches("Thumbs.db.bak"));
+        assert!(!hidden.matches("partial.txt"));
+        assert!(HiddenNames::from_patterns(" , ").is_none());
+    }
+}
@@ -1,12 +1,17 @@
 //! HTTP 辅助工具：请求方案识别、CORS 与安全头。
 
 use axum::body::Body as AxumBody;
-use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::http::{Extensions, HeaderMap, HeaderValue, Request, StatusCode, Version, header};
 use axum::{middleware, response::Response};
+use serde::Deserialize;
 use std::net::IpAddr;
+use tower_http::compression::predicate::{Predicate, SizeAbove};
+use tower_http::compression::{CompressionLayer, CompressionLevel};
 use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tracing::warn;
 
+use crate::config::RESPONSE_COMPRESSION_MIN_SIZE;
+
 #[derive(Clone, Copy, Debug)]
 pub enum RequestScheme {
     Http,
@@ -48,6 +53,52 @@ pub fn build_cors_layer(cors_origins: Option<&str>) -> Option<CorsLayer> {
     )
 }
 
+/// 构建按 Accept-Encoding 协商 gzip / brotli / zstd 的响应压缩 Layer。
+pub fn build_compression_layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .quality(CompressionLevel::Fastest)
+        .compress_when(SizeAbove::new(RESPONSE_COMPRESSION_MIN_SIZE).and(is_compressible_response))
+}
+
+/// 仅压缩可压缩 MIME 类型的完整响应；Range 响应保持原样，以免区间偏移指向压缩后的内容。
+fn is_compressible_response(
+    status: StatusCode,
+    _version: Version,
+    headers: &HeaderMap,
+    _extensions: &Extensions,
+) -> bool {
+    if status == StatusCode::PARTIAL_CONTENT || headers.contains_key(header::CONTENT_RANGE) {
+        return false;
+    }
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|essence| is_compressible_type(&essence.trim().to_ascii_lowercase()))
+}
+
+/// 文本、JSON、JavaScript 与 XML 类型可压缩；图片、音视频与压缩包等已压缩格式不在此列。
+/// SSE 事件流需要逐条推送，也不压缩。
+fn is_compressible_type(essence: &str) -> bool {
+    if essence == "text/event-stream" {
+        return false;
+    }
+    essence.starts_with("text/")
+        || matches!(
+            essence,
+            "application/json"
+                | "application/javascript"
+                | "application/xml"
+                | "application/wasm"
+                | "application/x-ndjson"
+                | "application/toml"
+                | "application/yaml"
+                | "application/x-sh"
+        )
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+}
+
 /// 从 `x-forwarded-for` 解析客户端 IP。
 pub fn extract_forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
     headers
@@ -75,6 +126,91 @@ pub fn is_https_request(headers: &HeaderMap, scheme: RequestScheme) -> bool {
     scheme.is_https()
 }
 
+/// 生成对外链接的基础地址：优先使用配置的公开地址，否则按请求协议与 Host（含反向代理头）拼接。
+pub fn link_base_url(
+    public_url: Option<&str>,
+    headers: &HeaderMap,
+    scheme: RequestScheme,
+) -> Option<String> {
+    if let Some(url) = public_url {
+        return Some(url.trim_end_matches('/').to_string());
+    }
+    let host = headers
+        .get("x-forwarded-host")
+        .or_else(|| headers.get(header::HOST))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())?;
+    let scheme = if is_https_request(headers, scheme) {
+        "https"
+    } else {
+        "http"
+    };
+    Some(format!("{scheme}://{host}"))
+}
+
+/// 下载响应的 Content-Disposition 类型。
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Disposition {
+    /// 在浏览器中直接展示（默认）。
+    #[default]
+    Inline,
+    /// 提示浏览器保存文件。
+    Attachment,
+}
+
+/// 随可执行内容一同发送的沙箱 CSP，即使客户端忽略 `attachment` 也无法执行脚本。
+pub const ACTIVE_CONTENT_CSP: &str = "sandbox; default-src 'none'";
+
+/// 判断 MIME 类型是否可能在浏览器中以站点源执行脚本（HTML、SVG 及其他 XML）。
+pub fn is_active_content(essence: &str) -> bool {
+    matches!(essence, "text/html" | "text/xml" | "application/xml") || essence.ends_with("+xml")
+}
+
+/// 按 RFC 6266 构建 Content-Disposition：`filename` 为 ASCII 回退，
+/// `filename*` 以 RFC 5987 编码保留原始 UTF-8 文件名。
+pub fn content_disposition(disposition: Disposition, filename: &str) -> HeaderValue {
+    let kind = match disposition {
+        Disposition::Inline => "inline",
+        Disposition::Attachment => "attachment",
+    };
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' | '%' => '_',
+            c if c == ' ' || c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    HeaderValue::from_str(&format!(
+        "{kind}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}"
+    ))
+    .unwrap_or_else(|_| HeaderValue::from_static("attachment"))
+}
+
+/// 对 URL 路径段做百分号编码，仅保留 RFC 3986 非保留字符。
+pub fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
 /// 添加基础安全响应头。
 pub async fn add_security_headers(
     request: Request<AxumBody>,
@@ -92,3 +228,44 @@ pub async fn add_security_headers(
     );
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_disposition_encodes_non_ascii_filenames() {
+        assert_eq!(
+            content_disposition(Disposition::Attachment, "季度 报告\"v2\"%.pdf"),
+            "attachment; filename=\"__ ___v2__.pdf\"; \
+             filename*=UTF-8''%E5%AD%A3%E5%BA%A6%20%E6%8A%A5%E5%91%8A%22v2%22%25.pdf"
+        );
+        assert_eq!(
+            content_disposition(Disposition::Inline, "a.txt"),
+            "inline; filename=\"a.txt\"; filename*=UTF-8''a.txt"
+        );
+        assert!(is_active_content("text/html"));
+        assert!(is_active_content("image/svg+xml"));
+        assert!(!is_active_content("image/png"));
+    }
+
+    #[test]
+    fn compression_skips_ranges_and_compressed_formats() {
+        let compressible = |status: StatusCode, content_type: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+            is_compressible_response(status, Version::HTTP_11, &headers, &Extensions::new())
+        };
+        assert!(compressible(StatusCode::OK, "application/json"));
+        assert!(compressible(StatusCode::OK, "text/plain; charset=utf-8"));
+        assert!(compressible(StatusCode::OK, "image/svg+xml"));
+        assert!(!compressible(StatusCode::PARTIAL_CONTENT, "text/plain"));
+        assert!(!compressible(
+            StatusCode::PARTIAL_CONTENT,
+            "multipart/byteranges; boundary=x"
+        ));
+        assert!(!compressible(StatusCode::OK, "image/jpeg"));
+        assert!(!compressible(StatusCode::OK, "application/zip"));
+        assert!(!compressible(StatusCode::OK, "text/event-stream"));
+    }
+}
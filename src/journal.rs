@@ -0,0 +1,327 @@
+//! 持久化的变更日志与 `/api/changes` 增量查询接口。
+//!
+//! 日志文件为 JSON Lines：首行记录日志标识，其后每行一条带递增序号的变更。
+//! 游标形如 `{id}:{seq}`，日志重建（标识变化）或游标早于保留范围时要求客户端全量重新同步。
+
+use axum::extract::{Extension, Query};
+use axum::response::Json as JsonResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, broadcast};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::{CHANGES_PAGE_LIMIT, JOURNAL_MAX_ENTRIES};
+use crate::error::ApiError;
+use crate::events::{ChangeEvent, EventHub};
+use crate::storage::format_timestamp;
+
+/// 日志中的一条变更记录。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// 递增序号。
+    pub seq: u64,
+    /// 记录时间。
+    pub time: String,
+    #[serde(flatten)]
+    pub change: ChangeEvent,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalHeader {
+    id: String,
+}
+
+/// 变更日志：内存中保留最近的记录，并追加写入日志文件。
+pub struct ChangeJournal {
+    inner: Mutex<JournalState>,
+}
+
+struct JournalState {
+    path: PathBuf,
+    id: String,
+    file: File,
+    entries: VecDeque<JournalEntry>,
+    /// 最后一条记录的序号（尚无记录时为 0）。
+    last_seq: u64,
+    /// 日志文件中的记录行数，超过保留数量两倍时压缩。
+    file_lines: usize,
+}
+
+/// `/api/changes` 的查询结果。
+#[derive(Debug, Serialize)]
+pub struct ChangesPage {
+    /// 下次查询使用的游标。
+    pub cursor: String,
+    /// 游标之后按序排列的变更。
+    pub changes: Vec<JournalEntry>,
+    /// 是否还有更多变更未返回。
+    pub has_more: bool,
+    /// 游标无效或已过期，客户端需重新列出全部文件后从新游标继续。
+    pub reset: bool,
+}
+
+impl ChangeJournal {
+    /// 打开（或创建）日志文件并加载最近的记录；文件损坏时重建并更换标识。
+    pub async fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let parsed = match fs::read_to_string(path).await {
+            Ok(content) => {
+                let parsed = parse_journal(&content);
+                if parsed.is_none() {
+                    warn!(path = ?path, "journal is unreadable, starting a new one");
+                }
+                parsed
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+        let (id, entries) = parsed.unwrap_or_else(|| (Uuid::new_v4().to_string(), VecDeque::new()));
+        let last_seq = entries.back().map_or(0, |entry| entry.seq);
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .await?;
+        let mut state = JournalState {
+            path: path.to_path_buf(),
+            id,
+            file,
+            entries,
+            last_seq,
+            file_lines: 0,
+        };
+        // 启动时重写日志：写入文件头并丢弃中断写入留下的不完整行。
+        state.compact().await?;
+        Ok(Self {
+            inner: Mutex::new(state),
+        })
+    }
+
+    /// 追加一条变更并返回其序号。
+    pub async fn append(&self, change: ChangeEvent) -> io::Result<u64> {
+        let mut state = self.inner.lock().await;
+        state.last_seq += 1;
+        let entry = JournalEntry {
+            seq: state.last_seq,
+            time: format_timestamp(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default(),
+            )
+            .unwrap_or_default(),
+            change,
+        };
+        let mut line = serde_json::to_vec(&entry).map_err(io::Error::other)?;
+        line.push(b'\n');
+        state.entries.push_back(entry);
+        if state.entries.len() > JOURNAL_MAX_ENTRIES {
+            state.entries.pop_front();
+        }
+        state.file.write_all(&line).await?;
+        state.file_lines += 1;
+        if state.file_lines > JOURNAL_MAX_ENTRIES * 2 {
+            state.compact().await?;
+        }
+        Ok(state.last_seq)
+    }
+
+    /// 返回游标之后的变更；游标缺失、属于其他日志或早于保留范围时返回 `reset`。
+    pub async fn changes_since(&self, cursor: Option<&str>, limit: usize) -> ChangesPage {
+        let state = self.inner.lock().await;
+        let since = cursor
+            .and_then(|cursor| cursor.split_once(':'))
+            .filter(|(id, _)| *id == state.id)
+            .and_then(|(_, seq)| seq.parse::<u64>().ok())
+            .filter(|seq| *seq <= state.last_seq);
+        // 保留范围内最早记录之前的序号仍可增量同步。
+        let oldest = state
+            .entries
+            .front()
+            .map_or(state.last_seq, |entry| entry.seq - 1);
+        let Some(since) = since.filter(|seq| *seq >= oldest) else {
+            return ChangesPage {
+                cursor: state.cursor(state.last_seq),
+                changes: Vec::new(),
+                has_more: false,
+                reset: true,
+            };
+        };
+        let changes: Vec<JournalEntry> = state
+            .entries
+            .iter()
+            .skip_while(|entry| entry.seq <= since)
+            .take(limit)
+            .cloned()
+            .collect();
+        let next = changes.last().map_or(since, |entry| entry.seq);
+        ChangesPage {
+            cursor: state.cursor(next),
+            has_more: next < state.last_seq,
+            changes,
+            reset: false,
+        }
+    }
+}
+
+impl JournalState {
+    fn cursor(&self, seq: u64) -> String {
+        format!("{}:{}", self.id, seq)
+    }
+
+    /// 以当前保留的记录重写日志文件（写入临时文件后原子替换）。
+    async fn compact(&mut self) -> io::Result<()> {
+        let mut content = serde_json::to_vec(&JournalHeader {
+            id: self.id.clone(),
+        })
+        .map_err(io::Error::other)?;
+        content.push(b'\n');
+        for entry in &self.entries {
+            serde_json::to_writer(&mut content, entry).map_err(io::Error::other)?;
+            content.push(b'\n');
+        }
+        let temp_path = self.path.with_extension(format!("tmp.{}", Uuid::new_v4()));
+        let mut temp = File::create(&temp_path).await?;
+        temp.write_all(&content).await?;
+        temp.sync_all().await?;
+        drop(temp);
+        if let Err(err) = fs::rename(&temp_path, &self.path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(err);
+        }
+        self.file = OpenOptions::new().append(true).open(&self.path).await?;
+        self.file_lines = self.entries.len();
+        Ok(())
+    }
+}
+
+/// 解析日志文件，返回标识与最近的记录。无法解析的行（写入中断）被忽略。
+fn parse_journal(content: &str) -> Option<(String, VecDeque<JournalEntry>)> {
+    let mut lines = content.lines();
+    let header: JournalHeader = serde_json::from_str(lines.next()?).ok()?;
+    let mut entries = VecDeque::new();
+    for line in lines.filter(|line| !line.trim().is_empty()) {
+        let Ok(entry) = serde_json::from_str::<JournalEntry>(line) else {
+            continue;
+        };
+        if entries
+            .back()
+            .is_some_and(|last: &JournalEntry| entry.seq <= last.seq)
+        {
+            return None;
+        }
+        entries.push_back(entry);
+        if entries.len() > JOURNAL_MAX_ENTRIES {
+            entries.pop_front();
+        }
+    }
+    Some((header.id, entries))
+}
+
+/// 将事件中心的变更写入日志。启动时及事件丢失时写入 `rescan` 记录，
+/// 服务停止期间的外部修改无法感知，客户端读到该记录后应全量重新同步。
+pub fn spawn_journal_writer(journal: Arc<ChangeJournal>, hub: &EventHub) {
+    let mut rx = hub.subscribe();
+    tokio::spawn(async move {
+        let mut next = Some(ChangeEvent::rescan());
+        loop {
+            if let Some(change) = next.take()
+                && let Err(err) = journal.append(change).await
+            {
+                warn!(error = %err, "failed to append change journal");
+            }
+            next = match rx.recv().await {
+                Ok(change) => Some(change),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "change journal lagged behind events");
+                    Some(ChangeEvent::rescan())
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+        }
+        info!("change journal writer stopped");
+    });
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChangesQuery {
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+/// 返回游标之后的变更列表与新游标。
+pub async fn list_changes(
+    Query(query): Query<ChangesQuery>,
+    Extension(journal): Extension<Arc<ChangeJournal>>,
+) -> Result<JsonResponse<ChangesPage>, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(CHANGES_PAGE_LIMIT)
+        .clamp(1, CHANGES_PAGE_LIMIT);
+    let page = journal.changes_since(query.cursor.as_deref(), limit).await;
+    info!(
+        count = page.changes.len(),
+        reset = page.reset,
+        "list changes"
+    );
+    Ok(JsonResponse(page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ChangeKind;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn cursors_survive_restart_and_expire_with_retention() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("journal/changes.jsonl");
+        let change = |path: &str| ChangeEvent::new(ChangeKind::Created, path.into(), false);
+
+        let journal = ChangeJournal::open(&path).await.expect("open");
+        let initial = journal.changes_since(None, 10).await;
+        assert!(initial.reset);
+        for name in ["a", "b", "c"] {
+            journal.append(change(name)).await.expect("append");
+        }
+        let page = journal.changes_since(Some(&initial.cursor), 2).await;
+        assert!(!page.reset && page.has_more);
+        assert_eq!(page.changes.len(), 2);
+        assert_eq!(page.changes[0].change.path, "a");
+        drop(journal);
+
+        let journal = ChangeJournal::open(&path).await.expect("reopen");
+        let page = journal.changes_since(Some(&page.cursor), 10).await;
+        assert!(!page.reset && !page.has_more);
+        assert_eq!(page.changes.len(), 1);
+        assert_eq!(page.changes[0].seq, 3);
+        assert_eq!(page.changes[0].change.path, "c");
+        let latest = page.cursor;
+        assert!(
+            journal
+                .changes_since(Some(&latest), 10)
+                .await
+                .changes
+                .is_empty()
+        );
+
+        for index in 0..=JOURNAL_MAX_ENTRIES {
+            journal
+                .append(change(&format!("f{index}")))
+                .await
+                .expect("append");
+        }
+        assert!(journal.changes_since(Some(&latest), 10).await.reset);
+        let other = format!("{}:0", Uuid::new_v4());
+        assert!(journal.changes_since(Some(&other), 10).await.reset);
+    }
+}
@@ -0,0 +1,420 @@
+//! 对外链接存储：随机令牌映射到存储路径，附带过期时间与下载次数限制，持久化到 JSON 文件。
+
+use argon2::Argon2;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::config::LINK_TOKEN_LEN;
+use crate::volumes::Volumes;
+
+/// 链接类型。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// 一次性上传生成的临时下载链接，链接失效时连同文件一起删除。
+    Transfer,
+    /// 公开分享链接，指向已有的文件或目录（目录只读浏览）。
+    Share,
+    /// 文件请求链接：只允许向指定目录上传，不能查看任何内容。
+    Request,
+}
+
+/// 一条对外链接。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+    pub token: String,
+    pub kind: LinkKind,
+    /// 链接指向的对外路径（含卷名）。
+    pub path: String,
+    /// 创建时间（Unix 秒）。
+    pub created_at: u64,
+    /// 过期时间（Unix 秒），None 表示不过期。
+    pub expires_at: Option<u64>,
+    /// 允许的下载次数，None 表示不限制。
+    pub max_downloads: Option<u64>,
+    /// 已下载次数。
+    #[serde(default)]
+    pub downloads: u64,
+    /// 访问密码摘要（见 [`hash_password`]），None 表示无需密码。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    /// 文件请求允许上传的文件数，None 表示不限制。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<u64>,
+    /// 文件请求允许上传的总字节数，None 表示不限制。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// 文件请求已接收的文件数。
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub files: u64,
+    /// 文件请求已接收的字节数。
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub bytes: u64,
+}
+
+impl Link {
+    /// 是否仍可访问：未过期且下载次数、上传文件数与字节数均未用完。
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+            && self
+                .max_downloads
+                .is_none_or(|max_downloads| self.downloads < max_downloads)
+            && self
+                .max_files
+                .is_none_or(|max_files| self.files < max_files)
+            && self
+                .max_bytes
+                .is_none_or(|max_bytes| self.bytes < max_bytes)
+    }
+
+    /// 文件请求剩余可接收的字节数，None 表示不限制。
+    pub fn remaining_bytes(&self) -> Option<u64> {
+        self.max_bytes
+            .map(|max_bytes| max_bytes.saturating_sub(self.bytes))
+    }
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+#[derive(Debug)]
+pub struct LinkConfig {
+    /// 生成链接使用的公开地址，未配置时按请求的 Host 拼接。
+    pub public_url: Option<String>,
+}
+
+/// 链接存储：内存中保存全部链接，每次修改后整体重写链接文件。
+pub struct LinkStore {
+    path: PathBuf,
+    links: Mutex<HashMap<String, Link>>,
+}
+
+impl LinkStore {
+    /// 打开（或创建）链接文件；文件无法解析时返回错误。
+    pub async fn open(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let links = match fs::read(path).await {
+            // 文件损坏时拒绝启动，避免下次保存覆盖仍可手动恢复的链接。
+            Ok(content) => serde_json::from_slice::<Vec<Link>>(&content).map_err(|err| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("link file {} is unreadable: {err}", path.display()),
+                )
+            })?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            links: Mutex::new(
+                links
+                    .into_iter()
+                    .map(|link| (link.token.clone(), link))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// 保存新链接。
+    pub async fn insert(&self, link: Link) -> io::Result<()> {
+        let mut links = self.links.lock().await;
+        links.insert(link.token.clone(), link);
+        self.save(&links).await
+    }
+
+    /// 返回仍可访问的链接。
+    pub async fn get(&self, token: &str) -> Option<Link> {
+        let links = self.links.lock().await;
+        links
+            .get(token)
+            .filter(|link| link.is_active(unix_now()))
+            .cloned()
+    }
+
+    /// 返回全部链接（含尚未清理的失效链接），按创建时间排序。
+    pub async fn list(&self) -> Vec<Link> {
+        let links = self.links.lock().await;
+        let mut list: Vec<Link> = links.values().cloned().collect();
+        list.sort_by(|a, b| (a.created_at, &a.token).cmp(&(b.created_at, &b.token)));
+        list
+    }
+
+    /// 撤销链接并返回被移除的链接。
+    pub async fn remove(&self, token: &str) -> io::Result<Option<Link>> {
+        let mut links = self.links.lock().await;
+        let removed = links.remove(token);
+        if removed.is_some() {
+            self.save(&links).await?;
+        }
+        Ok(removed)
+    }
+
+    /// 占用一次下载次数并返回链接；链接不存在、已过期或次数用完时返回 None。
+    pub async fn claim_download(&self, token: &str) -> io::Result<Option<Link>> {
+        let mut links = self.links.lock().await;
+        let Some(link) = links
+            .get_mut(token)
+            .filter(|link| link.is_active(unix_now()))
+        else {
+            return Ok(None);
+        };
+        link.downloads += 1;
+        let link = link.clone();
+        self.save(&links).await?;
+        Ok(Some(link))
+    }
+
+    /// 归还下载失败时占用的次数。
+    pub async fn release_download(&self, token: &str) -> io::Result<()> {
+        let mut links = self.links.lock().await;
+        let Some(link) = links.get_mut(token) else {
+            return Ok(());
+        };
+        link.downloads = link.downloads.saturating_sub(1);
+        self.save(&links).await
+    }
+
+    /// 为文件请求记录一个大小为 `size` 的文件；链接不可用或超出剩余字节数时返回 None。
+    pub async fn claim_upload(&self, token: &str, size: u64) -> io::Result<Option<Link>> {
+        let mut links = self.links.lock().await;
+        let Some(link) = links.get_mut(token).filter(|link| {
+            link.kind == LinkKind::Request
+                && link.is_active(unix_now())
+                && link
+                    .remaining_bytes()
+                    .is_none_or(|remaining| size <= remaining)
+        }) else {
+            return Ok(None);
+        };
+        link.files += 1;
+        link.bytes += size;
+        let link = link.clone();
+        self.save(&links).await?;
+        Ok(Some(link))
+    }
+
+    /// 归还写入失败的文件占用的次数与字节数。
+    pub async fn release_upload(&self, token: &str, size: u64) -> io::Result<()> {
+        let mut links = self.links.lock().await;
+        let Some(link) = links.get_mut(token) else {
+            return Ok(());
+        };
+        link.files = link.files.saturating_sub(1);
+        link.bytes = link.bytes.saturating_sub(size);
+        self.save(&links).await
+    }
+
+    /// 移除已失效的链接并返回被移除的链接。
+    pub async fn prune(&self) -> io::Result<Vec<Link>> {
+        let mut links = self.links.lock().await;
+        let now = unix_now();
+        let expired: Vec<String> = links
+            .values()
+            .filter(|link| !link.is_active(now))
+            .map(|link| link.token.clone())
+            .collect();
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+        let removed = expired
+            .iter()
+            .filter_map(|token| links.remove(token))
+            .collect();
+        self.save(&links).await?;
+        Ok(removed)
+    }
+
+    /// 将全部链接写入临时文件后原子替换链接文件。
+    async fn save(&self, links: &HashMap<String, Link>) -> io::Result<()> {
+        let mut sorted: Vec<&Link> = links.values().collect();
+        sorted.sort_by(|a, b| (a.created_at, &a.token).cmp(&(b.created_at, &b.token)));
+        let content = serde_json::to_vec_pretty(&sorted).map_err(io::Error::other)?;
+        let temp_path = self.path.with_extension(format!("tmp.{}", Uuid::new_v4()));
+        let mut temp = File::create(&temp_path).await?;
+        temp.write_all(&content).await?;
+        temp.sync_all().await?;
+        drop(temp);
+        if let Err(err) = fs::rename(&temp_path, &self.path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+/// 生成由字母与数字组成的随机令牌。
+pub fn new_token() -> String {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let mut token = String::with_capacity(LINK_TOKEN_LEN);
+    while token.len() < LINK_TOKEN_LEN {
+        let mut value = Uuid::new_v4().as_u128();
+        // UUID v4 含 122 位随机数，每个 UUID 取 20 个字符（约 119 位）。
+        for _ in 0..20 {
+            if token.len() == LINK_TOKEN_LEN {
+                break;
+            }
+            token.push(ALPHABET[(value % 62) as usize] as char);
+            value /= 62;
+        }
+    }
+    token
+}
+
+/// 当前 Unix 时间（秒）。
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+/// 移除失效链接，并删除一次性上传链接的文件。
+pub async fn prune_links(volumes: &Volumes, links: &LinkStore) -> io::Result<usize> {
+    let removed = links.prune().await?;
+    for link in &removed {
+        remove_link_files(volumes, link).await;
+    }
+    Ok(removed.len())
+}
+
+/// 删除一次性上传链接所在的令牌目录；其他类型的链接不拥有文件，不做处理。
+pub async fn remove_link_files(volumes: &Volumes, link: &Link) {
+    if link.kind != LinkKind::Transfer {
+        return;
+    }
+    let Some((token_dir, _)) = link.path.rsplit_once('/') else {
+        return;
+    };
+    let deleted = match volumes.resolve_writable(token_dir) {
+        Ok((volume, relative)) => volume.storage().delete_path(relative).await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+    match deleted {
+        Ok(()) => info!(
+            token = link.token,
+            path = link.path,
+            "removed transfer files"
+        ),
+        Err(err) => {
+            warn!(token = link.token, path = link.path, error = ?err, "failed to remove transfer files")
+        }
+    }
+}
+
+/// 以随机盐计算访问密码的 Argon2id 摘要（PHC 字符串格式）；计算量较大，在阻塞线程中执行。
+pub async fn hash_password(password: String) -> io::Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| io::Error::other(err.to_string()))
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// 校验访问密码是否与摘要匹配；摘要输出以常数时间比较，不会按首个差异提前返回。
+pub async fn verify_password(hash: String, password: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&hash).is_ok_and(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+    })
+    .await
+    .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn download_limits_persist_and_exhausted_links_are_pruned() {
+        let temp = tempdir().expect("tempdir");
+        let path = temp.path().join("links/links.json");
+        let store = LinkStore::open(&path).await.expect("open");
+        let token = new_token();
+        assert_eq!(token.len(), LINK_TOKEN_LEN);
+        assert!(token.chars().all(|ch| ch.is_ascii_alphanumeric()));
+        store
+            .insert(Link {
+                token: token.clone(),
+                kind: LinkKind::Transfer,
+                path: "transfers/x/a.txt".into(),
+                created_at: unix_now(),
+                expires_at: Some(unix_now() + 60),
+                max_downloads: Some(2),
+                downloads: 0,
+                password_hash: None,
+                max_files: None,
+                max_bytes: None,
+                files: 0,
+                bytes: 0,
+            })
+            .await
+            .expect("insert");
+        store
+            .insert(Link {
+                token: "expired".into(),
+                kind: LinkKind::Transfer,
+                path: "transfers/y/b.txt".into(),
+                created_at: 0,
+                expires_at: Some(1),
+                max_downloads: None,
+                downloads: 0,
+                password_hash: None,
+                max_files: None,
+                max_bytes: None,
+                files: 0,
+                bytes: 0,
+            })
+            .await
+            .expect("insert");
+        assert!(store.get("expired").await.is_none());
+
+        assert!(store.claim_download(&token).await.expect("claim").is_some());
+        drop(store);
+
+        let store = LinkStore::open(&path).await.expect("reopen");
+        assert_eq!(store.get(&token).await.expect("link").downloads, 1);
+        store.claim_download(&token).await.expect("claim");
+        store.release_download(&token).await.expect("release");
+        store.claim_download(&token).await.expect("claim");
+        assert!(store.claim_download(&token).await.expect("claim").is_none());
+
+        let hash = hash_password("secret".into()).await.expect("hash");
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password(hash.clone(), "secret".into()).await);
+        assert!(!verify_password(hash.clone(), "Secret".into()).await);
+        assert!(!verify_password("salt:digest".into(), "secret".into()).await);
+        assert_ne!(hash, hash_password("secret".into()).await.expect("hash"));
+
+        let removed = store.prune().await.expect("prune");
+        assert_eq!(removed.len(), 2);
+        assert!(store.prune().await.expect("prune").is_empty());
+        drop(store);
+
+        std::fs::write(&path, b"{broken").expect("corrupt");
+        let err = LinkStore::open(&path).await.err().expect("corrupt file");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(std::fs::read(&path).expect("read"), b"{broken");
+    }
+}
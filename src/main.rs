@@ -6,32 +6,50 @@
 mod atomic;
 mod auth;
 mod background;
+mod beneath;
+mod cache;
+mod compression;
 mod config;
+mod content;
+mod crypto;
+mod dav_fs;
 mod error;
 mod etag;
+mod events;
+mod file_requests;
 mod files;
 mod frontend;
+mod hidden;
 mod http;
+mod journal;
+mod links;
 mod locking;
 mod logging;
+mod metadata;
+mod preview;
+mod shares;
+mod stat;
 mod storage;
+mod thumbnail;
 mod tls;
+mod transfer;
 mod upload;
 mod version;
+mod volumes;
 mod webdav;
 mod webdav_lock;
 
 use axum::extract::{DefaultBodyLimit, Extension, connect_info::ConnectInfo};
-use axum::http::Request;
+use axum::http::{HeaderValue, Request};
 use axum::routing::{any, delete, get, patch, post, put};
 use axum::{Router, middleware};
 use axum_server::Handle;
 use clap::Parser;
-use dav_server::{DavHandler, localfs::LocalFs};
+use dav_server::DavHandler;
 use shadow_rs::shadow;
 use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::signal;
@@ -41,11 +59,23 @@ use tracing::{Level, info, info_span};
 
 use crate::auth::AuthConfig;
 use crate::background::spawn_background_tasks;
-use crate::config::Args;
-use crate::http::{RequestScheme, build_cors_layer};
+use crate::compression::CompressionConfig;
+use crate::config::{Args, Command};
+use crate::crypto::Keyring;
+use crate::dav_fs::build_dav_filesystem;
+use crate::events::{EventHub, spawn_watchers};
+use crate::files::DownloadConfig;
+use crate::hidden::HiddenNames;
+use crate::http::{RequestScheme, build_compression_layer, build_cors_layer};
+use crate::journal::{ChangeJournal, spawn_journal_writer};
+use crate::links::{LinkConfig, LinkStore};
 use crate::locking::LockManager;
+use crate::metadata::MetadataExtractor;
 use crate::storage::Storage;
+use crate::thumbnail::{Thumbnailer, parse_transform_sizes, spawn_pregeneration};
+use crate::transfer::TransferConfig;
 use crate::upload::UploadConfig;
+use crate::volumes::Volumes;
 use crate::webdav_lock::WebDavLockSystem;
 
 shadow!(build);
@@ -57,7 +87,43 @@ async fn main() -> Result<(), std::io::Error> {
 
     let args = Args::parse();
     let storage_dir = args.storage_dir.clone();
-    let storage = Arc::new(Storage::new(PathBuf::from(storage_dir)));
+    let keyring = Keyring::from_args(&args)?;
+    let compression = args
+        .compress_dirs
+        .as_deref()
+        .and_then(|dirs| CompressionConfig::from_dirs(dirs, args.compress_level));
+    let hidden = args
+        .hidden_names
+        .as_deref()
+        .and_then(HiddenNames::from_patterns);
+    let build_storage = |root: PathBuf| {
+        Storage::new(root)
+            .with_keyring(keyring.clone())
+            .with_compression(compression.clone())
+            .with_symlink_policy(args.symlinks)
+            .with_hidden(hidden.clone())
+    };
+    let volumes = if args.volumes.is_empty() {
+        Volumes::single(Arc::new(build_storage(PathBuf::from(storage_dir))))
+    } else {
+        Volumes::from_specs(&args.volumes, build_storage)?
+    };
+    let volumes = Arc::new(volumes);
+    volumes.ensure_roots().await?;
+    if let Some(Command::Reencrypt { dry_run }) = args.command {
+        for volume in volumes.iter() {
+            let summary = content::reencrypt_storage(volume.storage(), dry_run).await?;
+            info!(
+                volume = volume.name(),
+                scanned = summary.scanned,
+                reencrypted = summary.reencrypted,
+                failed = summary.failed,
+                dry_run,
+                "re-encryption finished"
+            );
+        }
+        return Ok(());
+    }
     let auth_config = Arc::new(AuthConfig {
         username: args.auth_user.clone(),
         password: args.auth_pass.clone(),
@@ -74,15 +140,49 @@ async fn main() -> Result<(), std::io::Error> {
         max_concurrent: args.upload_max_concurrent,
         temp_ttl: Duration::from_secs(args.upload_temp_ttl_secs),
     });
+    let download_config = Arc::new(DownloadConfig {
+        cache_control: HeaderValue::from_str(&args.download_cache_control).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid download Cache-Control value",
+            )
+        })?,
+    });
     let lock_manager = Arc::new(LockManager::new());
-    let storage_for_tasks = storage.clone();
+    let event_hub = Arc::new(EventHub::new());
+    let journal = Arc::new(ChangeJournal::open(Path::new(&args.journal_file)).await?);
+    spawn_journal_writer(journal.clone(), &event_hub);
+    let links = Arc::new(LinkStore::open(Path::new(&args.links_file)).await?);
+    let transfer_config = Arc::new(TransferConfig {
+        dir: args.transfer_dir.clone(),
+        default_days: args.transfer_default_days,
+    });
+    let link_config = Arc::new(LinkConfig {
+        public_url: args.public_url.clone(),
+    });
+    let thumbnailer = Arc::new(
+        Thumbnailer::new(PathBuf::from(&args.thumbnail_cache_dir))
+            .with_transform_sizes(parse_transform_sizes(&args.image_transform_sizes)?),
+    );
+    let metadata_extractor = Arc::new(MetadataExtractor::new(PathBuf::from(
+        &args.metadata_cache_dir,
+    )));
+    if args.thumbnail_pregenerate {
+        spawn_pregeneration(volumes.clone(), thumbnailer.clone(), &event_hub);
+    }
+    if !args.no_watch {
+        spawn_watchers(volumes.clone(), event_hub.clone());
+    }
+    let volumes_for_tasks = volumes.clone();
     let auth_for_tasks = auth_config.clone();
     let upload_for_tasks = upload_config.clone();
-    storage.ensure_root().await?;
+    let thumbnailer_for_tasks = thumbnailer.clone();
+    let metadata_for_tasks = metadata_extractor.clone();
+    let links_for_tasks = links.clone();
     let dav_handler = Arc::new(
         DavHandler::builder()
             .strip_prefix("/webdav")
-            .filesystem(LocalFs::new(storage.root_path(), false, false, false))
+            .filesystem(build_dav_filesystem(&volumes))
             .locksystem(WebDavLockSystem::new())
             .build_handler(),
     );
@@ -91,10 +191,24 @@ async fn main() -> Result<(), std::io::Error> {
         .route("/webdav", any(webdav::webdav_handler))
         .route("/webdav/{*path}", any(webdav::webdav_handler))
         .route("/api/files/list", get(files::list_files))
-        .route("/api/files/download", get(files::download_file))
-        .route("/api/files/write", put(files::write_file))
+        .route(
+            "/api/files/download",
+            get(files::download_file).head(files::download_file),
+        )
+        .route("/api/files/thumbnail", get(thumbnail::get_thumbnail))
+        .route("/api/files/metadata", get(metadata::get_metadata))
+        .route("/api/files/preview", get(preview::preview_file))
+        .route("/api/files/stat", get(stat::stat_file))
+        .route(
+            "/api/files/write",
+            put(files::write_file).patch(files::patch_file),
+        )
         .route("/api/files/delete", delete(files::delete_entry))
         .route("/api/files/mkdir", post(files::create_directory))
+        .route(
+            "/api/files/upload",
+            post(upload::upload_form).layer(DefaultBodyLimit::disable()),
+        )
         .route("/api/upload/init", post(upload::init_upload))
         .route(
             "/api/upload/chunk",
@@ -102,9 +216,61 @@ async fn main() -> Result<(), std::io::Error> {
         )
         .route("/api/upload/complete", post(upload::complete_upload))
         .route("/api/upload/abort", post(upload::abort_upload))
+        .route("/api/upload/batch/init", post(upload::init_batch_upload))
+        .route(
+            "/api/upload/batch/complete",
+            post(upload::complete_batch_upload),
+        )
+        .route("/api/upload/batch/abort", post(upload::abort_batch_upload))
+        .route(
+            "/api/transfer/{name}",
+            put(transfer::put_transfer).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/t/{token}/{name}",
+            get(transfer::get_transfer).head(transfer::get_transfer),
+        )
+        .route(
+            "/s/{token}",
+            get(shares::open_share).head(shares::open_share),
+        )
+        .route(
+            "/s/{token}/{*path}",
+            get(shares::open_share_path).head(shares::open_share_path),
+        )
+        .route(
+            "/api/links",
+            get(shares::list_links).post(shares::create_share),
+        )
+        .route("/api/links/{token}", delete(shares::revoke_link))
+        .route("/api/requests", post(file_requests::create_file_request))
+        .route(
+            "/r/{token}",
+            get(file_requests::request_status)
+                .post(file_requests::request_form_upload)
+                .layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/r/{token}/upload/init",
+            post(file_requests::request_upload_init),
+        )
+        .route(
+            "/r/{token}/upload/chunk",
+            patch(file_requests::request_upload_chunk).layer(DefaultBodyLimit::disable()),
+        )
+        .route(
+            "/r/{token}/upload/complete",
+            post(file_requests::request_upload_complete),
+        )
+        .route(
+            "/r/{token}/upload/abort",
+            post(file_requests::request_upload_abort),
+        )
         .route("/api/auth/login", post(auth::auth_login))
         .route("/api/auth/logout", post(auth::auth_logout))
         .route("/api/auth/status", get(auth::auth_status))
+        .route("/api/events", get(events::events_stream))
+        .route("/api/changes", get(journal::list_changes))
         .route("/api/version", get(version::get_version_info))
         .fallback(frontend::serve_frontend)
         .layer(middleware::from_fn(auth::auth_middleware))
@@ -136,12 +302,23 @@ async fn main() -> Result<(), std::io::Error> {
                 .on_request(DefaultOnRequest::new().level(Level::DEBUG))
                 .on_response(DefaultOnResponse::new().level(Level::DEBUG)),
         )
-        .layer(Extension(storage))
+        .layer(Extension(volumes))
         .layer(Extension(auth_config))
         .layer(Extension(upload_config))
+        .layer(Extension(download_config))
         .layer(Extension(lock_manager))
+        .layer(Extension(event_hub))
+        .layer(Extension(journal))
+        .layer(Extension(thumbnailer))
+        .layer(Extension(metadata_extractor))
+        .layer(Extension(links))
+        .layer(Extension(transfer_config))
+        .layer(Extension(link_config))
         .layer(Extension(dav_handler));
 
+    if !args.no_response_compression {
+        app = app.layer(build_compression_layer());
+    }
     if let Some(cors_layer) = build_cors_layer(args.cors_origins.as_deref()) {
         app = app.layer(cors_layer);
     }
@@ -168,7 +345,14 @@ async fn main() -> Result<(), std::io::Error> {
         .handle(handle.clone())
         .serve(https_app.into_make_service_with_connect_info::<SocketAddr>());
 
-    spawn_background_tasks(storage_for_tasks, auth_for_tasks, upload_for_tasks);
+    spawn_background_tasks(
+        volumes_for_tasks,
+        auth_for_tasks,
+        upload_for_tasks,
+        thumbnailer_for_tasks,
+        metadata_for_tasks,
+        links_for_tasks,
+    );
     tokio::select! {
         result = http_server => result?,
         result = https_server => result?,
@@ -0,0 +1,1530 @@
+//! 媒体与文档元数据提取：图片 EXIF、音频标签与时长、视频容器与流信息、PDF 页数。
+//!
+//! 全部以纯 Rust 解析，结果按源文件 ETag 缓存到磁盘；启用存储加密时缓存同样加密保存。
+
+use axum::extract::{Extension, Query};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Json as JsonResponse, Response};
+use chrono::NaiveDateTime;
+use exif::{In, Tag, Value};
+use image::ImageReader;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+use tracing::{debug, info, warn};
+
+use crate::cache::DiskCache;
+use crate::config::{
+    METADATA_CACHE_TTL_SECS, METADATA_MAX_CONCURRENT, METADATA_MAX_NESTING, METADATA_MAX_PDF_SIZE,
+};
+use crate::content::ContentReader;
+use crate::crypto::hex_encode;
+use crate::error::ApiError;
+use crate::etag::none_match;
+use crate::storage::{StorageError, format_timestamp};
+use crate::volumes::{Volume, Volumes};
+
+/// 缓存格式版本，提取逻辑变化时递增以使旧缓存失效。
+const CACHE_VERSION: &str = "1";
+/// MP4 时间戳（1904-01-01 起）与 Unix 纪元的秒数差。
+const MP4_EPOCH_OFFSET: u64 = 2_082_844_800;
+/// Matroska 时间戳（2001-01-01 起）与 Unix 纪元的秒数差。
+const MATROSKA_EPOCH_OFFSET: u64 = 978_307_200;
+
+/// 按扩展名判断的文件类别。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    Audio,
+    Video,
+    Pdf,
+    Other,
+}
+
+impl MediaKind {
+    fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        match extension.as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" | "tif" | "tiff" | "heic" | "heif" => {
+                MediaKind::Image
+            }
+            "mp3" | "flac" | "ogg" | "oga" | "opus" | "wav" | "m4a" | "aac" => MediaKind::Audio,
+            "mp4" | "m4v" | "mov" | "mkv" | "webm" => MediaKind::Video,
+            "pdf" => MediaKind::Pdf,
+            _ => MediaKind::Other,
+        }
+    }
+}
+
+/// 文件的结构化元数据，仅包含能够识别的部分。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MediaMetadata {
+    pub kind: MediaKind,
+    pub mime: String,
+    /// 内容大小（字节）。
+    pub size: u64,
+    /// 拍摄或录制时间：图片取 EXIF 原始拍摄时间（拍摄地本地时间），视频取容器创建时间（UTC）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub captured: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video: Option<VideoInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pdf: Option<PdfInfo>,
+}
+
+/// 图片尺寸与 EXIF 信息。宽高为按 EXIF 方向校正后的显示尺寸。
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ImageInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_make: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lens: Option<String>,
+    /// 曝光时间，如 `1/250`（秒）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exposure_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub f_number: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iso: Option<u32>,
+    /// 焦距（毫米）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focal_length: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gps: Option<GpsPosition>,
+}
+
+/// 十进制度表示的拍摄位置，南纬、西经为负；海拔单位为米。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+}
+
+/// 音频编码、时长与标签（ID3、Vorbis Comment、iTunes 等）。
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AudioInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    /// 时长（秒）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<String>,
+}
+
+/// 视频容器与其中的流。
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VideoInfo {
+    /// 容器格式：`mp4`、`mov`、`matroska` 或 `webm`。
+    pub container: String,
+    /// 时长（秒）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    pub streams: Vec<StreamInfo>,
+}
+
+/// 容器中的一条视频、音频或字幕流。
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StreamInfo {
+    /// `video`、`audio` 或 `subtitle`。
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channels: Option<u32>,
+}
+
+/// PDF 页数与文档信息字典。
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PdfInfo {
+    pub pages: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub producer: Option<String>,
+    /// 文档记录的创建时间（按文档中的时区原样给出）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+}
+
+/// 元数据提取器：管理缓存目录并限制并发解析数量。
+pub struct MetadataExtractor {
+    cache: DiskCache,
+    permits: Semaphore,
+}
+
+impl MetadataExtractor {
+    /// 创建使用指定缓存目录的提取器。
+    pub fn new(cache_dir: std::path::PathBuf) -> Self {
+        Self {
+            cache: DiskCache::new(cache_dir),
+            permits: Semaphore::new(METADATA_MAX_CONCURRENT),
+        }
+    }
+
+    /// 返回（必要时提取）指定文件的元数据与对应的 ETag。
+    pub async fn extract(
+        &self,
+        volume: &Volume,
+        relative: &str,
+    ) -> Result<(MediaMetadata, String), ApiError> {
+        let storage = volume.storage();
+        let target = storage.resolve_path_checked(relative, false).await?;
+        let metadata = target.metadata().await.map_err(StorageError::from)?;
+        if !metadata.is_file() {
+            return Err(ApiError::BadRequest("path is not a file".into()));
+        }
+        let source_etag = storage.content_etag(&target, &metadata).await;
+        let key = cache_key(volume.name(), relative, &source_etag);
+        let etag = format!("W/\"{}\"", &key[..32]);
+        if let Some(cached) = self.read_cached(storage.keyring(), &key).await {
+            return Ok((cached, etag));
+        }
+
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        if let Some(cached) = self.read_cached(storage.keyring(), &key).await {
+            return Ok((cached, etag));
+        }
+        let file = target.open_file().await.map_err(StorageError::from)?;
+        let reader = ContentReader::new(file, storage.content_format())
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        let source = BlockingContent::new(reader, Handle::current());
+        let path = relative.to_string();
+        let extracted = tokio::task::spawn_blocking(move || extract_blocking(source, &path))
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        match serde_json::to_vec(&extracted) {
+            Ok(data) => {
+                if let Err(err) = self
+                    .cache
+                    .write(storage.keyring(), &key, "json", &data)
+                    .await
+                {
+                    warn!(path = relative, error = %err, "failed to cache metadata");
+                }
+            }
+            Err(err) => warn!(path = relative, error = %err, "failed to encode metadata"),
+        }
+        debug!(path = relative, kind = ?extracted.kind, "extracted metadata");
+        Ok((extracted, etag))
+    }
+
+    async fn read_cached(
+        &self,
+        keyring: Option<&crate::crypto::Keyring>,
+        key: &str,
+    ) -> Option<MediaMetadata> {
+        let data = self.cache.read(keyring, key, "json").await?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// 删除超过保留时长未更新的缓存文件，返回删除数量。
+    pub async fn prune_cache(&self) -> io::Result<u64> {
+        self.cache
+            .prune(Duration::from_secs(METADATA_CACHE_TTL_SECS))
+            .await
+    }
+}
+
+/// 判断文件是否可能带有拍摄时间（图片与视频）。
+pub fn has_capture_time(path: &str) -> bool {
+    matches!(
+        MediaKind::from_path(path),
+        MediaKind::Image | MediaKind::Video
+    )
+}
+
+fn cache_key(volume: &str, relative: &str, source_etag: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        CACHE_VERSION,
+        volume,
+        relative.trim_matches('/'),
+        source_etag,
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hex_encode(&hasher.finalize())
+}
+
+/// 在阻塞线程中以同步 `Read + Seek` 方式读取存储内容（透明解密、解压）。
+struct BlockingContent {
+    reader: ContentReader,
+    handle: Handle,
+    position: u64,
+    len: u64,
+}
+
+impl BlockingContent {
+    fn new(reader: ContentReader, handle: Handle) -> Self {
+        let len = reader.len();
+        Self {
+            reader,
+            handle,
+            position: 0,
+            len,
+        }
+    }
+}
+
+impl Read for BlockingContent {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let chunk = self
+            .handle
+            .block_on(self.reader.read_at(self.position, buf.len()))?;
+        buf[..chunk.len()].copy_from_slice(&chunk);
+        self.position += chunk.len() as u64;
+        Ok(chunk.len())
+    }
+}
+
+impl Seek for BlockingContent {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let target = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = target.ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "seek before start of content")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl MediaSource for BlockingContent {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
+
+/// 按扩展名选择解析器；无法解析的部分省略，不视为错误。
+fn extract_blocking(source: BlockingContent, path: &str) -> MediaMetadata {
+    let kind = MediaKind::from_path(path);
+    let mut metadata = MediaMetadata {
+        kind,
+        mime: mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .essence_str()
+            .to_string(),
+        size: source.len,
+        captured: None,
+        image: None,
+        audio: None,
+        video: None,
+        pdf: None,
+    };
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    let result = match kind {
+        MediaKind::Image => {
+            let (image, captured) = read_image_info(&mut BufReader::new(source));
+            metadata.image = Some(image);
+            metadata.captured = captured;
+            Ok(())
+        }
+        MediaKind::Audio => read_audio_info(source, &extension).map(|audio| {
+            metadata.audio = Some(audio);
+        }),
+        MediaKind::Video => {
+            let mut reader = BufReader::new(source);
+            let len = reader.get_ref().len;
+            let parsed = if matches!(extension.as_str(), "mkv" | "webm") {
+                read_matroska_info(&mut reader, len)
+            } else {
+                read_mp4_info(&mut reader, len)
+            };
+            parsed.map(|(video, created)| {
+                metadata.video = Some(video);
+                metadata.captured = created;
+            })
+        }
+        MediaKind::Pdf => read_pdf_info(source).map(|pdf| {
+            metadata.pdf = pdf;
+        }),
+        MediaKind::Other => Ok(()),
+    };
+    if let Err(err) = result {
+        debug!(path, error = %err, "metadata parsing failed");
+    }
+    metadata
+}
+
+/// 读取图片尺寸与 EXIF，返回图片信息与拍摄时间。
+fn read_image_info<R: BufRead + Seek>(reader: &mut R) -> (ImageInfo, Option<String>) {
+    let mut info = ImageInfo::default();
+    if let Ok((width, height)) = ImageReader::new(&mut *reader)
+        .with_guessed_format()
+        .and_then(|reader| reader.into_dimensions().map_err(io::Error::other))
+    {
+        info.width = Some(width);
+        info.height = Some(height);
+    }
+    if reader.seek(SeekFrom::Start(0)).is_err() {
+        return (info, None);
+    }
+    let Ok(exif) = exif::Reader::new().read_from_container(reader) else {
+        return (info, None);
+    };
+    let field = |tag| exif.get_field(tag, In::PRIMARY).map(|field| &field.value);
+    let uint = |tag| field(tag).and_then(|value| value.get_uint(0));
+    let ascii = |tag| match field(tag) {
+        Some(Value::Ascii(values)) => values
+            .first()
+            .map(|value| {
+                String::from_utf8_lossy(value)
+                    .trim_matches(['\0', ' '])
+                    .to_string()
+            })
+            .filter(|value| !value.is_empty()),
+        _ => None,
+    };
+    let rationals = |tag| match field(tag) {
+        Some(Value::Rational(values)) => values
+            .iter()
+            .map(|value| Some(value.to_f64()).filter(|value| value.is_finite()))
+            .collect::<Option<Vec<f64>>>(),
+        _ => None,
+    };
+    let rational = |tag| rationals(tag).and_then(|values| values.first().copied());
+
+    if info.width.is_none() {
+        info.width = uint(Tag::PixelXDimension);
+        info.height = uint(Tag::PixelYDimension);
+    }
+    info.orientation = uint(Tag::Orientation);
+    if matches!(info.orientation, Some(5..=8)) {
+        (info.width, info.height) = (info.height, info.width);
+    }
+    info.camera_make = ascii(Tag::Make);
+    info.camera_model = ascii(Tag::Model);
+    info.lens = ascii(Tag::LensModel);
+    info.exposure_time = match field(Tag::ExposureTime) {
+        Some(Value::Rational(values)) => values.first().and_then(|value| {
+            if value.num > 0 && value.num < value.denom {
+                Some(format!(
+                    "1/{}",
+                    (f64::from(value.denom) / f64::from(value.num)).round()
+                ))
+            } else {
+                Some(value.to_f64()).filter(|value| value.is_finite())?;
+                Some(format!("{}", value.to_f64()))
+            }
+        }),
+        _ => None,
+    };
+    info.f_number = rational(Tag::FNumber);
+    info.iso = uint(Tag::PhotographicSensitivity);
+    info.focal_length = rational(Tag::FocalLength);
+
+    let coordinate = |value_tag, reference_tag, negative: char| {
+        let parts = rationals(value_tag).filter(|parts| parts.len() >= 3)?;
+        let degrees = parts[0] + parts[1] / 60.0 + parts[2] / 3600.0;
+        let negative =
+            ascii(reference_tag).is_some_and(|reference| reference.starts_with(negative));
+        Some(if negative { -degrees } else { degrees })
+    };
+    let latitude = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, 'S');
+    let longitude = coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, 'W');
+    if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+        let below_sea = uint(Tag::GPSAltitudeRef) == Some(1);
+        info.gps = Some(GpsPosition {
+            latitude,
+            longitude,
+            altitude: rational(Tag::GPSAltitude)
+                .map(|altitude| if below_sea { -altitude } else { altitude }),
+        });
+    }
+
+    let captured = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime]
+        .into_iter()
+        .filter_map(ascii)
+        .find_map(|value| NaiveDateTime::parse_from_str(&value, "%Y:%m:%d %H:%M:%S").ok())
+        .map(|captured| captured.format("%Y-%m-%d %H:%M:%S").to_string());
+    (info, captured)
+}
+
+/// 用 Symphonia 探测音频格式，读取编码参数、时长与标签。
+fn read_audio_info(source: BlockingContent, extension: &str) -> io::Result<AudioInfo> {
+    let stream = MediaSourceStream::new(Box::new(source), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension(extension);
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))?;
+    let mut info = AudioInfo::default();
+    if let Some(track) = probed.format.default_track() {
+        let params = &track.codec_params;
+        info.codec = symphonia::default::get_codecs()
+            .get_codec(params.codec)
+            .map(|codec| codec.short_name.to_string());
+        info.sample_rate = params.sample_rate;
+        info.channels = params.channels.map(|channels| channels.count() as u32);
+        info.duration = match (params.n_frames, params.time_base, params.sample_rate) {
+            (Some(frames), Some(base), _) => {
+                let time = base.calc_time(frames);
+                Some(time.seconds as f64 + time.frac)
+            }
+            (Some(frames), None, Some(rate)) if rate > 0 => Some(frames as f64 / f64::from(rate)),
+            _ => None,
+        }
+        .map(|seconds| (seconds * 1000.0).round() / 1000.0);
+    }
+    // 容器内的标签优先，其次是文件头部的 ID3v2 等标签。
+    if let Some(revision) = probed.format.metadata().current() {
+        apply_tags(&mut info, revision);
+    }
+    if let Some(revision) = probed
+        .metadata
+        .get()
+        .as_ref()
+        .and_then(|metadata| metadata.current())
+    {
+        apply_tags(&mut info, revision);
+    }
+    Ok(info)
+}
+
+fn apply_tags(info: &mut AudioInfo, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let slot = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => &mut info.title,
+            Some(StandardTagKey::Artist) => &mut info.artist,
+            Some(StandardTagKey::Album) => &mut info.album,
+            Some(StandardTagKey::AlbumArtist) => &mut info.album_artist,
+            Some(StandardTagKey::Genre) => &mut info.genre,
+            Some(StandardTagKey::Date) => &mut info.date,
+            Some(StandardTagKey::TrackNumber) => &mut info.track,
+            _ => continue,
+        };
+        let value = tag.value.to_string();
+        let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+        if slot.is_none() && !value.is_empty() {
+            *slot = Some(value.to_string());
+        }
+    }
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// 读取 ISO BMFF 盒子头，返回类型与内容区间 `(类型, 内容起点, 盒子终点)`。
+fn read_box<R: Read + Seek>(reader: &mut R, end: u64) -> io::Result<Option<([u8; 4], u64, u64)>> {
+    let start = reader.stream_position()?;
+    if start + 8 > end {
+        return Ok(None);
+    }
+    let size = u64::from(read_u32(reader)?);
+    let mut kind = [0; 4];
+    reader.read_exact(&mut kind)?;
+    let (body, box_end) = match size {
+        0 => (start + 8, end),
+        1 => (start + 16, start.saturating_add(read_u64(reader)?)),
+        size => (start + 8, start + size),
+    };
+    if box_end < body || box_end > end {
+        return Err(invalid("mp4: box exceeds its parent"));
+    }
+    Ok(Some((kind, body, box_end)))
+}
+
+fn for_each_box<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    mut visit: impl FnMut(&mut R, [u8; 4], u64, u64) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut position = start;
+    while position < end {
+        reader.seek(SeekFrom::Start(position))?;
+        let Some((kind, body, box_end)) = read_box(reader, end)? else {
+            break;
+        };
+        visit(reader, kind, body, box_end)?;
+        position = box_end;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Mp4Track {
+    handler: Option<[u8; 4]>,
+    sample_entry: Option<[u8; 4]>,
+    width: u32,
+    height: u32,
+    sample_rate: u32,
+    channels: u32,
+}
+
+/// 解析 MP4/MOV：`ftyp` 品牌、`mvhd` 时长与创建时间、各 `trak` 的类型、编码与尺寸。
+fn read_mp4_info<R: Read + Seek>(
+    reader: &mut R,
+    len: u64,
+) -> io::Result<(VideoInfo, Option<String>)> {
+    let mut info = VideoInfo {
+        container: "mp4".into(),
+        duration: None,
+        streams: Vec::new(),
+    };
+    let mut created = None;
+    let mut found_movie = false;
+    for_each_box(reader, 0, len, |reader, kind, body, end| {
+        match &kind {
+            b"ftyp" if read_u32(reader)?.to_be_bytes() == *b"qt  " => {
+                info.container = "mov".into();
+            }
+            b"moov" => {
+                found_movie = true;
+                for_each_box(reader, body, end, |reader, kind, body, end| {
+                    match &kind {
+                        b"mvhd" => {
+                            let version = read_u32(reader)? >> 24;
+                            let (creation, timescale, duration) = if version == 1 {
+                                let creation = read_u64(reader)?;
+                                read_u64(reader)?;
+                                (creation, read_u32(reader)?, read_u64(reader)?)
+                            } else {
+                                let creation = u64::from(read_u32(reader)?);
+                                read_u32(reader)?;
+                                let timescale = read_u32(reader)?;
+                                (creation, timescale, u64::from(read_u32(reader)?))
+                            };
+                            if timescale > 0
+                                && duration != u64::MAX
+                                && duration != u64::from(u32::MAX)
+                            {
+                                let seconds = duration as f64 / f64::from(timescale);
+                                info.duration = Some((seconds * 1000.0).round() / 1000.0);
+                            }
+                            created = creation
+                                .checked_sub(MP4_EPOCH_OFFSET)
+                                .filter(|seconds| *seconds > 0)
+                                .and_then(|seconds| format_timestamp(Duration::from_secs(seconds)));
+                        }
+                        b"trak" => {
+                            let mut track = Mp4Track::default();
+                            walk_mp4_track(reader, body, end, &mut track, 0)?;
+                            if let Some(stream) = track.into_stream() {
+                                info.streams.push(stream);
+                            }
+                        }
+                        _ => {}
+                    }
+                    Ok(())
+                })?;
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    if !found_movie {
+        return Err(invalid("mp4: missing moov box"));
+    }
+    Ok((info, created))
+}
+
+fn walk_mp4_track<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    track: &mut Mp4Track,
+    depth: usize,
+) -> io::Result<()> {
+    if depth > METADATA_MAX_NESTING {
+        return Err(invalid("mp4: boxes nested too deeply"));
+    }
+    for_each_box(reader, start, end, |reader, kind, body, end| {
+        match &kind {
+            b"mdia" | b"minf" | b"stbl" => walk_mp4_track(reader, body, end, track, depth + 1)?,
+            b"tkhd" => {
+                let version = read_u32(reader)? >> 24;
+                // 跳过时间、轨道号与时长，再跳过图层、音量与变换矩阵，宽高为 16.16 定点数。
+                let skip = if version == 1 { 32 } else { 20 };
+                reader.seek(SeekFrom::Current(skip + 52))?;
+                track.width = read_u32(reader)? >> 16;
+                track.height = read_u32(reader)? >> 16;
+            }
+            b"hdlr" => {
+                reader.seek(SeekFrom::Current(8))?;
+                track.handler = Some(read_u32(reader)?.to_be_bytes());
+            }
+            b"stsd" => {
+                reader.seek(SeekFrom::Current(8))?;
+                if body + 16 > end {
+                    return Ok(());
+                }
+                read_u32(reader)?;
+                track.sample_entry = Some(read_u32(reader)?.to_be_bytes());
+                if track.handler == Some(*b"soun") && body + 44 <= end {
+                    reader.seek(SeekFrom::Current(16))?;
+                    track.channels = u32::from(read_u16(reader)?);
+                    reader.seek(SeekFrom::Current(6))?;
+                    track.sample_rate = read_u32(reader)? >> 16;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    })
+}
+
+impl Mp4Track {
+    fn into_stream(self) -> Option<StreamInfo> {
+        let kind = match &self.handler? {
+            b"vide" => "video",
+            b"soun" => "audio",
+            b"sbtl" | b"subt" | b"text" => "subtitle",
+            _ => return None,
+        };
+        let codec = self.sample_entry.map(|entry| {
+            match &entry {
+                b"avc1" | b"avc3" => "h264",
+                b"hvc1" | b"hev1" => "hevc",
+                b"av01" => "av1",
+                b"vp08" => "vp8",
+                b"vp09" => "vp9",
+                b"mp4a" => "aac",
+                b"Opus" => "opus",
+                b"fLaC" => "flac",
+                b"ac-3" => "ac3",
+                b"ec-3" => "eac3",
+                _ => {
+                    return String::from_utf8_lossy(&entry).trim().to_ascii_lowercase();
+                }
+            }
+            .to_string()
+        });
+        let positive = |value: u32| Some(value).filter(|value| *value > 0);
+        let is_video = kind == "video";
+        Some(StreamInfo {
+            kind: kind.into(),
+            codec,
+            width: positive(self.width).filter(|_| is_video),
+            height: positive(self.height).filter(|_| is_video),
+            sample_rate: positive(self.sample_rate),
+            channels: positive(self.channels),
+        })
+    }
+}
+
+const EBML_HEADER: u32 = 0x1A45_DFA3;
+const EBML_DOC_TYPE: u32 = 0x4282;
+const MKV_SEGMENT: u32 = 0x1853_8067;
+const MKV_INFO: u32 = 0x1549_A966;
+const MKV_TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const MKV_DURATION: u32 = 0x4489;
+const MKV_DATE_UTC: u32 = 0x4461;
+const MKV_TRACKS: u32 = 0x1654_AE6B;
+const MKV_TRACK_ENTRY: u32 = 0xAE;
+const MKV_TRACK_TYPE: u32 = 0x83;
+const MKV_CODEC_ID: u32 = 0x86;
+const MKV_VIDEO: u32 = 0xE0;
+const MKV_PIXEL_WIDTH: u32 = 0xB0;
+const MKV_PIXEL_HEIGHT: u32 = 0xBA;
+const MKV_AUDIO: u32 = 0xE1;
+const MKV_SAMPLING_FREQUENCY: u32 = 0xB5;
+const MKV_CHANNELS: u32 = 0x9F;
+const MKV_CLUSTER: u32 = 0x1F43_B675;
+
+/// 读取 EBML 变长整数，返回值与是否为“未知长度”（数据位全为 1）。
+fn read_vint<R: Read>(reader: &mut R, keep_marker: bool) -> io::Result<(u64, bool)> {
+    let first = read_u8(reader)?;
+    let length = first.leading_zeros() as usize + 1;
+    if length > 8 {
+        return Err(invalid("mkv: invalid variable-length integer"));
+    }
+    let mut value = if keep_marker {
+        u64::from(first)
+    } else {
+        u64::from(first) & (0xFF >> length)
+    };
+    for _ in 1..length {
+        value = (value << 8) | u64::from(read_u8(reader)?);
+    }
+    let unknown = !keep_marker && value == (1u64 << (7 * length)) - 1;
+    Ok((value, unknown))
+}
+
+/// 遍历 `[start, end)` 中的 EBML 子元素；`visit` 返回 `false` 时停止。
+/// 未知长度的元素延伸到父元素末尾；`depth` 为当前嵌套层数。
+fn for_each_element<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    depth: usize,
+    mut visit: impl FnMut(&mut R, u32, u64, u64, bool) -> io::Result<bool>,
+) -> io::Result<()> {
+    if depth > METADATA_MAX_NESTING {
+        return Err(invalid("mkv: elements nested too deeply"));
+    }
+    let mut position = start;
+    while position < end {
+        reader.seek(SeekFrom::Start(position))?;
+        let (id, _) = read_vint(reader, true)?;
+        let (size, unknown) = read_vint(reader, false)?;
+        let body = reader.stream_position()?;
+        let element_end = if unknown {
+            end
+        } else {
+            body.saturating_add(size).min(end)
+        };
+        if !visit(reader, id as u32, body, element_end, unknown)? {
+            break;
+        }
+        position = element_end;
+    }
+    Ok(())
+}
+
+fn read_ebml_uint<R: Read>(reader: &mut R, size: u64) -> io::Result<u64> {
+    if size > 8 {
+        return Err(invalid("mkv: integer element too large"));
+    }
+    let mut value = 0;
+    for _ in 0..size {
+        value = (value << 8) | u64::from(read_u8(reader)?);
+    }
+    Ok(value)
+}
+
+fn read_ebml_float<R: Read>(reader: &mut R, size: u64) -> io::Result<f64> {
+    match size {
+        4 => Ok(f64::from(f32::from_bits(read_u32(reader)?))),
+        8 => Ok(f64::from_bits(read_u64(reader)?)),
+        _ => Err(invalid("mkv: invalid float element")),
+    }
+}
+
+fn read_ebml_string<R: Read>(reader: &mut R, size: u64) -> io::Result<String> {
+    if size > 256 {
+        return Err(invalid("mkv: string element too large"));
+    }
+    let mut buf = vec![0; size as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf)
+        .trim_end_matches('\0')
+        .to_string())
+}
+
+/// 解析 Matroska/WebM：文档类型、`Info` 中的时长与创建时间、`Tracks` 中的各条流。
+/// 遇到首个 `Cluster` 且已读到所需信息时停止，不扫描媒体数据。
+fn read_matroska_info<R: Read + Seek>(
+    reader: &mut R,
+    len: u64,
+) -> io::Result<(VideoInfo, Option<String>)> {
+    let mut info = VideoInfo {
+        container: "matroska".into(),
+        duration: None,
+        streams: Vec::new(),
+    };
+    let mut created = None;
+    let mut is_matroska = false;
+    let mut segment = None;
+    for_each_element(reader, 0, len, 0, |reader, id, body, end, _| match id {
+        EBML_HEADER => {
+            is_matroska = true;
+            for_each_element(reader, body, end, 1, |reader, id, body, end, _| {
+                if id == EBML_DOC_TYPE && read_ebml_string(reader, end - body)? == "webm" {
+                    info.container = "webm".into();
+                }
+                Ok(true)
+            })?;
+            Ok(true)
+        }
+        MKV_SEGMENT if is_matroska => {
+            segment = Some((body, end));
+            Ok(false)
+        }
+        _ => Ok(false),
+    })?;
+    let Some((segment_start, segment_end)) = segment else {
+        return Err(invalid("mkv: not a matroska file"));
+    };
+
+    let mut timecode_scale = 1_000_000u64;
+    let mut duration = None;
+    let mut have_info = false;
+    let mut have_tracks = false;
+    for_each_element(
+        reader,
+        segment_start,
+        segment_end,
+        1,
+        |reader, id, body, end, unknown| {
+            match id {
+                MKV_INFO => {
+                    have_info = true;
+                    for_each_element(reader, body, end, 2, |reader, id, body, end, _| {
+                        let size = end - body;
+                        match id {
+                            MKV_TIMECODE_SCALE => timecode_scale = read_ebml_uint(reader, size)?,
+                            MKV_DURATION => duration = Some(read_ebml_float(reader, size)?),
+                            MKV_DATE_UTC => {
+                                let nanos = read_ebml_uint(reader, size)? as i64;
+                                created = MATROSKA_EPOCH_OFFSET
+                                    .checked_add_signed(nanos / 1_000_000_000)
+                                    .and_then(|seconds| {
+                                        format_timestamp(Duration::from_secs(seconds))
+                                    });
+                            }
+                            _ => {}
+                        }
+                        Ok(true)
+                    })?;
+                }
+                MKV_TRACKS => {
+                    have_tracks = true;
+                    for_each_element(reader, body, end, 2, |reader, id, body, end, _| {
+                        if id == MKV_TRACK_ENTRY
+                            && let Some(stream) = read_matroska_track(reader, body, end, 3)?
+                        {
+                            info.streams.push(stream);
+                        }
+                        Ok(true)
+                    })?;
+                }
+                MKV_CLUSTER if have_info && have_tracks => return Ok(false),
+                _ if unknown => return Ok(false),
+                _ => {}
+            }
+            Ok(true)
+        },
+    )?;
+    info.duration = duration
+        .map(|duration| duration * timecode_scale as f64 / 1e9)
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(|seconds| (seconds * 1000.0).round() / 1000.0);
+    Ok((info, created))
+}
+
+fn read_matroska_track<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    depth: usize,
+) -> io::Result<Option<StreamInfo>> {
+    let mut track_type = 0;
+    let mut stream = StreamInfo::default();
+    for_each_element(reader, start, end, depth, |reader, id, body, end, _| {
+        let size = end - body;
+        match id {
+            MKV_TRACK_TYPE => track_type = read_ebml_uint(reader, size)?,
+            MKV_CODEC_ID => stream.codec = Some(matroska_codec(&read_ebml_string(reader, size)?)),
+            MKV_VIDEO | MKV_AUDIO => {
+                for_each_element(reader, body, end, depth + 1, |reader, id, body, end, _| {
+                    let size = end - body;
+                    match id {
+                        MKV_PIXEL_WIDTH => {
+                            stream.width = Some(read_ebml_uint(reader, size)? as u32)
+                        }
+                        MKV_PIXEL_HEIGHT => {
+                            stream.height = Some(read_ebml_uint(reader, size)? as u32)
+                        }
+                        MKV_SAMPLING_FREQUENCY => {
+                            stream.sample_rate = Some(read_ebml_float(reader, size)? as u32)
+                        }
+                        MKV_CHANNELS => {
+                            stream.channels = Some(read_ebml_uint(reader, size)? as u32)
+                        }
+                        _ => {}
+                    }
+                    Ok(true)
+                })?;
+            }
+            _ => {}
+        }
+        Ok(true)
+    })?;
+    stream.kind = match track_type {
+        1 => "video",
+        2 => "audio",
+        17 => "subtitle",
+        _ => return Ok(None),
+    }
+    .into();
+    Ok(Some(stream))
+}
+
+fn matroska_codec(codec_id: &str) -> String {
+    match codec_id {
+        "V_MPEG4/ISO/AVC" => "h264",
+        "V_MPEGH/ISO/HEVC" => "hevc",
+        "V_AV1" => "av1",
+        "V_VP8" => "vp8",
+        "V_VP9" => "vp9",
+        "A_OPUS" => "opus",
+        "A_VORBIS" => "vorbis",
+        "A_FLAC" => "flac",
+        "A_AC3" => "ac3",
+        "A_EAC3" => "eac3",
+        "A_MPEG/L3" => "mp3",
+        "S_TEXT/UTF8" => "srt",
+        "S_TEXT/ASS" | "S_TEXT/SSA" => "ass",
+        id if id.starts_with("A_AAC") => "aac",
+        id => return id.to_ascii_lowercase(),
+    }
+    .to_string()
+}
+
+/// 读取 PDF 页数与信息字典；超过大小限制时跳过。
+fn read_pdf_info(mut source: BlockingContent) -> io::Result<Option<PdfInfo>> {
+    if source.len > METADATA_MAX_PDF_SIZE {
+        return Ok(None);
+    }
+    let mut data = Vec::with_capacity(source.len as usize);
+    source.read_to_end(&mut data)?;
+    let document = lopdf::Document::load_mem(&data)
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err.to_string()))?;
+    let info_dict = document
+        .trailer
+        .get(b"Info")
+        .ok()
+        .and_then(|object| document.dereference(object).ok())
+        .and_then(|(_, object)| object.as_dict().ok());
+    let text = |key: &[u8]| {
+        let object = info_dict?.get(key).ok()?;
+        let (_, object) = document.dereference(object).ok()?;
+        Some(decode_pdf_text(object.as_str().ok()?)).filter(|value| !value.is_empty())
+    };
+    Ok(Some(PdfInfo {
+        pages: document.get_pages().len(),
+        title: text(b"Title"),
+        author: text(b"Author"),
+        creator: text(b"Creator"),
+        producer: text(b"Producer"),
+        created: text(b"CreationDate").and_then(|value| parse_pdf_date(&value)),
+    }))
+}
+
+/// 解码 PDF 文本字符串：带 BOM 的 UTF-16BE，否则按 Latin-1 近似 PDFDocEncoding。
+fn decode_pdf_text(bytes: &[u8]) -> String {
+    let text = match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|byte| char::from(*byte)).collect(),
+    };
+    text.trim_matches(['\0', ' ']).to_string()
+}
+
+/// 解析 `D:YYYYMMDDHHmmSS` 形式的 PDF 日期，省略的部分按最早值补齐。
+fn parse_pdf_date(value: &str) -> Option<String> {
+    let digits: String = value
+        .trim_start_matches("D:")
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .take(14)
+        .collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    let padded = format!("{digits}{}", &"0101000000"[digits.len() - 4..]);
+    NaiveDateTime::parse_from_str(&padded, "%Y%m%d%H%M%S")
+        .ok()
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MetadataQuery {
+    path: String,
+}
+
+#[derive(Serialize)]
+struct MetadataBody {
+    path: String,
+    #[serde(flatten)]
+    metadata: MediaMetadata,
+}
+
+/// 返回文件的结构化元数据，支持 If-None-Match 协商缓存。
+pub async fn get_metadata(
+    Query(query): Query<MetadataQuery>,
+    headers: HeaderMap,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(extractor): Extension<Arc<MetadataExtractor>>,
+) -> Result<Response, ApiError> {
+    let (volume, relative) = volumes.resolve(&query.path)?;
+    let (metadata, etag) = extractor.extract(volume, relative).await?;
+    info!(path = query.path, kind = ?metadata.kind, "file metadata");
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
+    );
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-cache"),
+    );
+    if !none_match(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+    let body = MetadataBody {
+        path: volumes.public_path(volume, relative.trim_matches('/')),
+        metadata,
+    };
+    Ok((StatusCode::OK, response_headers, JsonResponse(body)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Keyring;
+    use crate::storage::Storage;
+    use exif::experimental::Writer;
+    use exif::{Field, Rational};
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{DynamicImage, Rgb, RgbImage};
+    use lopdf::{Document, Object, dictionary};
+    use tempfile::tempdir;
+
+    fn jpeg_with_exif() -> Vec<u8> {
+        let ascii = |tag, value: &str| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        };
+        let rationals = |tag, values: &[(u32, u32)]| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(
+                values
+                    .iter()
+                    .map(|&(num, denom)| Rational { num, denom })
+                    .collect(),
+            ),
+        };
+        let fields = [
+            ascii(Tag::Make, "Axo"),
+            ascii(Tag::Model, "Cam 1"),
+            ascii(Tag::DateTimeOriginal, "2024:05:01 12:34:56"),
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            rationals(Tag::ExposureTime, &[(1, 250)]),
+            rationals(Tag::FNumber, &[(28, 10)]),
+            ascii(Tag::GPSLatitudeRef, "S"),
+            rationals(Tag::GPSLatitude, &[(35, 1), (30, 1), (0, 1)]),
+            ascii(Tag::GPSLongitudeRef, "E"),
+            rationals(Tag::GPSLongitude, &[(139, 1), (45, 1), (36, 1)]),
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).expect("write exif");
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend_from_slice(tiff.get_ref());
+
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 20, Rgb([1, 2, 3])))
+            .write_with_encoder(JpegEncoder::new(&mut jpeg))
+            .expect("encode jpeg");
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        data.extend_from_slice(&(app1.len() as u16 + 2).to_be_bytes());
+        data.extend_from_slice(&app1);
+        data.extend_from_slice(&jpeg[2..]);
+        data
+    }
+
+    /// 8 kHz 单声道 16 位 PCM，2 秒，带 RIFF INFO 标题。
+    fn wav_with_title() -> Vec<u8> {
+        let samples = vec![0u8; 8000 * 2 * 2];
+        let mut info = b"INFOINAM".to_vec();
+        info.extend_from_slice(&10u32.to_le_bytes());
+        info.extend_from_slice(b"Test Tone\0");
+        let mut body = b"WAVEfmt ".to_vec();
+        body.extend_from_slice(&16u32.to_le_bytes());
+        for value in [1u16, 1] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        body.extend_from_slice(&8000u32.to_le_bytes());
+        body.extend_from_slice(&16000u32.to_le_bytes());
+        for value in [2u16, 16] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        body.extend_from_slice(b"LIST");
+        body.extend_from_slice(&(info.len() as u32).to_le_bytes());
+        body.extend_from_slice(&info);
+        body.extend_from_slice(b"data");
+        body.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        body.extend_from_slice(&samples);
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(&body);
+        data
+    }
+
+    fn mp4_box(kind: &[u8; 4], parts: &[&[u8]]) -> Vec<u8> {
+        let body = parts.concat();
+        let mut data = (body.len() as u32 + 8).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(&body);
+        data
+    }
+
+    /// 1920x1080 H.264 视频轨与 48 kHz 立体声 AAC 音频轨，时长 90 秒。
+    fn mp4_movie() -> Vec<u8> {
+        let created = (MP4_EPOCH_OFFSET as u32 + 1_700_000_000).to_be_bytes();
+        let mvhd = mp4_box(
+            b"mvhd",
+            &[
+                &[0; 4],
+                &created,
+                &created,
+                &1000u32.to_be_bytes(),
+                &90_000u32.to_be_bytes(),
+            ],
+        );
+        let track = |handler: &[u8; 4], entry: &[u8; 4], width: u32, height: u32, audio: &[u8]| {
+            let tkhd = mp4_box(
+                b"tkhd",
+                &[
+                    &[0; 4],
+                    &[0; 20],
+                    &[0; 52],
+                    &(width << 16).to_be_bytes(),
+                    &(height << 16).to_be_bytes(),
+                ],
+            );
+            let hdlr = mp4_box(b"hdlr", &[&[0; 8], handler, &[0; 12]]);
+            let sample_entry = mp4_box(entry, &[&[0; 8], audio]);
+            let stsd = mp4_box(b"stsd", &[&[0; 4], &1u32.to_be_bytes(), &sample_entry]);
+            let stbl = mp4_box(b"stbl", &[&stsd]);
+            let minf = mp4_box(b"minf", &[&stbl]);
+            let mdia = mp4_box(b"mdia", &[&hdlr, &minf]);
+            mp4_box(b"trak", &[&tkhd, &mdia])
+        };
+        let mut audio = vec![0; 8];
+        audio.extend_from_slice(&2u16.to_be_bytes());
+        audio.extend_from_slice(&16u16.to_be_bytes());
+        audio.extend_from_slice(&[0; 4]);
+        audio.extend_from_slice(&(48_000u32 << 16).to_be_bytes());
+        let video = track(b"vide", b"avc1", 1920, 1080, &[0; 70]);
+        let sound = track(b"soun", b"mp4a", 0, 0, &audio);
+        let moov = mp4_box(b"moov", &[&mvhd, &video, &sound]);
+        [mp4_box(b"ftyp", &[b"isom", &[0; 4]]), moov].concat()
+    }
+
+    fn ebml(id: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+        let body = parts.concat();
+        let mut data = id.to_vec();
+        data.push(0x01);
+        data.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+        data.extend_from_slice(&body);
+        data
+    }
+
+    /// 640x360 VP9 视频与 Opus 音频的 WebM，时长 12.5 秒；Segment 为未知长度。
+    fn webm_movie() -> Vec<u8> {
+        let header = ebml(
+            &[0x1A, 0x45, 0xDF, 0xA3],
+            &[&ebml(&[0x42, 0x82], &[b"webm"])],
+        );
+        let info = ebml(
+            &[0x15, 0x49, 0xA9, 0x66],
+            &[
+                &ebml(&[0x2A, 0xD7, 0xB1], &[&1_000_000u32.to_be_bytes()]),
+                &ebml(&[0x44, 0x89], &[&12_500f64.to_be_bytes()]),
+            ],
+        );
+        let video = ebml(
+            &[0xAE],
+            &[
+                &ebml(&[0x83], &[&[1]]),
+                &ebml(&[0x86], &[b"V_VP9"]),
+                &ebml(
+                    &[0xE0],
+                    &[
+                        &ebml(&[0xB0], &[&640u16.to_be_bytes()]),
+                        &ebml(&[0xBA], &[&360u16.to_be_bytes()]),
+                    ],
+                ),
+            ],
+        );
+        let audio = ebml(
+            &[0xAE],
+            &[
+                &ebml(&[0x83], &[&[2]]),
+                &ebml(&[0x86], &[b"A_OPUS"]),
+                &ebml(
+                    &[0xE1],
+                    &[
+                        &ebml(&[0xB5], &[&48_000f32.to_be_bytes()]),
+                        &ebml(&[0x9F], &[&[2]]),
+                    ],
+                ),
+            ],
+        );
+        let tracks = ebml(&[0x16, 0x54, 0xAE, 0x6B], &[&video, &audio]);
+        let cluster = ebml(&[0x1F, 0x43, 0xB6, 0x75], &[&[0; 32]]);
+        let mut segment = vec![
+            0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+        segment.extend_from_slice(&[info, tracks, cluster].concat());
+        [header, segment].concat()
+    }
+
+    fn pdf_document() -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let kids: Vec<Object> = (0..3)
+            .map(|_| {
+                document
+                    .add_object(dictionary! { "Type" => "Page", "Parent" => pages_id })
+                    .into()
+            })
+            .collect();
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => 3 }),
+        );
+        let catalog_id =
+            document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = document.add_object(dictionary! {
+            "Title" => Object::string_literal("Quarterly Report"),
+            "CreationDate" => Object::string_literal("D:20230115103000+08'00'"),
+        });
+        document.trailer.set("Root", catalog_id);
+        document.trailer.set("Info", info_id);
+        let mut data = Vec::new();
+        document.save_to(&mut data).expect("save pdf");
+        data
+    }
+
+    #[test]
+    fn deeply_nested_mp4_boxes_are_rejected() {
+        let mut nested = Vec::new();
+        for _ in 0..10_000 {
+            nested = mp4_box(b"mdia", &[&nested]);
+        }
+        let data = mp4_box(b"moov", &[&mp4_box(b"trak", &[&nested])]);
+        let err = read_mp4_info(&mut std::io::Cursor::new(&data), data.len() as u64)
+            .expect_err("nesting must be rejected");
+        assert_eq!(err.to_string(), "mp4: boxes nested too deeply");
+    }
+
+    #[test]
+    fn out_of_range_mp4_creation_time_is_omitted() {
+        for creation in [MP4_EPOCH_OFFSET + 9_000_000_000_000, u64::MAX] {
+            let mvhd = mp4_box(
+                b"mvhd",
+                &[
+                    &[1, 0, 0, 0],
+                    &creation.to_be_bytes(),
+                    &creation.to_be_bytes(),
+                    &1000u32.to_be_bytes(),
+                    &90_000u64.to_be_bytes(),
+                ],
+            );
+            let data = mp4_box(b"moov", &[&mvhd]);
+            let (info, created) =
+                read_mp4_info(&mut std::io::Cursor::new(&data), data.len() as u64)
+                    .expect("parse mp4");
+            assert_eq!(info.duration, Some(90.0));
+            assert_eq!(created, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn media_metadata_is_extracted_and_cached_by_etag() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create root");
+        for (name, data) in [
+            ("photo.jpg", jpeg_with_exif()),
+            ("tone.wav", wav_with_title()),
+            ("clip.mp4", mp4_movie()),
+            ("clip.webm", webm_movie()),
+            ("report.pdf", pdf_document()),
+            ("notes.txt", b"hello".to_vec()),
+        ] {
+            std::fs::write(root.join(name), data).expect("write fixture");
+        }
+        let storage = Storage::new(root).with_keyring(Some(Keyring::new(&[5u8; 32], &[])));
+        let volume = Volume::new("", Arc::new(storage));
+        let extractor = MetadataExtractor::new(temp.path().join("metadata"));
+        let extract = |path: &'static str| {
+            let extractor = &extractor;
+            let volume = &volume;
+            async move {
+                extractor
+                    .extract(volume, path)
+                    .await
+                    .unwrap_or_else(|_| panic!("extract {path}"))
+            }
+        };
+
+        let (photo, etag) = extract("photo.jpg").await;
+        assert_eq!(photo.kind, MediaKind::Image);
+        assert_eq!(photo.captured.as_deref(), Some("2024-05-01 12:34:56"));
+        let image = photo.image.expect("image info");
+        assert_eq!((image.width, image.height), (Some(20), Some(40)));
+        assert_eq!(image.camera_model.as_deref(), Some("Cam 1"));
+        assert_eq!(image.exposure_time.as_deref(), Some("1/250"));
+        let gps = image.gps.expect("gps");
+        assert!((gps.latitude + 35.5).abs() < 1e-9);
+        assert!((gps.longitude - 139.76).abs() < 1e-9);
+
+        let (tone, _) = extract("tone.wav").await;
+        let audio = tone.audio.expect("audio info");
+        assert_eq!(audio.duration, Some(2.0));
+        assert_eq!((audio.sample_rate, audio.channels), (Some(8000), Some(1)));
+        assert_eq!(audio.title.as_deref(), Some("Test Tone"));
+
+        let (mp4, _) = extract("clip.mp4").await;
+        assert_eq!(mp4.captured.as_deref(), Some("2023-11-14 22:13:20"));
+        let video = mp4.video.expect("mp4 info");
+        assert_eq!(
+            (video.container.as_str(), video.duration),
+            ("mp4", Some(90.0))
+        );
+        assert_eq!(video.streams.len(), 2);
+        assert_eq!(video.streams[0].codec.as_deref(), Some("h264"));
+        assert_eq!(
+            (video.streams[0].width, video.streams[0].height),
+            (Some(1920), Some(1080))
+        );
+        assert_eq!(video.streams[1].kind, "audio");
+        assert_eq!(
+            (video.streams[1].sample_rate, video.streams[1].channels),
+            (Some(48_000), Some(2))
+        );
+
+        let (webm, _) = extract("clip.webm").await;
+        let video = webm.video.expect("webm info");
+        assert_eq!(
+            (video.container.as_str(), video.duration),
+            ("webm", Some(12.5))
+        );
+        let codecs: Vec<_> = video
+            .streams
+            .iter()
+            .map(|stream| stream.codec.as_deref())
+            .collect();
+        assert_eq!(codecs, [Some("vp9"), Some("opus")]);
+        assert_eq!(video.streams[0].height, Some(360));
+
+        let (report, _) = extract("report.pdf").await;
+        let pdf = report.pdf.expect("pdf info");
+        assert_eq!(pdf.pages, 3);
+        assert_eq!(pdf.title.as_deref(), Some("Quarterly Report"));
+        assert_eq!(pdf.created.as_deref(), Some("2023-01-15 10:30:00"));
+
+        let (notes, _) = extract("notes.txt").await;
+        assert_eq!((notes.kind, notes.size), (MediaKind::Other, 5));
+
+        let cached = std::fs::read_dir(temp.path().join("metadata"))
+            .expect("cache dir")
+            .flatten()
+            .flat_map(|shard| std::fs::read_dir(shard.path()).expect("shard"))
+            .count();
+        assert_eq!(cached, 6);
+        let (again, again_etag) = extract("photo.jpg").await;
+        assert_eq!(again_etag, etag);
+        assert_eq!(again.captured, photo.captured);
+    }
+}
@@ -0,0 +1,371 @@
+//! 文本预览：读取文件开头或指定行范围，识别字符编码并转换为 UTF-8。
+
+use axum::extract::{Extension, Query};
+use axum::response::Json as JsonResponse;
+use chardetng::EncodingDetector;
+use encoding_rs::{Decoder, Encoding, UTF_8};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::config::{
+    PREVIEW_DEFAULT_BYTES, PREVIEW_DEFAULT_LINES, PREVIEW_DETECT_BYTES, PREVIEW_MAX_BYTES,
+    PREVIEW_MAX_LINES, PREVIEW_MAX_SCAN_BYTES,
+};
+use crate::content::ContentReader;
+use crate::error::ApiError;
+use crate::files::open_content;
+use crate::volumes::Volumes;
+
+/// 逐块读取时每次读取的字节数。
+const READ_CHUNK: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+pub(crate) struct PreviewQuery {
+    path: String,
+    /// 最多返回的原始字节数。
+    bytes: Option<usize>,
+    /// 起始行号（从 1 开始），与 `lines` 任一出现时按行读取。
+    start_line: Option<usize>,
+    lines: Option<usize>,
+    /// 指定编码（WHATWG 标签，如 `gbk`、`shift_jis`），跳过自动识别。
+    encoding: Option<String>,
+}
+
+/// 换行风格。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+    Mixed,
+    None,
+}
+
+/// 预览结果。
+#[derive(Debug, Serialize)]
+pub struct TextPreview {
+    pub path: String,
+    /// 转换为 UTF-8 后的文本。
+    pub content: String,
+    /// 编码名称（如 `UTF-8`、`GBK`、`Shift_JIS`）。
+    pub encoding: &'static str,
+    /// 编码是否由内容识别得出（指定 `encoding` 时为 false）。
+    pub detected: bool,
+    /// 文件是否以 BOM 开头。
+    pub bom: bool,
+    /// 是否含有无法按该编码解码、已替换为 U+FFFD 的字节。
+    pub had_errors: bool,
+    pub line_ending: LineEnding,
+    /// 按行读取时返回内容的首行与末行行号。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    /// 文件内容总字节数。
+    pub size: u64,
+    /// 文件在返回内容之后是否还有未读取的部分。
+    pub truncated: bool,
+}
+
+/// 返回文件开头或指定行范围的 UTF-8 文本及编码、换行风格信息。
+pub async fn preview_file(
+    Query(query): Query<PreviewQuery>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+) -> Result<JsonResponse<TextPreview>, ApiError> {
+    let (volume, relative) = volumes.resolve(&query.path)?;
+    let max_bytes = query
+        .bytes
+        .unwrap_or(PREVIEW_DEFAULT_BYTES)
+        .clamp(1, PREVIEW_MAX_BYTES);
+    let override_encoding = match query.encoding.as_deref() {
+        Some(label) => Some(
+            Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| ApiError::BadRequest(format!("unknown encoding: {label}")))?,
+        ),
+        None => None,
+    };
+    let (mut reader, _) = open_content(volume, relative).await?;
+    let size = reader.len();
+    let sample = read_range(&mut reader, 0, PREVIEW_DETECT_BYTES).await?;
+    let (encoding, bom_len) = match Encoding::for_bom(&sample) {
+        Some((encoding, bom_len)) => (override_encoding.unwrap_or(encoding), bom_len),
+        None => {
+            if override_encoding.is_none() && sample.contains(&0) {
+                return Err(ApiError::BadRequest("file is not text".into()));
+            }
+            let encoding = override_encoding
+                .unwrap_or_else(|| detect_encoding(&sample, sample.len() as u64 >= size));
+            (encoding, 0)
+        }
+    };
+    let decoder = encoding.new_decoder_without_bom_handling();
+    let line_mode = query.start_line.is_some() || query.lines.is_some();
+    let mut preview = if line_mode {
+        let start_line = query.start_line.unwrap_or(1).max(1);
+        let count = query
+            .lines
+            .unwrap_or(PREVIEW_DEFAULT_LINES)
+            .clamp(1, PREVIEW_MAX_LINES);
+        read_lines(
+            &mut reader,
+            decoder,
+            bom_len as u64,
+            start_line,
+            count,
+            max_bytes,
+        )
+        .await?
+    } else {
+        read_head(&mut reader, decoder, bom_len as u64, max_bytes).await?
+    };
+    preview.path = query.path;
+    preview.encoding = encoding.name();
+    preview.detected = override_encoding.is_none();
+    preview.bom = bom_len > 0;
+    preview.line_ending = line_ending(&preview.content);
+    info!(
+        path = preview.path,
+        encoding = preview.encoding,
+        truncated = preview.truncated,
+        "preview file"
+    );
+    Ok(JsonResponse(preview))
+}
+
+/// 识别编码：合法 UTF-8（允许末尾不完整的字符）直接采用，否则交给 chardetng 猜测。
+fn detect_encoding(sample: &[u8], complete: bool) -> &'static Encoding {
+    let utf8 = match std::str::from_utf8(sample) {
+        Ok(_) => true,
+        // 样本截断在多字节字符中间。
+        Err(err) => !complete && err.error_len().is_none(),
+    };
+    if utf8 {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, complete);
+    detector.guess(None, true)
+}
+
+/// 从偏移处读取最多 `max` 字节（跨越压缩帧时分多次读取）。
+async fn read_range(
+    reader: &mut ContentReader,
+    offset: u64,
+    max: usize,
+) -> Result<Vec<u8>, ApiError> {
+    let mut data = Vec::new();
+    while data.len() < max {
+        let chunk = reader
+            .read_at(offset + data.len() as u64, max - data.len())
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+fn decode(decoder: &mut Decoder, bytes: &[u8], last: bool, had_errors: &mut bool) -> String {
+    let capacity = decoder
+        .max_utf8_buffer_length(bytes.len())
+        .unwrap_or(bytes.len() * 3);
+    let mut text = String::with_capacity(capacity);
+    let (_, _, errors) = decoder.decode_to_string(bytes, &mut text, last);
+    *had_errors |= errors;
+    text
+}
+
+fn empty_preview(size: u64) -> TextPreview {
+    TextPreview {
+        path: String::new(),
+        content: String::new(),
+        encoding: UTF_8.name(),
+        detected: true,
+        bom: false,
+        had_errors: false,
+        line_ending: LineEnding::None,
+        start_line: None,
+        end_line: None,
+        size,
+        truncated: false,
+    }
+}
+
+/// 读取文件开头最多 `max_bytes` 字节；截断处不完整的字符留待下次读取，不输出替换字符。
+async fn read_head(
+    reader: &mut ContentReader,
+    mut decoder: Decoder,
+    start: u64,
+    max_bytes: usize,
+) -> Result<TextPreview, ApiError> {
+    let size = reader.len();
+    let bytes = read_range(reader, start, max_bytes).await?;
+    let end = start + bytes.len() as u64;
+    let mut preview = empty_preview(size);
+    preview.content = decode(&mut decoder, &bytes, end >= size, &mut preview.had_errors);
+    preview.truncated = end < size;
+    Ok(preview)
+}
+
+/// 从 `start_line` 起读取 `count` 行。跳过的前置内容最多扫描 [`PREVIEW_MAX_SCAN_BYTES`]，
+/// 返回内容超过 `max_bytes` 时在字符边界截断。
+async fn read_lines(
+    reader: &mut ContentReader,
+    mut decoder: Decoder,
+    start: u64,
+    start_line: usize,
+    count: usize,
+    max_bytes: usize,
+) -> Result<TextPreview, ApiError> {
+    let size = reader.len();
+    let mut preview = empty_preview(size);
+    let mut offset = start;
+    let mut line = 1;
+    let end_line = start_line.saturating_add(count);
+    let mut content = String::new();
+    loop {
+        if offset - start >= PREVIEW_MAX_SCAN_BYTES && line < start_line {
+            return Err(ApiError::BadRequest(
+                "start line is beyond the preview scan limit".into(),
+            ));
+        }
+        let chunk = read_range(reader, offset, READ_CHUNK).await?;
+        offset += chunk.len() as u64;
+        let last = offset >= size;
+        let text = decode(&mut decoder, &chunk, last, &mut preview.had_errors);
+        let mut consumed = 0;
+        for piece in text.split_inclusive('\n') {
+            consumed += piece.len();
+            if line >= start_line {
+                content.push_str(piece);
+            }
+            if piece.ends_with('\n') {
+                line += 1;
+                if line >= end_line {
+                    break;
+                }
+            }
+        }
+        if line >= end_line {
+            preview.truncated = consumed < text.len() || !last;
+            break;
+        }
+        if content.len() > max_bytes {
+            preview.truncated = true;
+            break;
+        }
+        if last {
+            break;
+        }
+    }
+    if content.len() > max_bytes {
+        let mut cut = max_bytes;
+        while !content.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        content.truncate(cut);
+        preview.truncated = true;
+    }
+    if !content.is_empty() {
+        let newlines = content.matches('\n').count();
+        let partial = usize::from(!content.ends_with('\n'));
+        preview.start_line = Some(start_line);
+        preview.end_line = Some(start_line + newlines + partial - 1);
+    }
+    preview.content = content;
+    Ok(preview)
+}
+
+/// 统计文本中的换行风格。
+fn line_ending(text: &str) -> LineEnding {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    let cr = text.matches('\r').count() - crlf;
+    match (crlf > 0, lf > 0, cr > 0) {
+        (false, false, false) => LineEnding::None,
+        (true, false, false) => LineEnding::Crlf,
+        (false, true, false) => LineEnding::Lf,
+        (false, false, true) => LineEnding::Cr,
+        _ => LineEnding::Mixed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+    use axum::http::Uri;
+    use tempfile::tempdir;
+
+    async fn preview(volumes: &Arc<Volumes>, query: &str) -> Result<TextPreview, ApiError> {
+        let uri: Uri = format!("/api/files/preview?{query}").parse().expect("uri");
+        let query = Query::<PreviewQuery>::try_from_uri(&uri).expect("query");
+        preview_file(query, Extension(volumes.clone()))
+            .await
+            .map(|JsonResponse(preview)| preview)
+    }
+
+    #[tokio::test]
+    async fn legacy_encodings_are_detected_and_lines_selected() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create root");
+        let gbk_text = "日志：服务已启动\r\n配置文件加载完成\r\n用户登录成功\r\n";
+        let (gbk, _, _) = encoding_rs::GBK.encode(gbk_text);
+        std::fs::write(root.join("app.log"), &gbk).expect("write gbk");
+        let sjis_text = "設定ファイルを読み込みました\nサーバーを起動します\n";
+        let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode(sjis_text);
+        std::fs::write(root.join("conf.ini"), &sjis).expect("write sjis");
+        let lines: String = (1..=500).map(|index| format!("line {index}\n")).collect();
+        std::fs::write(root.join("long.txt"), lines).expect("write utf8");
+        std::fs::write(root.join("blob.bin"), [0u8, 1, 2, 0, 255]).expect("write binary");
+        let volumes = Arc::new(Volumes::single(Arc::new(Storage::new(root))));
+
+        let log = preview(&volumes, "path=app.log")
+            .await
+            .unwrap_or_else(|_| panic!("gbk"));
+        assert_eq!((log.encoding, log.detected), ("GBK", true));
+        assert_eq!(log.content, gbk_text);
+        assert_eq!(log.line_ending, LineEnding::Crlf);
+        assert!(!log.truncated);
+
+        let conf = preview(&volumes, "path=conf.ini")
+            .await
+            .unwrap_or_else(|_| panic!("sjis"));
+        assert_eq!(conf.encoding, "Shift_JIS");
+        assert_eq!(conf.content, sjis_text);
+        assert_eq!(conf.line_ending, LineEnding::Lf);
+
+        // 截断在多字节字符中间时不输出半个字符。
+        let head = preview(&volumes, "path=app.log&bytes=7&encoding=gbk")
+            .await
+            .unwrap_or_else(|_| panic!("head"));
+        assert_eq!((head.content.as_str(), head.detected), ("日志：", false));
+        assert!(head.truncated);
+
+        let range = preview(&volumes, "path=long.txt&start_line=100&lines=3")
+            .await
+            .unwrap_or_else(|_| panic!("lines"));
+        assert_eq!(range.content, "line 100\nline 101\nline 102\n");
+        assert_eq!((range.start_line, range.end_line), (Some(100), Some(102)));
+        assert!(range.truncated);
+        let tail = preview(&volumes, "path=long.txt&start_line=499&lines=10")
+            .await
+            .unwrap_or_else(|_| panic!("tail"));
+        assert_eq!(tail.content, "line 499\nline 500\n");
+        assert!(!tail.truncated);
+
+        assert!(matches!(
+            preview(&volumes, "path=blob.bin").await,
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(matches!(
+            preview(&volumes, "path=app.log&encoding=klingon").await,
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,758 @@
+//! 公开分享链接：免登录访问单个文件或只读浏览目录，支持过期时间、访问密码与下载次数限制。
+
+use axum::extract::{Extension, Json, Path, Query, connect_info::ConnectInfo};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use axum::response::{Html, IntoResponse, Json as JsonResponse, Response};
+use axum_extra::headers::authorization::{Basic, Credentials};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::auth::{AuthConfig, check_login_rate_limit, register_login_failure};
+use crate::error::ApiError;
+use crate::files::{DownloadConfig, DownloadQuery, download_file};
+use crate::http::{
+    Disposition, RequestScheme, encode_path_segment, link_base_url, resolve_client_ip,
+};
+use crate::links::{
+    Link, LinkConfig, LinkKind, LinkStore, hash_password, new_token, remove_link_files, unix_now,
+    verify_password,
+};
+use crate::storage::StorageError;
+use crate::thumbnail::{Thumbnailer, TransformQuery};
+use crate::volumes::Volumes;
+
+/// 访问受密码保护的分享时携带密码的请求头（也可使用 Basic 认证的密码部分）。
+const SHARE_PASSWORD_HEADER: &str = "x-share-password";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ShareCreateRequest {
+    path: String,
+    /// 有效时长（秒），None 表示不过期。
+    expires_in: Option<u64>,
+    password: Option<String>,
+    max_downloads: Option<u64>,
+}
+
+/// 链接管理接口返回的链接信息（不含密码摘要）。
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkInfo {
+    pub token: String,
+    pub kind: LinkKind,
+    pub path: String,
+    pub url: String,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub max_downloads: Option<u64>,
+    pub downloads: u64,
+    pub has_password: bool,
+    pub max_files: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub files: u64,
+    pub bytes: u64,
+    /// 是否仍可访问（未过期且次数与字节数未用完）。
+    pub active: bool,
+}
+
+impl LinkInfo {
+    pub(crate) fn new(link: Link, base: &str) -> Self {
+        let url = match link.kind {
+            LinkKind::Transfer => format!(
+                "{base}/t/{}/{}",
+                link.token,
+                encode_path_segment(link.path.rsplit('/').next().unwrap_or_default())
+            ),
+            LinkKind::Share => format!("{base}/s/{}", link.token),
+            LinkKind::Request => format!("{base}/r/{}", link.token),
+        };
+        Self {
+            active: link.is_active(unix_now()),
+            has_password: link.password_hash.is_some(),
+            token: link.token,
+            kind: link.kind,
+            path: link.path,
+            url,
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            max_downloads: link.max_downloads,
+            downloads: link.downloads,
+            max_files: link.max_files,
+            max_bytes: link.max_bytes,
+            files: link.files,
+            bytes: link.bytes,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ShareQuery {
+    #[serde(default)]
+    disposition: Disposition,
+}
+
+/// 分享目录列表中的条目。
+#[derive(Serialize)]
+pub struct ShareEntry {
+    pub name: String,
+    /// 相对分享根目录的路径。
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<String>,
+}
+
+/// 分享目录的列表结果。
+#[derive(Serialize)]
+pub struct ShareListing {
+    /// 分享根目录名称。
+    pub name: String,
+    /// 当前目录相对分享根目录的路径（根目录为空字符串）。
+    pub path: String,
+    pub entries: Vec<ShareEntry>,
+}
+
+/// 为已有的文件或目录创建分享链接。
+pub async fn create_share(
+    headers: HeaderMap,
+    Extension(scheme): Extension<RequestScheme>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    Extension(link_config): Extension<Arc<LinkConfig>>,
+    Json(payload): Json<ShareCreateRequest>,
+) -> Result<Response, ApiError> {
+    let path = payload.path.trim().trim_matches(['/', '\\']).to_string();
+    if payload.expires_in == Some(0) {
+        return Err(ApiError::BadRequest("expiresIn must be positive".into()));
+    }
+    if payload.max_downloads == Some(0) {
+        return Err(ApiError::BadRequest("maxDownloads must be positive".into()));
+    }
+    let (volume, relative) = volumes.resolve(&path)?;
+    let target = volume
+        .storage()
+        .resolve_path_checked(relative, false)
+        .await?;
+    target
+        .metadata()
+        .await
+        .map_err(|_| ApiError::NotFound("path not found".into()))?;
+
+    let password_hash = match payload.password.filter(|password| !password.is_empty()) {
+        Some(password) => Some(
+            hash_password(password)
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?,
+        ),
+        None => None,
+    };
+    let now = unix_now();
+    let link = Link {
+        token: new_token(),
+        kind: LinkKind::Share,
+        path,
+        created_at: now,
+        expires_at: payload.expires_in.map(|secs| now.saturating_add(secs)),
+        max_downloads: payload.max_downloads,
+        downloads: 0,
+        password_hash,
+        max_files: None,
+        max_bytes: None,
+        files: 0,
+        bytes: 0,
+    };
+    links
+        .insert(link.clone())
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    info!(token = link.token, path = link.path, "create share");
+    let base =
+        link_base_url(link_config.public_url.as_deref(), &headers, scheme).unwrap_or_default();
+    Ok((
+        StatusCode::CREATED,
+        JsonResponse(LinkInfo::new(link, &base)),
+    )
+        .into_response())
+}
+
+/// 列出全部对外链接（含一次性上传链接）。
+pub async fn list_links(
+    headers: HeaderMap,
+    Extension(scheme): Extension<RequestScheme>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    Extension(link_config): Extension<Arc<LinkConfig>>,
+) -> JsonResponse<Vec<LinkInfo>> {
+    let base =
+        link_base_url(link_config.public_url.as_deref(), &headers, scheme).unwrap_or_default();
+    let list = links
+        .list()
+        .await
+        .into_iter()
+        .map(|link| LinkInfo::new(link, &base))
+        .collect();
+    JsonResponse(list)
+}
+
+/// 撤销链接；一次性上传链接的文件随之删除。
+pub async fn revoke_link(
+    Path(token): Path<String>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+) -> Result<StatusCode, ApiError> {
+    let link = links
+        .remove(&token)
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?
+        .ok_or_else(|| ApiError::NotFound("link not found".into()))?;
+    remove_link_files(&volumes, &link).await;
+    info!(token, path = link.path, "revoke link");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 访问分享根目录或分享的文件。
+#[allow(clippy::too_many_arguments)]
+pub async fn open_share(
+    method: Method,
+    Path(token): Path<String>,
+    Query(query): Query<ShareQuery>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(auth): Extension<Arc<AuthConfig>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(thumbnailer): Extension<Arc<Thumbnailer>>,
+    Extension(download): Extension<Arc<DownloadConfig>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+) -> Result<Response, ApiError> {
+    let share = ShareContext {
+        auth,
+        volumes,
+        thumbnailer,
+        download,
+        links,
+    };
+    share
+        .serve(method, &token, "", query.disposition, headers, addr)
+        .await
+}
+
+/// 访问分享目录中的子目录或文件。
+#[allow(clippy::too_many_arguments)]
+pub async fn open_share_path(
+    method: Method,
+    Path((token, path)): Path<(String, String)>,
+    Query(query): Query<ShareQuery>,
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(auth): Extension<Arc<AuthConfig>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(thumbnailer): Extension<Arc<Thumbnailer>>,
+    Extension(download): Extension<Arc<DownloadConfig>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+) -> Result<Response, ApiError> {
+    let share = ShareContext {
+        auth,
+        volumes,
+        thumbnailer,
+        download,
+        links,
+    };
+    share
+        .serve(method, &token, &path, query.disposition, headers, addr)
+        .await
+}
+
+struct ShareContext {
+    auth: Arc<AuthConfig>,
+    volumes: Arc<Volumes>,
+    thumbnailer: Arc<Thumbnailer>,
+    download: Arc<DownloadConfig>,
+    links: Arc<LinkStore>,
+}
+
+impl ShareContext {
+    async fn serve(
+        self,
+        method: Method,
+        token: &str,
+        sub_path: &str,
+        disposition: Disposition,
+        headers: HeaderMap,
+        addr: SocketAddr,
+    ) -> Result<Response, ApiError> {
+        let not_found = || ApiError::NotFound("link not found or expired".into());
+        let link = self
+            .links
+            .get(token)
+            .await
+            .filter(|link| link.kind == LinkKind::Share)
+            .ok_or_else(not_found)?;
+        if let Some(hash) = &link.password_hash {
+            self.check_password(hash, &headers, addr).await?;
+        }
+
+        let mut segments = Vec::new();
+        for segment in sub_path.split('/').filter(|segment| !segment.is_empty()) {
+            if segment == "." || segment == ".." || segment.contains('\\') {
+                return Err(ApiError::BadRequest("invalid path".into()));
+            }
+            segments.push(segment);
+        }
+        let sub_path = segments.join("/");
+        let public = match (link.path.is_empty(), sub_path.is_empty()) {
+            (_, true) => link.path.clone(),
+            (true, false) => sub_path.clone(),
+            (false, false) => format!("{}/{sub_path}", link.path),
+        };
+        let (volume, relative) = self.volumes.resolve(&public)?;
+        let storage = volume.storage();
+        // 分享内的路径与列表一致地应用隐藏规则。
+        let relative_segments: Vec<&str> = relative.split('/').filter(|s| !s.is_empty()).collect();
+        let first_sub = relative_segments.len() - segments.len();
+        if relative_segments
+            .iter()
+            .enumerate()
+            .skip(first_sub)
+            .any(|(index, name)| storage.is_hidden(index == 0, name.as_ref()))
+        {
+            return Err(ApiError::NotFound("path not found".into()));
+        }
+        // 分享内的路径以分享目录为边界解析，目录中的链接不能指向分享之外。
+        let (_, share_relative) = self.volumes.resolve(&link.path)?;
+        let share_root = if segments.is_empty() {
+            None
+        } else {
+            Some(storage.open_dir(Some(share_relative), false).await?)
+        };
+        let target = match &share_root {
+            Some(share_root) => storage.resolve_path_within(share_root, &sub_path).await?,
+            None => storage.resolve_path_checked(relative, false).await?,
+        };
+        let metadata = target
+            .metadata()
+            .await
+            .map_err(|_| ApiError::NotFound("path not found".into()))?;
+
+        if metadata.is_dir() {
+            let share_root = match share_root {
+                Some(share_root) => share_root,
+                None => storage.open_dir(Some(share_relative), false).await?,
+            };
+            let entries = match storage.list_dir_within(&share_root, &target).await {
+                Ok(entries) => entries,
+                Err(StorageError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(ApiError::NotFound("path not found".into()));
+                }
+                Err(err) => return Err(err.into()),
+            };
+            let listing = ShareListing {
+                name: link
+                    .path
+                    .rsplit('/')
+                    .next()
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(volume.name())
+                    .to_string(),
+                entries: entries
+                    .into_iter()
+                    .filter(|entry| !entry.is_symlink)
+                    .map(|entry| ShareEntry {
+                        path: if sub_path.is_empty() {
+                            entry.name.clone()
+                        } else {
+                            format!("{sub_path}/{}", entry.name)
+                        },
+                        name: entry.name,
+                        is_dir: entry.is_dir,
+                        size: entry.size,
+                        modified: entry.modified,
+                    })
+                    .collect(),
+                path: sub_path,
+            };
+            info!(token, path = public, "browse share");
+            let wants_html = headers
+                .get(header::ACCEPT)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.contains("text/html"));
+            return Ok(if wants_html {
+                Html(render_listing(token, &listing)).into_response()
+            } else {
+                JsonResponse(listing).into_response()
+            });
+        }
+
+        let counted = method != Method::HEAD;
+        if counted
+            && self
+                .links
+                .claim_download(token)
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?
+                .is_none()
+        {
+            return Err(not_found());
+        }
+        let result = download_file(
+            method,
+            Query(DownloadQuery {
+                path: public,
+                disposition,
+            }),
+            Query(TransformQuery::default()),
+            headers,
+            Extension(self.volumes),
+            Extension(self.thumbnailer),
+            Extension(self.download),
+        )
+        .await;
+        let served = matches!(&result, Ok(response) if response.status().is_success());
+        if counted
+            && !served
+            && let Err(err) = self.links.release_download(token).await
+        {
+            warn!(token, error = %err, "failed to release share download");
+        }
+        result
+    }
+
+    /// 校验分享密码；失败次数与登录共用按 IP 的限流。
+    async fn check_password(
+        &self,
+        hash: &str,
+        headers: &HeaderMap,
+        addr: SocketAddr,
+    ) -> Result<(), ApiError> {
+        let client_ip = resolve_client_ip(headers, Some(addr.ip())).unwrap_or_else(|| addr.ip());
+        if let Some(retry_after) = check_login_rate_limit(&self.auth, client_ip).await {
+            return Err(ApiError::TooManyRequests(retry_after));
+        }
+        let password = headers
+            .get(SHARE_PASSWORD_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .or_else(|| {
+                headers
+                    .get(header::AUTHORIZATION)
+                    .and_then(Basic::decode)
+                    .map(|basic| basic.password().to_string())
+            });
+        let Some(password) = password else {
+            return Err(password_challenge());
+        };
+        if verify_password(hash.to_string(), password).await {
+            return Ok(());
+        }
+        register_login_failure(&self.auth, client_ip).await;
+        Err(password_challenge())
+    }
+}
+
+fn password_challenge() -> ApiError {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static(r#"Basic realm="AxoDrive share""#),
+    );
+    ApiError::Unauthorized(headers)
+}
+
+/// 渲染供浏览器访问的只读目录页面。
+fn render_listing(token: &str, listing: &ShareListing) -> String {
+    let href = |path: &str, is_dir: bool| {
+        let mut href = format!("/s/{token}");
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            href.push('/');
+            href.push_str(&encode_path_segment(segment));
+        }
+        if is_dir {
+            href.push('/');
+        }
+        href
+    };
+    let title = if listing.path.is_empty() {
+        escape_html(&listing.name)
+    } else {
+        escape_html(&format!("{}/{}", listing.name, listing.path))
+    };
+    let mut rows = String::new();
+    if let Some(parent) = (!listing.path.is_empty()).then(|| {
+        listing
+            .path
+            .rsplit_once('/')
+            .map_or("", |(parent, _)| parent)
+    }) {
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}\">..</a></td><td></td><td></td></tr>\n",
+            href(parent, true)
+        ));
+    }
+    for entry in &listing.entries {
+        let name = if entry.is_dir {
+            format!("{}/", escape_html(&entry.name))
+        } else {
+            escape_html(&entry.name)
+        };
+        let size = if entry.is_dir {
+            String::new()
+        } else {
+            entry.size.to_string()
+        };
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}\">{name}</a></td><td>{size}</td><td>{}</td></tr>\n",
+            href(&entry.path, entry.is_dir),
+            escape_html(entry.modified.as_deref().unwrap_or_default())
+        ));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title></head>\n\
+         <body><h1>{title}</h1>\n<table>\n\
+         <tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n{rows}</table></body></html>\n"
+    )
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tempfile::tempdir;
+    use tokio::sync::Mutex;
+
+    use crate::storage::Storage;
+
+    #[tokio::test]
+    async fn password_protected_folder_share_counts_file_downloads() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(root.join("docs/sub")).expect("create dirs");
+        std::fs::write(root.join("docs/sub/a.txt"), b"hello").expect("write file");
+        std::fs::write(root.join("secret.txt"), b"secret").expect("write file");
+        let volumes = Arc::new(Volumes::single(Arc::new(Storage::new(root))));
+        let links = Arc::new(
+            LinkStore::open(&temp.path().join("links.json"))
+                .await
+                .expect("links"),
+        );
+        let link_config = Arc::new(LinkConfig {
+            public_url: Some("https://drive.example".into()),
+        });
+
+        let response = create_share(
+            HeaderMap::new(),
+            Extension(RequestScheme::Https),
+            Extension(volumes.clone()),
+            Extension(links.clone()),
+            Extension(link_config.clone()),
+            Json(ShareCreateRequest {
+                path: "/docs/".into(),
+                expires_in: Some(3600),
+                password: Some("pw".into()),
+                max_downloads: Some(1),
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("create share failed"));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let token = links.list().await[0].token.clone();
+
+        let auth = Arc::new(AuthConfig {
+            username: "admin".into(),
+            password: "admin".into(),
+            sessions: Mutex::new(HashMap::new()),
+            session_ttl: Duration::from_secs(60),
+            login_attempts: Mutex::new(HashMap::new()),
+            login_window: Duration::from_secs(60),
+            login_max_attempts: 5,
+            login_lockout: Duration::from_secs(60),
+        });
+        let open = |method: Method, path: &str, password: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(password) = password {
+                headers.insert(SHARE_PASSWORD_HEADER, HeaderValue::from_static(password));
+            }
+            open_share_path(
+                method,
+                Path((token.clone(), path.to_string())),
+                Query(ShareQuery {
+                    disposition: Disposition::default(),
+                }),
+                headers,
+                ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))),
+                Extension(auth.clone()),
+                Extension(volumes.clone()),
+                Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+                Extension(Arc::new(DownloadConfig {
+                    cache_control: HeaderValue::from_static("private, no-cache"),
+                })),
+                Extension(links.clone()),
+            )
+        };
+
+        assert!(matches!(
+            open(Method::GET, "sub", None).await,
+            Err(ApiError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            open(Method::GET, "sub", Some("wrong")).await,
+            Err(ApiError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            open(Method::GET, "../secret.txt", Some("pw")).await,
+            Err(ApiError::BadRequest(_))
+        ));
+
+        let listing = open(Method::GET, "sub", Some("pw"))
+            .await
+            .unwrap_or_else(|_| panic!("listing failed"));
+        let body = axum::body::to_bytes(listing.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let listing: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(listing["name"], "docs");
+        assert_eq!(listing["entries"][0]["path"], "sub/a.txt");
+
+        let head = open(Method::HEAD, "sub/a.txt", Some("pw"))
+            .await
+            .unwrap_or_else(|_| panic!("head failed"));
+        assert_eq!(head.status(), StatusCode::OK);
+        let download = open(Method::GET, "sub/a.txt", Some("pw"))
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        let body = axum::body::to_bytes(download.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(&body[..], b"hello");
+        assert!(matches!(
+            open(Method::GET, "sub/a.txt", Some("pw")).await,
+            Err(ApiError::NotFound(_))
+        ));
+
+        let listed = list_links(
+            HeaderMap::new(),
+            Extension(RequestScheme::Https),
+            Extension(links.clone()),
+            Extension(link_config),
+        )
+        .await;
+        assert_eq!(listed.0[0].url, format!("https://drive.example/s/{token}"));
+        assert!(listed.0[0].has_password);
+        assert!(!listed.0[0].active);
+
+        let revoked = revoke_link(
+            Path(token.clone()),
+            Extension(volumes.clone()),
+            Extension(links.clone()),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("revoke failed"));
+        assert_eq!(revoked, StatusCode::NO_CONTENT);
+        assert!(links.list().await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn followed_links_cannot_leave_the_shared_folder() {
+        use crate::storage::SymlinkPolicy;
+        use std::os::unix::fs::symlink;
+
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(root.join("docs/sub")).expect("create dirs");
+        std::fs::create_dir_all(root.join("private")).expect("create private");
+        std::fs::write(root.join("docs/sub/a.txt"), b"hello").expect("write file");
+        std::fs::write(root.join("private/secret.txt"), b"secret").expect("write file");
+        symlink("../private", root.join("docs/x")).expect("outside link");
+        symlink("sub", root.join("docs/inner")).expect("inside link");
+        let storage = Storage::new(root).with_symlink_policy(SymlinkPolicy::Follow);
+        let volumes = Arc::new(Volumes::single(Arc::new(storage)));
+        let links = Arc::new(
+            LinkStore::open(&temp.path().join("links.json"))
+                .await
+                .expect("links"),
+        );
+        create_share(
+            HeaderMap::new(),
+            Extension(RequestScheme::Https),
+            Extension(volumes.clone()),
+            Extension(links.clone()),
+            Extension(Arc::new(LinkConfig { public_url: None })),
+            Json(ShareCreateRequest {
+                path: "docs".into(),
+                expires_in: None,
+                password: None,
+                max_downloads: None,
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("create share failed"));
+        let token = links.list().await[0].token.clone();
+        let auth = Arc::new(AuthConfig {
+            username: "admin".into(),
+            password: "admin".into(),
+            sessions: Mutex::new(HashMap::new()),
+            session_ttl: Duration::from_secs(60),
+            login_attempts: Mutex::new(HashMap::new()),
+            login_window: Duration::from_secs(60),
+            login_max_attempts: 5,
+            login_lockout: Duration::from_secs(60),
+        });
+        let open = |path: &str| {
+            open_share_path(
+                Method::GET,
+                Path((token.clone(), path.to_string())),
+                Query(ShareQuery {
+                    disposition: Disposition::default(),
+                }),
+                HeaderMap::new(),
+                ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))),
+                Extension(auth.clone()),
+                Extension(volumes.clone()),
+                Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+                Extension(Arc::new(DownloadConfig {
+                    cache_control: HeaderValue::from_static("private, no-cache"),
+                })),
+                Extension(links.clone()),
+            )
+        };
+
+        assert!(open("x/secret.txt").await.is_err());
+        assert!(open("x").await.is_err());
+        let inside = open("inner/a.txt")
+            .await
+            .unwrap_or_else(|_| panic!("download through inside link failed"));
+        let body = axum::body::to_bytes(inside.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(&body[..], b"hello");
+
+        let listing = open("").await.unwrap_or_else(|_| panic!("listing failed"));
+        let body = axum::body::to_bytes(listing.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let listing: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        let names: Vec<&str> = listing["entries"]
+            .as_array()
+            .expect("entries")
+            .iter()
+            .filter_map(|entry| entry["name"].as_str())
+            .collect();
+        assert_eq!(names, vec!["inner", "sub"]);
+    }
+}
@@ -0,0 +1,265 @@
+//! 文件详细元数据：RFC 3339 时间、权限与属主、按内容识别的 MIME 类型与目录子项数。
+
+use axum::extract::{Extension, Query};
+use axum::response::Json as JsonResponse;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::config::STAT_SNIFF_BYTES;
+use crate::content::ContentReader;
+use crate::error::ApiError;
+use crate::etag::etag_from_metadata;
+use crate::storage::{FileEntry, ResolvedPath, Storage, StorageError, format_timestamp};
+use crate::volumes::Volumes;
+
+/// 列表 `detail=true` 与 stat 接口附加的详细字段。
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct EntryDetails {
+    /// 创建时间（RFC 3339，文件系统不支持时为 None）。
+    pub created_at: Option<String>,
+    /// 修改时间（RFC 3339）。
+    pub modified_at: Option<String>,
+    /// 访问时间（RFC 3339）。
+    pub accessed_at: Option<String>,
+    /// Unix 权限位（八进制字符串，如 `0644`）。
+    pub mode: Option<String>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// 属主用户名（无法解析时为 None）。
+    pub owner: Option<String>,
+    /// 属组名称（无法解析时为 None）。
+    pub group: Option<String>,
+    /// 按文件头魔数识别的 MIME 类型，无法识别时按扩展名推断；目录为 None。
+    pub mime: Option<String>,
+    /// 目录中可见子项数量（与列表一致地应用隐藏与符号链接策略）；文件为 None。
+    pub child_count: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct StatQuery {
+    path: String,
+}
+
+/// 返回单个文件或目录的详细元数据。
+pub async fn stat_file(
+    Query(query): Query<StatQuery>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+) -> Result<JsonResponse<FileEntry>, ApiError> {
+    let (volume, relative) = volumes.resolve(&query.path)?;
+    let mut entry = stat_entry(volume.storage(), relative).await?;
+    entry.path = volumes.public_path(volume, &entry.path);
+    if entry.name.is_empty() {
+        entry.name = volume.name().to_string();
+    }
+    info!(path = query.path, is_dir = entry.is_dir, "stat file");
+    Ok(JsonResponse(entry))
+}
+
+/// 读取存储相对路径的列表条目及详细字段；根目录的名称为空字符串。
+pub async fn stat_entry(storage: &Storage, relative: &str) -> Result<FileEntry, StorageError> {
+    let target = storage.resolve_path_checked(relative, false).await?;
+    let metadata = target.metadata().await?;
+    let is_dir = metadata.is_dir();
+    let is_symlink = metadata.file_type().is_symlink();
+    let mut reader = if metadata.is_file() {
+        Some(ContentReader::new(target.open_file().await?, storage.content_format()).await?)
+    } else {
+        None
+    };
+    let size = match &reader {
+        Some(reader) => reader.len(),
+        None if is_symlink => 0,
+        None => metadata.len(),
+    };
+    let details = entry_details(storage, &target, &metadata, reader.as_mut()).await?;
+    let path = target
+        .path()
+        .strip_prefix(storage.root_path())
+        .map_err(|_| StorageError::InvalidPath)?
+        .to_string_lossy()
+        .replace(std::path::MAIN_SEPARATOR, "/");
+    Ok(FileEntry {
+        name: target
+            .name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path,
+        is_dir,
+        is_symlink,
+        size,
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(|ts| ts.duration_since(UNIX_EPOCH).ok())
+            .and_then(format_timestamp),
+        etag: (!is_dir && !is_symlink).then(|| etag_from_metadata(&metadata, size)),
+        captured: None,
+        details: Some(details),
+    })
+}
+
+/// 收集条目的详细字段；`reader` 为文件内容读取器，用于识别 MIME 类型。
+async fn entry_details(
+    storage: &Storage,
+    target: &ResolvedPath,
+    metadata: &Metadata,
+    reader: Option<&mut ContentReader>,
+) -> io::Result<EntryDetails> {
+    let mut details = EntryDetails {
+        created_at: rfc3339(metadata.created()),
+        modified_at: rfc3339(metadata.modified()),
+        accessed_at: rfc3339(metadata.accessed()),
+        ..EntryDetails::default()
+    };
+    apply_ownership(&mut details, metadata).await;
+    if let Some(reader) = reader {
+        let head = reader.read_at(0, STAT_SNIFF_BYTES).await?;
+        details.mime = Some(sniff_mime(&target.path().to_string_lossy(), &head));
+    } else if metadata.is_dir() {
+        details.child_count = Some(count_children(storage, target).await?);
+    }
+    Ok(details)
+}
+
+fn rfc3339(time: io::Result<SystemTime>) -> Option<String> {
+    // 超出可表示范围的时间直接省略，避免转换时 panic。
+    let time = time.ok()?;
+    let time = match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => DateTime::<Utc>::UNIX_EPOCH
+            .checked_add_signed(chrono::TimeDelta::from_std(after).ok()?)?,
+        Err(err) => DateTime::<Utc>::UNIX_EPOCH
+            .checked_sub_signed(chrono::TimeDelta::from_std(err.duration()).ok()?)?,
+    };
+    Some(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+#[cfg(unix)]
+async fn apply_ownership(details: &mut EntryDetails, metadata: &Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    let (uid, gid) = (metadata.uid(), metadata.gid());
+    details.mode = Some(format!("{:04o}", metadata.mode() & 0o7777));
+    details.uid = Some(uid);
+    details.gid = Some(gid);
+    // 用户名解析可能经过 NSS（如 LDAP），放到阻塞线程中执行。
+    let names = tokio::task::spawn_blocking(move || {
+        (
+            uzers::get_user_by_uid(uid).map(|user| user.name().to_string_lossy().into_owned()),
+            uzers::get_group_by_gid(gid).map(|group| group.name().to_string_lossy().into_owned()),
+        )
+    })
+    .await;
+    if let Ok((owner, group)) = names {
+        details.owner = owner;
+        details.group = group;
+    }
+}
+
+#[cfg(not(unix))]
+async fn apply_ownership(_details: &mut EntryDetails, _metadata: &Metadata) {}
+
+/// 优先按魔数识别；无法识别时按扩展名推断，未知扩展名的 UTF-8 文本视为 `text/plain`。
+fn sniff_mime(path: &str, head: &[u8]) -> String {
+    if let Some(kind) = infer::get(head) {
+        return kind.mime_type().to_string();
+    }
+    let guessed = mime_guess::from_path(path).first_or_octet_stream();
+    if guessed == mime_guess::mime::APPLICATION_OCTET_STREAM
+        && !head.is_empty()
+        && !head.contains(&0)
+        && is_utf8_prefix(head)
+    {
+        return "text/plain".to_string();
+    }
+    guessed.essence_str().to_string()
+}
+
+/// 判断字节是否为合法 UTF-8，允许末尾被截断的多字节字符。
+fn is_utf8_prefix(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+async fn count_children(storage: &Storage, target: &ResolvedPath) -> io::Result<u64> {
+    let dir = match target.name() {
+        Some(name) => target.dir().open_dir(Path::new(name), false).await?,
+        None => target.dir().clone(),
+    };
+    let at_root = dir.path() == storage.root_path();
+    let mut count = 0;
+    for name in dir.read_dir().await? {
+        if !storage.is_hidden(at_root, &name) && storage.resolve_entry(&dir, &name).await?.is_some()
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Keyring;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn stat_reports_sniffed_mime_times_and_child_count() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(root.join("docs/nested")).expect("create dirs");
+        std::fs::write(root.join("docs/readme"), "plain text").expect("write text");
+        std::fs::write(
+            root.join("docs/.a.tmp.00000000-0000-0000-0000-000000000000"),
+            "temp",
+        )
+        .expect("write temp");
+        let png = [
+            0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 0x0d, b'I', b'H', b'D', b'R',
+        ];
+        std::fs::write(root.join("docs/image.dat"), png).expect("write png");
+        let storage = Storage::new(root).with_keyring(Some(Keyring::new(&[3u8; 32], &[])));
+
+        let image = stat_entry(&storage, "docs/image.dat")
+            .await
+            .expect("stat image");
+        let details = image.details.expect("details");
+        assert_eq!(image.size, png.len() as u64);
+        assert_eq!(details.mime.as_deref(), Some("image/png"));
+        assert!(details.child_count.is_none());
+        let modified_at = details.modified_at.expect("modified");
+        assert!(DateTime::parse_from_rfc3339(&modified_at).is_ok());
+        #[cfg(unix)]
+        {
+            assert!(details.mode.is_some());
+            assert!(details.uid.is_some());
+        }
+
+        let text = stat_entry(&storage, "docs/readme")
+            .await
+            .expect("stat text");
+        assert_eq!(
+            text.details.and_then(|details| details.mime).as_deref(),
+            Some("text/plain")
+        );
+
+        let dir = stat_entry(&storage, "docs").await.expect("stat dir");
+        assert!(dir.is_dir);
+        assert_eq!(dir.name, "docs");
+        let details = dir.details.expect("details");
+        assert_eq!(details.child_count, Some(3));
+        assert!(details.mime.is_none());
+
+        assert!(matches!(
+            stat_entry(&storage, "docs/missing").await,
+            Err(StorageError::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+    }
+}
@@ -1,28 +1,155 @@
 //! 存储层工具：在指定根目录下执行文件操作。
 //!
-//! 存储层负责规范化用户路径、阻止符号链接穿透，并提供
-//! 列表、创建、删除等基础能力。
+//! 存储层负责规范化用户路径，并从根目录句柄出发逐级解析路径以阻止
+//! 符号链接穿透，提供列表、创建、删除等基础能力。
 
 use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use serde::Serialize;
 use std::cmp::Ordering;
-use std::io;
+use std::ffi::{OsStr, OsString};
+use std::fs::Metadata;
+use std::io::{self, ErrorKind};
 use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, UNIX_EPOCH};
-use tokio::fs;
-use tokio::io::ErrorKind;
+use tokio::fs::{self, File};
 
+use crate::atomic::is_temp_name;
+use crate::beneath::{DirHandle, is_escape};
+use crate::compression::CompressionConfig;
+use crate::content::{ContentFormat, ContentReader};
+use crate::crypto::Keyring;
 use crate::etag::etag_from_metadata;
+use crate::hidden::HiddenNames;
+use crate::stat::EntryDetails;
 /// Filesystem-backed storage rooted at a dedicated directory.
 #[derive(Clone, Debug)]
 pub struct Storage {
     root: PathBuf,
+    keyring: Option<Arc<Keyring>>,
+    compression: Option<Arc<CompressionConfig>>,
+    temp_dir: Option<PathBuf>,
+    symlink_policy: SymlinkPolicy,
+    hidden: Option<Arc<HiddenNames>>,
+}
+
+/// 存储根目录内符号链接的处理策略。
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SymlinkPolicy {
+    /// 拒绝访问经过符号链接的路径，列表中隐藏链接。
+    #[default]
+    Reject,
+    /// 跟随解析结果仍位于根目录内的符号链接。
+    Follow,
+    /// 列表中将链接显示为条目，但不跟随其读写。
+    Show,
 }
 
 impl Storage {
     /// 创建以指定目录为根的存储实例。
     pub fn new(root: PathBuf) -> Self {
-        Self { root }
+        Self {
+            root,
+            keyring: None,
+            compression: None,
+            temp_dir: None,
+            symlink_policy: SymlinkPolicy::default(),
+            hidden: None,
+        }
+    }
+
+    /// 设置静态加密密钥环（None 表示不加密）。
+    pub fn with_keyring(mut self, keyring: Option<Keyring>) -> Self {
+        self.keyring = keyring.map(Arc::new);
+        self
+    }
+
+    /// 返回静态加密密钥环。
+    pub fn keyring(&self) -> Option<&Keyring> {
+        self.keyring.as_deref()
+    }
+
+    /// 设置按目录启用的静态压缩（None 表示不压缩）。
+    pub fn with_compression(mut self, compression: Option<CompressionConfig>) -> Self {
+        self.compression = compression.map(Arc::new);
+        self
+    }
+
+    /// 返回写入目标路径时应使用的压缩级别（不压缩时为 None）。
+    pub fn compression_level(&self, target: &Path) -> Option<i32> {
+        let relative = target.strip_prefix(&self.root).ok()?;
+        self.compression.as_ref()?.level_for(relative)
+    }
+
+    /// 返回读取存储文件时需要识别的格式。
+    pub fn content_format(&self) -> ContentFormat<'_> {
+        ContentFormat {
+            keyring: self.keyring(),
+            compressed: self.compression.is_some(),
+        }
+    }
+
+    /// 指定上传临时目录（默认位于存储根目录的上级）。
+    pub fn with_temp_dir(mut self, temp_dir: PathBuf) -> Self {
+        self.temp_dir = Some(temp_dir);
+        self
+    }
+
+    /// 返回显式配置的上传临时目录。
+    pub fn temp_dir(&self) -> Option<&Path> {
+        self.temp_dir.as_deref()
+    }
+
+    /// 返回位于根目录内的上传临时目录名（临时目录在根目录外时为 None）。
+    pub fn internal_dir_name(&self) -> Option<&OsStr> {
+        let relative = self.temp_dir.as_deref()?.strip_prefix(&self.root).ok()?;
+        match relative.components().next()? {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// 设置列表中额外隐藏的名称模式（None 表示不额外隐藏）。
+    pub fn with_hidden(mut self, hidden: Option<HiddenNames>) -> Self {
+        self.hidden = hidden.map(Arc::new);
+        self
+    }
+
+    /// 判断目录项是否应从列表中隐藏：根目录下的上传临时目录、原子写入
+    /// 遗留的临时文件以及匹配隐藏模式的名称。
+    pub fn is_hidden(&self, at_root: bool, name: &OsStr) -> bool {
+        (at_root && self.internal_dir_name() == Some(name))
+            || is_temp_name(name)
+            || self
+                .hidden
+                .as_ref()
+                .is_some_and(|hidden| hidden.matches(&name.to_string_lossy()))
+    }
+
+    /// 设置符号链接处理策略。
+    pub fn with_symlink_policy(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlink_policy = policy;
+        self
+    }
+
+    /// 计算文件基于客户端可见内容长度的 ETag。
+    pub async fn content_etag(&self, target: &ResolvedPath, metadata: &Metadata) -> String {
+        etag_from_metadata(metadata, self.content_len(target, metadata).await)
+    }
+
+    /// 返回文件在客户端可见的内容长度（目录与无法识别格式的文件返回磁盘长度）。
+    pub async fn content_len(&self, target: &ResolvedPath, metadata: &Metadata) -> u64 {
+        if !metadata.is_file() || self.content_format().is_plain() {
+            return metadata.len();
+        }
+        match target.open_file().await {
+            Ok(file) => ContentReader::new(file, self.content_format())
+                .await
+                .map(|reader| reader.len())
+                .unwrap_or(metadata.len()),
+            Err(_) => metadata.len(),
+        }
     }
 
     /// 确保根目录在磁盘上存在。
@@ -35,92 +162,209 @@ impl Storage {
         &self.root
     }
 
-    /// 解析并校验相对路径，可选择允许末端不存在。
+    /// 在根目录句柄下解析相对路径，`create_parents` 为真时创建缺失的父目录。
+    /// 末端可以不存在；路径中的符号链接按配置的策略处理。
     pub async fn resolve_path_checked(
         &self,
         relative: &str,
-        allow_missing_leaf: bool,
-    ) -> Result<PathBuf, StorageError> {
-        let target = self.resolve(Some(relative))?;
-        self.ensure_no_symlink_components(&target, allow_missing_leaf)
-            .await?;
-        Ok(target)
+        create_parents: bool,
+    ) -> Result<ResolvedPath, StorageError> {
+        let root = DirHandle::open_root(&self.root).await?;
+        self.resolve_from(&root, relative, create_parents).await
     }
 
-    /// 解析并校验存储根路径。
-    pub async fn resolve_root_checked(&self) -> Result<PathBuf, StorageError> {
-        let target = self.resolve(None)?;
-        self.ensure_no_symlink_components(&target, false).await?;
-        Ok(target)
+    /// 以 `base` 目录为边界解析其下的相对路径：跟随策略下链接的目标也不得越出 `base`，
+    /// 用于分享等只允许访问某个子目录的场景。
+    pub async fn resolve_path_within(
+        &self,
+        base: &DirHandle,
+        relative: &str,
+    ) -> Result<ResolvedPath, StorageError> {
+        self.resolve_from(base, relative, false).await
     }
 
-    fn resolve(&self, relative: Option<&str>) -> Result<PathBuf, StorageError> {
-        let mut normalized = PathBuf::new();
+    async fn resolve_from(
+        &self,
+        base: &DirHandle,
+        relative: &str,
+        create_parents: bool,
+    ) -> Result<ResolvedPath, StorageError> {
+        let normalized = self.normalize(relative)?;
+        let follow = self.symlink_policy == SymlinkPolicy::Follow;
+        let (dir, name) = base.resolve(&normalized, create_parents, follow).await?;
+        let resolved = ResolvedPath::new(dir, name);
+        match resolved.metadata().await {
+            Ok(metadata)
+                if metadata.file_type().is_symlink()
+                    && self.symlink_policy != SymlinkPolicy::Show =>
+            {
+                Err(StorageError::InvalidPath)
+            }
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(resolved),
+        }
+    }
 
-        if let Some(value) = relative {
-            let trimmed = value.trim_start_matches(['/', '\\']);
-            for component in Path::new(trimmed).components() {
-                match component {
-                    Component::Normal(segment) => normalized.push(segment),
-                    Component::CurDir => continue,
-                    Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
-                        return Err(StorageError::InvalidPath);
-                    }
-                }
+    /// 解析存储相对路径但不跟随末端的符号链接，用于删除或重命名链接本身；
+    /// 拒绝策略下末端为链接时返回 InvalidPath。
+    pub async fn resolve_link_checked(&self, relative: &str) -> Result<ResolvedPath, StorageError> {
+        let normalized = self.normalize(relative)?;
+        let resolved = match (normalized.parent(), normalized.file_name()) {
+            (Some(parent), Some(name)) => {
+                let dir = self.open_dir(parent.to_str(), false).await?;
+                ResolvedPath::new(dir, Some(name.to_os_string()))
+            }
+            _ => ResolvedPath::new(DirHandle::open_root(&self.root).await?, None),
+        };
+        match resolved.metadata().await {
+            Ok(metadata)
+                if metadata.file_type().is_symlink()
+                    && self.symlink_policy == SymlinkPolicy::Reject =>
+            {
+                Err(StorageError::InvalidPath)
             }
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(resolved),
+        }
+    }
+
+    /// 打开存储相对路径对应的目录句柄（None 表示根目录）。
+    pub async fn open_dir(
+        &self,
+        relative: Option<&str>,
+        create: bool,
+    ) -> Result<DirHandle, StorageError> {
+        let normalized = match relative {
+            Some(path) => self.normalize(path)?,
+            None => PathBuf::new(),
+        };
+        let root = DirHandle::open_root(&self.root).await?;
+        let follow = self.symlink_policy == SymlinkPolicy::Follow;
+        let (dir, name) = root.resolve(&normalized, create, follow).await?;
+        match name {
+            Some(name) => Ok(dir.open_dir(Path::new(&name), create).await?),
+            None => Ok(dir),
         }
+    }
 
-        Ok(self.root.join(normalized))
+    /// 按符号链接策略解析目录中的条目并读取元数据；返回 None 表示该条目
+    /// 应被隐藏（拒绝策略下的链接，或跟随策略下指向根目录外、已失效的链接）。
+    pub async fn resolve_entry(
+        &self,
+        dir: &DirHandle,
+        name: &OsStr,
+    ) -> io::Result<Option<(ResolvedPath, Metadata)>> {
+        self.resolve_entry_within(None, dir, name).await
     }
 
-    async fn ensure_no_symlink_components(
+    /// 同 [`Storage::resolve_entry`]，但跟随链接时以 `base`（None 表示存储根目录）为边界，
+    /// 从边界目录按完整相对路径解析，与按路径访问时的判定保持一致。
+    pub async fn resolve_entry_within(
         &self,
-        target: &Path,
-        allow_missing_leaf: bool,
-    ) -> Result<(), StorageError> {
-        let relative = target
-            .strip_prefix(&self.root)
-            .map_err(|_| StorageError::InvalidPath)?;
-        let mut current = PathBuf::from(&self.root);
-        let mut components = relative.components().peekable();
-
-        while let Some(component) = components.next() {
-            current.push(component.as_os_str());
-            match fs::symlink_metadata(&current).await {
-                Ok(metadata) => {
-                    if metadata.file_type().is_symlink() {
-                        return Err(StorageError::InvalidPath);
+        base: Option<&DirHandle>,
+        dir: &DirHandle,
+        name: &OsStr,
+    ) -> io::Result<Option<(ResolvedPath, Metadata)>> {
+        let metadata = match dir.metadata(Some(name)).await {
+            Ok(metadata) => metadata,
+            // 列出与读取元数据之间被删除的条目直接跳过。
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let entry = ResolvedPath::new(dir.clone(), Some(name.to_os_string()));
+        if !metadata.file_type().is_symlink() {
+            return Ok(Some((entry, metadata)));
+        }
+        match self.symlink_policy {
+            SymlinkPolicy::Reject => Ok(None),
+            SymlinkPolicy::Show => Ok(Some((entry, metadata))),
+            SymlinkPolicy::Follow => {
+                let root;
+                let base = match base {
+                    Some(base) => base,
+                    None => {
+                        root = DirHandle::open_root(&self.root).await?;
+                        &root
                     }
-                    if components.peek().is_some() && !metadata.is_dir() {
-                        return Err(StorageError::InvalidPath);
+                };
+                let Ok(dir_relative) = dir.path().strip_prefix(base.path()) else {
+                    return Ok(None);
+                };
+                let relative = dir_relative.join(name);
+                let target = match base.resolve(&relative, false, true).await {
+                    Ok((dir, name)) => ResolvedPath::new(dir, name),
+                    Err(err) if is_escape(&err) || err.kind() == ErrorKind::NotFound => {
+                        return Ok(None);
                     }
+                    Err(err) => return Err(err),
+                };
+                match target.metadata().await {
+                    Ok(metadata) => Ok(Some((target, metadata))),
+                    Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+                    Err(err) => Err(err),
                 }
-                Err(err) if err.kind() == ErrorKind::NotFound && allow_missing_leaf => {
-                    return Ok(());
-                }
-                Err(err) => return Err(StorageError::Io(err)),
             }
         }
+    }
 
-        Ok(())
+    fn normalize(&self, value: &str) -> Result<PathBuf, StorageError> {
+        let mut normalized = PathBuf::new();
+        let trimmed = value.trim_start_matches(['/', '\\']);
+        for component in Path::new(trimmed).components() {
+            match component {
+                Component::Normal(segment)
+                    if normalized.as_os_str().is_empty()
+                        && self.internal_dir_name() == Some(segment) =>
+                {
+                    return Err(StorageError::InvalidPath);
+                }
+                Component::Normal(segment) => normalized.push(segment),
+                Component::CurDir => continue,
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(StorageError::InvalidPath);
+                }
+            }
+        }
+        Ok(normalized)
     }
 
     /// 列出目录内容并返回排序后的元数据。
     pub async fn list_dir(&self, relative: Option<&str>) -> Result<Vec<FileEntry>, StorageError> {
-        let target = match relative {
-            Some(path) => self.resolve_path_checked(path, false).await?,
-            None => self.resolve_root_checked().await?,
+        let dir = self.open_dir(relative, false).await?;
+        self.list_handle(None, dir).await
+    }
+
+    /// 列出已解析的目录，跟随链接时以 `base` 为边界（见 [`Storage::resolve_path_within`]）。
+    pub async fn list_dir_within(
+        &self,
+        base: &DirHandle,
+        target: &ResolvedPath,
+    ) -> Result<Vec<FileEntry>, StorageError> {
+        let dir = match target.name() {
+            Some(name) => target.dir().open_dir(Path::new(name), false).await?,
+            None => target.dir().clone(),
         };
-        let mut dir = fs::read_dir(&target).await?;
+        self.list_handle(Some(base), dir).await
+    }
+
+    async fn list_handle(
+        &self,
+        base: Option<&DirHandle>,
+        dir: DirHandle,
+    ) -> Result<Vec<FileEntry>, StorageError> {
         let mut entries = Vec::new();
 
-        while let Some(entry) = dir.next_entry().await? {
-            let metadata = entry.metadata().await?;
-            let path = entry.path();
-            let name = entry.file_name().to_string_lossy().to_string();
-            if name == ".upload_temp" {
+        let at_root = dir.path() == self.root;
+        for name in dir.read_dir().await? {
+            if self.is_hidden(at_root, &name) {
                 continue;
             }
+            let Some((target, metadata)) = self.resolve_entry_within(base, &dir, &name).await?
+            else {
+                continue;
+            };
+            let path = dir.path().join(&name);
+            let name = name.to_string_lossy().to_string();
             let relative_path = path
                 .strip_prefix(&self.root)
                 .map_err(|_| StorageError::InvalidPath)?
@@ -130,21 +374,32 @@ impl Storage {
                 .modified()
                 .ok()
                 .and_then(|ts| ts.duration_since(UNIX_EPOCH).ok())
-                .map(format_timestamp);
+                .and_then(format_timestamp);
 
             let is_dir = metadata.is_dir();
-            let etag = if is_dir {
+            let is_symlink = metadata.file_type().is_symlink();
+            let size = if metadata.is_file() {
+                self.content_len(&target, &metadata).await
+            } else if is_symlink {
+                0
+            } else {
+                metadata.len()
+            };
+            let etag = if is_dir || is_symlink {
                 None
             } else {
-                Some(etag_from_metadata(&metadata))
+                Some(etag_from_metadata(&metadata, size))
             };
             entries.push(FileEntry {
                 name,
                 path: relative_path,
                 is_dir,
-                size: metadata.len(),
+                is_symlink,
+                size,
                 modified,
                 etag,
+                captured: None,
+                details: None,
             });
         }
 
@@ -157,30 +412,84 @@ impl Storage {
         Ok(entries)
     }
 
-    /// 删除存储根目录下的文件或目录（递归）。
-    pub async fn delete_path(&self, relative: &str) -> Result<(), StorageError> {
-        let target = self.resolve_path_checked(relative, false).await?;
-        let metadata = fs::metadata(&target).await?;
+    /// 删除存储根目录下的文件或目录（递归），返回删除的是否为目录。
+    /// 末端为符号链接时只删除链接本身。
+    pub async fn delete_path(&self, relative: &str) -> Result<bool, StorageError> {
+        let normalized = self.normalize(relative)?;
+        let (Some(parent), Some(name)) = (normalized.parent(), normalized.file_name()) else {
+            return Err(StorageError::InvalidPath);
+        };
+        let dir = self.open_dir(parent.to_str(), false).await?;
+        let metadata = dir.metadata(Some(name)).await?;
+        if metadata.file_type().is_symlink() && self.symlink_policy == SymlinkPolicy::Reject {
+            return Err(StorageError::InvalidPath);
+        }
         if metadata.is_dir() {
-            fs::remove_dir_all(target).await?;
+            dir.remove_dir_all(name).await?;
         } else {
-            fs::remove_file(target).await?;
+            dir.remove_file(name).await?;
         }
-        Ok(())
+        Ok(metadata.is_dir())
     }
 
     /// 在存储根目录下创建目录及其缺失的父级。
     pub async fn create_dir(&self, relative: &str) -> Result<(), StorageError> {
-        let target = self.resolve_path_checked(relative, true).await?;
-        fs::create_dir_all(target).await?;
+        self.open_dir(Some(relative), true).await?;
         Ok(())
     }
 }
 
-fn format_timestamp(duration: Duration) -> String {
-    let timestamp = UNIX_EPOCH + duration;
-    let datetime: DateTime<Utc> = timestamp.into();
-    datetime.format("%Y-%m-%d %H:%M:%S").to_string()
+/// 存储相对路径的解析结果：父目录句柄与末端名称。
+#[derive(Clone, Debug)]
+pub struct ResolvedPath {
+    dir: DirHandle,
+    name: Option<OsString>,
+    path: PathBuf,
+}
+
+impl ResolvedPath {
+    fn new(dir: DirHandle, name: Option<OsString>) -> Self {
+        let path = match &name {
+            Some(name) => dir.path().join(name),
+            None => dir.path().to_path_buf(),
+        };
+        Self { dir, name, path }
+    }
+
+    /// 返回父目录句柄（路径为根目录时为根目录句柄）。
+    pub fn dir(&self) -> &DirHandle {
+        &self.dir
+    }
+
+    /// 返回末端名称（路径为根目录时为 None）。
+    pub fn name(&self) -> Option<&OsStr> {
+        self.name.as_deref()
+    }
+
+    /// 返回解析后的磁盘路径，仅用于日志与配置匹配。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 读取末端的元数据（不跟随符号链接）。
+    pub async fn metadata(&self) -> io::Result<Metadata> {
+        self.dir.metadata(self.name()).await
+    }
+
+    /// 以只读方式打开末端文件。
+    pub async fn open_file(&self) -> io::Result<File> {
+        let name = self
+            .name()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "path is not a file"))?;
+        self.dir.open_file(name).await
+    }
+}
+
+/// 将自 Unix 纪元起的时长格式化为 UTC 时间，超出可表示范围时返回 None。
+pub(crate) fn format_timestamp(duration: Duration) -> Option<String> {
+    let seconds = i64::try_from(duration.as_secs()).ok()?;
+    let datetime = DateTime::<Utc>::from_timestamp(seconds, duration.subsec_nanos())?;
+    Some(datetime.format("%Y-%m-%d %H:%M:%S").to_string())
 }
 
 /// 存储操作可能返回的错误类型。
@@ -188,13 +497,21 @@ fn format_timestamp(duration: Duration) -> String {
 pub enum StorageError {
     /// 路径非法或试图越界存储根目录。
     InvalidPath,
+    /// 目标位于只读卷。
+    ReadOnly,
+    /// 写入后将超出卷容量配额。
+    QuotaExceeded,
     /// 文件系统 I/O 错误。
     Io(io::Error),
 }
 
 impl From<io::Error> for StorageError {
     fn from(err: io::Error) -> Self {
-        StorageError::Io(err)
+        if is_escape(&err) {
+            StorageError::InvalidPath
+        } else {
+            StorageError::Io(err)
+        }
     }
 }
 
@@ -207,17 +524,25 @@ pub struct FileEntry {
     pub path: String,
     /// 是否为目录。
     pub is_dir: bool,
+    /// 是否为未跟随的符号链接（仅 `show` 策略下出现）。
+    pub is_symlink: bool,
     /// 文件大小（字节），目录为 0。
     pub size: u64,
     /// 格式化后的修改时间。
     pub modified: Option<String>,
     /// 文件的 ETag（目录为 None）。
     pub etag: Option<String>,
+    /// 拍摄时间（仅按拍摄时间排序的列表中出现）。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub captured: Option<String>,
+    /// 详细字段（仅 stat 接口与 `detail=true` 的列表中出现）。
+    #[serde(flatten)]
+    pub details: Option<EntryDetails>,
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Storage, StorageError};
+    use super::{Storage, StorageError, SymlinkPolicy};
     use tempfile::tempdir;
 
     #[cfg(unix)]
@@ -234,8 +559,107 @@ mod tests {
         let link_path = root.join("link");
         symlink(&outside, &link_path).expect("symlink");
 
-        let storage = Storage::new(root);
+        let storage = Storage::new(root.clone());
         let result = storage.resolve_path_checked("link", false).await;
         assert!(matches!(result, Err(StorageError::InvalidPath)));
+
+        let outside_dir = temp.path().join("outside");
+        std::fs::create_dir_all(&outside_dir).expect("create outside dir");
+        symlink(&outside_dir, root.join("linkdir")).expect("dir symlink");
+        let result = storage
+            .resolve_path_checked("linkdir/new/a.txt", true)
+            .await;
+        assert!(matches!(result, Err(StorageError::InvalidPath)));
+        assert!(!outside_dir.join("new").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlink_policy_controls_listing_and_resolution() {
+        use std::os::unix::fs::symlink;
+
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("root");
+        let outside = temp.path().join("outside");
+        std::fs::create_dir_all(root.join("docs")).expect("create docs");
+        std::fs::create_dir_all(&outside).expect("create outside");
+        std::fs::write(root.join("docs/a.txt"), b"hello").expect("write file");
+        symlink("docs", root.join("alias")).expect("relative link");
+        symlink(root.join("docs/a.txt"), root.join("file")).expect("absolute link");
+        symlink(&outside, root.join("escape")).expect("outside link");
+
+        let names = |entries: Vec<super::FileEntry>| {
+            entries
+                .into_iter()
+                .map(|entry| {
+                    let size = if entry.is_dir { 0 } else { entry.size };
+                    (entry.name, entry.is_dir, entry.is_symlink, size)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let reject = Storage::new(root.clone());
+        assert_eq!(
+            names(reject.list_dir(None).await.expect("list")),
+            vec![("docs".to_string(), true, false, 0)]
+        );
+        assert!(matches!(
+            reject.resolve_path_checked("alias/a.txt", false).await,
+            Err(StorageError::InvalidPath)
+        ));
+
+        let follow = Storage::new(root.clone()).with_symlink_policy(SymlinkPolicy::Follow);
+        let listed = names(follow.list_dir(None).await.expect("list"));
+        assert_eq!(listed.len(), 3);
+        assert!(listed.contains(&("alias".to_string(), true, false, 0)));
+        assert!(listed.contains(&("file".to_string(), false, false, 5)));
+        let target = follow
+            .resolve_path_checked("alias/a.txt", false)
+            .await
+            .unwrap_or_else(|_| panic!("follow inside root"));
+        assert_eq!(target.path(), root.join("docs/a.txt"));
+        assert!(matches!(
+            follow.resolve_path_checked("escape/x.txt", true).await,
+            Err(StorageError::InvalidPath)
+        ));
+
+        let show = Storage::new(root.clone()).with_symlink_policy(SymlinkPolicy::Show);
+        let listed = names(show.list_dir(None).await.expect("list"));
+        assert!(listed.contains(&("escape".to_string(), false, true, 0)));
+        assert!(matches!(
+            show.resolve_path_checked("alias/a.txt", false).await,
+            Err(StorageError::InvalidPath)
+        ));
+        let link = show
+            .resolve_path_checked("file", false)
+            .await
+            .unwrap_or_else(|_| panic!("show link entry"));
+        assert!(link.open_file().await.is_err());
+        show.delete_path("escape").await.expect("delete link");
+        assert!(outside.exists());
+        assert!(!root.join("escape").exists());
+
+        // 子目录中的链接按根目录为边界解析，列表与按路径访问一致。
+        std::fs::create_dir_all(root.join("nested/deep")).expect("create nested");
+        symlink("../../docs/a.txt", root.join("nested/deep/up")).expect("parent link");
+        symlink(root.join("docs"), root.join("nested/deep/abs")).expect("absolute link");
+        symlink("../../../outside", root.join("nested/deep/out")).expect("outside link");
+        let listed = names(follow.list_dir(Some("nested/deep")).await.expect("list"));
+        assert_eq!(
+            listed,
+            vec![
+                ("abs".to_string(), true, false, 0),
+                ("up".to_string(), false, false, 5),
+            ]
+        );
+        for path in ["nested/deep/up", "nested/deep/abs/a.txt"] {
+            assert!(follow.resolve_path_checked(path, false).await.is_ok());
+        }
+        assert!(
+            follow
+                .resolve_path_checked("nested/deep/out", false)
+                .await
+                .is_err()
+        );
     }
 }
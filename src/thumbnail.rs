@@ -0,0 +1,628 @@
+//! 图片缩略图、下载时的图片变换与磁盘缓存。
+//!
+//! 输出按源文件 ETag 与变换参数缓存，源文件变化后自动失效；启用存储加密时缓存同样加密保存。
+
+use axum::extract::{Extension, Query};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::{self, Cursor, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Semaphore, broadcast};
+use tracing::{debug, info, warn};
+
+use crate::cache::DiskCache;
+use crate::config::{
+    DEFAULT_IMAGE_QUALITY, DEFAULT_THUMBNAIL_SIZE, THUMBNAIL_CACHE_TTL_SECS,
+    THUMBNAIL_MAX_CONCURRENT, THUMBNAIL_MAX_DIMENSION, THUMBNAIL_MAX_SOURCE_SIZE, THUMBNAIL_SIZES,
+};
+use crate::content::ContentReader;
+use crate::crypto::hex_encode;
+use crate::error::ApiError;
+use crate::etag::none_match;
+use crate::events::{ChangeKind, EventHub};
+use crate::storage::{Storage, StorageError};
+use crate::volumes::{Volume, Volumes};
+
+/// 支持生成缩略图的源文件扩展名。
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp"];
+
+/// 图片输出格式。WebP 为无损编码，`quality` 仅作用于 JPEG。
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl OutputFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Webp => "image/webp",
+        }
+    }
+}
+
+/// 输出格式的选择方式。
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatChoice {
+    /// 不透明图片输出 JPEG，带透明通道的图片输出 WebP（缩略图默认）。
+    Auto,
+    /// 保持源格式，无法编码的源格式（GIF、BMP）输出 PNG（图片变换默认）。
+    Source,
+    Fixed(OutputFormat),
+}
+
+impl FormatChoice {
+    fn tag(self) -> &'static str {
+        match self {
+            FormatChoice::Auto => "auto",
+            FormatChoice::Source => "source",
+            FormatChoice::Fixed(format) => format.extension(),
+        }
+    }
+
+    /// 缓存中可能存在的输出格式。
+    fn candidates(self) -> &'static [OutputFormat] {
+        match self {
+            FormatChoice::Auto => &[OutputFormat::Jpeg, OutputFormat::Webp],
+            FormatChoice::Source => &[OutputFormat::Jpeg, OutputFormat::Png, OutputFormat::Webp],
+            FormatChoice::Fixed(OutputFormat::Jpeg) => &[OutputFormat::Jpeg],
+            FormatChoice::Fixed(OutputFormat::Png) => &[OutputFormat::Png],
+            FormatChoice::Fixed(OutputFormat::Webp) => &[OutputFormat::Webp],
+        }
+    }
+}
+
+/// 图片变换参数：在宽高限制内等比缩放（不放大）并编码为目标格式。
+#[derive(Clone, Copy, Debug)]
+pub struct ImageTransform {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: FormatChoice,
+    pub quality: u8,
+}
+
+/// 生成完成的图片。
+pub struct RenderedImage {
+    pub data: Vec<u8>,
+    pub format: OutputFormat,
+    pub etag: String,
+}
+
+/// 缩略图与图片变换生成器：管理缓存目录、允许的变换尺寸并限制并发解码数量。
+pub struct Thumbnailer {
+    cache: DiskCache,
+    permits: Semaphore,
+    transform_sizes: Vec<u32>,
+}
+
+impl Thumbnailer {
+    /// 创建使用指定缓存目录的生成器。
+    pub fn new(cache_dir: PathBuf) -> Self {
+        Self {
+            cache: DiskCache::new(cache_dir),
+            permits: Semaphore::new(THUMBNAIL_MAX_CONCURRENT),
+            transform_sizes: Vec::new(),
+        }
+    }
+
+    /// 设置下载变换允许的宽高（像素），为空时禁用下载变换。
+    pub fn with_transform_sizes(mut self, sizes: Vec<u32>) -> Self {
+        self.transform_sizes = sizes;
+        self
+    }
+
+    /// 返回（必要时生成）指定文件的缩略图。`size` 向上取整到预设尺寸，
+    /// `format` 为空时不透明图片输出 JPEG、带透明通道的图片输出 WebP。
+    pub async fn thumbnail(
+        &self,
+        volume: &Volume,
+        relative: &str,
+        size: u32,
+        format: Option<OutputFormat>,
+    ) -> Result<RenderedImage, ApiError> {
+        let size = THUMBNAIL_SIZES
+            .iter()
+            .copied()
+            .find(|candidate| *candidate >= size)
+            .unwrap_or(THUMBNAIL_SIZES[THUMBNAIL_SIZES.len() - 1]);
+        let transform = ImageTransform {
+            width: Some(size),
+            height: Some(size),
+            format: format.map_or(FormatChoice::Auto, FormatChoice::Fixed),
+            quality: DEFAULT_IMAGE_QUALITY,
+        };
+        self.render(volume, relative, transform).await
+    }
+
+    /// 按下载请求中的参数变换图片；宽高必须为配置允许的尺寸之一。
+    pub async fn transform(
+        &self,
+        volume: &Volume,
+        relative: &str,
+        query: &TransformQuery,
+    ) -> Result<RenderedImage, ApiError> {
+        if self.transform_sizes.is_empty() {
+            return Err(ApiError::BadRequest("image transforms are disabled".into()));
+        }
+        let allowed =
+            |size: Option<u32>| size.is_none_or(|size| self.transform_sizes.contains(&size));
+        if !allowed(query.w) || !allowed(query.h) {
+            return Err(ApiError::BadRequest("image size is not allowed".into()));
+        }
+        let quality = query.quality.unwrap_or(DEFAULT_IMAGE_QUALITY);
+        if !(1..=100).contains(&quality) {
+            return Err(ApiError::BadRequest("quality must be 1-100".into()));
+        }
+        let transform = ImageTransform {
+            width: query.w,
+            height: query.h,
+            format: query
+                .format
+                .map_or(FormatChoice::Source, FormatChoice::Fixed),
+            quality,
+        };
+        self.render(volume, relative, transform).await
+    }
+
+    async fn render(
+        &self,
+        volume: &Volume,
+        relative: &str,
+        transform: ImageTransform,
+    ) -> Result<RenderedImage, ApiError> {
+        if !is_image_path(relative) {
+            return Err(ApiError::BadRequest("unsupported image format".into()));
+        }
+        let storage = volume.storage();
+        let target = storage.resolve_path_checked(relative, false).await?;
+        let metadata = target.metadata().await.map_err(StorageError::from)?;
+        if !metadata.is_file() {
+            return Err(ApiError::BadRequest("path is not a file".into()));
+        }
+        let source_etag = storage.content_etag(&target, &metadata).await;
+        let key = cache_key(volume.name(), relative, &source_etag, &transform);
+        let etag = format!("W/\"{}\"", &key[..32]);
+        if let Some(cached) = self.read_cached(storage, &key, transform.format).await {
+            return Ok(cached.with_etag(etag));
+        }
+
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        // 等待期间可能已由其他请求生成。
+        if let Some(cached) = self.read_cached(storage, &key, transform.format).await {
+            return Ok(cached.with_etag(etag));
+        }
+        let file = target.open_file().await.map_err(StorageError::from)?;
+        let mut reader = ContentReader::new(file, storage.content_format())
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        if reader.len() > THUMBNAIL_MAX_SOURCE_SIZE {
+            return Err(ApiError::BadRequest("image is too large".into()));
+        }
+        let source = reader
+            .read_to_end()
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        let (data, format) = tokio::task::spawn_blocking(move || render_image(source, transform))
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?
+            .map_err(|err| ApiError::BadRequest(format!("cannot decode image: {err}")))?;
+        if let Err(err) = self
+            .cache
+            .write(storage.keyring(), &key, format.extension(), &data)
+            .await
+        {
+            warn!(path = relative, error = %err, "failed to cache rendered image");
+        }
+        debug!(path = relative, ?transform, "rendered image");
+        Ok(RenderedImage { data, format, etag })
+    }
+
+    async fn read_cached(
+        &self,
+        storage: &Storage,
+        key: &str,
+        format: FormatChoice,
+    ) -> Option<RenderedImage> {
+        for &format in format.candidates() {
+            if let Some(data) = self
+                .cache
+                .read(storage.keyring(), key, format.extension())
+                .await
+            {
+                return Some(RenderedImage {
+                    data,
+                    format,
+                    etag: String::new(),
+                });
+            }
+        }
+        None
+    }
+
+    /// 删除超过保留时长未更新的缓存文件，返回删除数量。
+    pub async fn prune_cache(&self) -> io::Result<u64> {
+        self.cache
+            .prune(Duration::from_secs(THUMBNAIL_CACHE_TTL_SECS))
+            .await
+    }
+}
+
+impl RenderedImage {
+    fn with_etag(mut self, etag: String) -> Self {
+        self.etag = etag;
+        self
+    }
+}
+
+/// 判断路径扩展名是否为支持的图片格式。
+pub fn is_image_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|candidate| ext.eq_ignore_ascii_case(candidate))
+        })
+}
+
+fn cache_key(
+    volume: &str,
+    relative: &str,
+    source_etag: &str,
+    transform: &ImageTransform,
+) -> String {
+    let mut hasher = Sha256::new();
+    let format = transform.format.tag();
+    for part in [volume, relative.trim_matches('/'), source_etag, format] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.update(transform.width.unwrap_or_default().to_be_bytes());
+    hasher.update(transform.height.unwrap_or_default().to_be_bytes());
+    // 质量只影响 JPEG；不可能输出 JPEG 时不同质量共用同一缓存。
+    if transform.format.candidates().contains(&OutputFormat::Jpeg) {
+        hasher.update([transform.quality]);
+    }
+    hex_encode(&hasher.finalize())
+}
+
+/// 解码图片、按 EXIF 方向旋转并等比缩放到宽高限制以内（不放大），返回编码结果与格式。
+fn render_image(
+    source: Vec<u8>,
+    transform: ImageTransform,
+) -> image::ImageResult<(Vec<u8>, OutputFormat)> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(THUMBNAIL_MAX_DIMENSION);
+    limits.max_image_height = Some(THUMBNAIL_MAX_DIMENSION);
+    let mut reader = ImageReader::new(Cursor::new(source)).with_guessed_format()?;
+    reader.limits(limits);
+    let source_format = match reader.format() {
+        Some(ImageFormat::Jpeg) => OutputFormat::Jpeg,
+        Some(ImageFormat::WebP) => OutputFormat::Webp,
+        _ => OutputFormat::Png,
+    };
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    let max_width = transform.width.unwrap_or(u32::MAX);
+    let max_height = transform.height.unwrap_or(u32::MAX);
+    if image.width() > max_width || image.height() > max_height {
+        image = image.thumbnail(max_width, max_height);
+    }
+    let format = match transform.format {
+        FormatChoice::Auto if image.color().has_alpha() => OutputFormat::Webp,
+        FormatChoice::Auto => OutputFormat::Jpeg,
+        FormatChoice::Source => source_format,
+        FormatChoice::Fixed(format) => format,
+    };
+    let mut data = Vec::new();
+    match format {
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, transform.quality))?,
+        OutputFormat::Png => image.write_with_encoder(PngEncoder::new(&mut data))?,
+        OutputFormat::Webp => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(WebPEncoder::new_lossless(&mut data))?,
+    }
+    Ok((data, format))
+}
+
+/// 新上传或修改的图片在后台预先生成默认尺寸的缩略图。
+pub fn spawn_pregeneration(volumes: Arc<Volumes>, thumbnailer: Arc<Thumbnailer>, hub: &EventHub) {
+    let mut rx = hub.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let change = match rx.recv().await {
+                Ok(change) => change,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let kind = change.kind;
+            if change.is_dir
+                || !matches!(
+                    kind,
+                    ChangeKind::Created | ChangeKind::Modified | ChangeKind::Moved
+                )
+                || !is_image_path(&change.path)
+            {
+                continue;
+            }
+            let Ok((volume, relative)) = volumes.resolve(&change.path) else {
+                continue;
+            };
+            match thumbnailer
+                .thumbnail(volume, relative, DEFAULT_THUMBNAIL_SIZE, None)
+                .await
+            {
+                Ok(_) => debug!(path = change.path, "pre-generated thumbnail"),
+                Err(_) => debug!(path = change.path, "thumbnail pre-generation skipped"),
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ThumbnailQuery {
+    path: String,
+    size: Option<u32>,
+    format: Option<OutputFormat>,
+}
+
+/// 返回图片缩略图，支持 If-None-Match 协商缓存。
+pub async fn get_thumbnail(
+    Query(query): Query<ThumbnailQuery>,
+    headers: HeaderMap,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(thumbnailer): Extension<Arc<Thumbnailer>>,
+) -> Result<Response, ApiError> {
+    let (volume, relative) = volumes.resolve(&query.path)?;
+    let size = query.size.unwrap_or(DEFAULT_THUMBNAIL_SIZE);
+    let thumbnail = thumbnailer
+        .thumbnail(volume, relative, size, query.format)
+        .await?;
+    info!(path = query.path, size, "thumbnail");
+    image_response(thumbnail, &headers)
+}
+
+/// 下载接口中的图片变换参数。
+#[derive(Default, Deserialize)]
+pub(crate) struct TransformQuery {
+    w: Option<u32>,
+    h: Option<u32>,
+    format: Option<OutputFormat>,
+    quality: Option<u8>,
+}
+
+impl TransformQuery {
+    /// 是否请求了任一变换参数。
+    pub(crate) fn is_requested(&self) -> bool {
+        self.w.is_some() || self.h.is_some() || self.format.is_some() || self.quality.is_some()
+    }
+}
+
+/// 构建图片响应，If-None-Match 匹配时返回 304。
+pub(crate) fn image_response(
+    image: RenderedImage,
+    request_headers: &HeaderMap,
+) -> Result<Response, ApiError> {
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&image.etag)
+            .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
+    );
+    response_headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-cache"),
+    );
+    if !none_match(request_headers, &image.etag) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(image.format.content_type()),
+    );
+    Ok((StatusCode::OK, response_headers, image.data).into_response())
+}
+
+/// 解析逗号分隔的变换尺寸列表。
+pub fn parse_transform_sizes(sizes: &str) -> io::Result<Vec<u32>> {
+    sizes
+        .split(',')
+        .map(str::trim)
+        .filter(|size| !size.is_empty())
+        .map(|size| {
+            size.parse::<u32>()
+                .ok()
+                .filter(|size| (1..=THUMBNAIL_MAX_DIMENSION).contains(size))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("invalid image transform size: {size}"),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Keyring;
+    use image::{ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn thumbnails_are_resized_and_cached_by_etag() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create root");
+        RgbImage::from_pixel(600, 300, Rgb([200, 10, 10]))
+            .save(root.join("wide.png"))
+            .expect("save png");
+        RgbaImage::from_pixel(40, 20, Rgba([0, 0, 0, 0]))
+            .save(root.join("clear.png"))
+            .expect("save png");
+        let storage = Storage::new(root.clone()).with_keyring(Some(Keyring::new(&[3u8; 32], &[])));
+        let volume = Volume::new("", Arc::new(storage));
+        let thumbnailer = Thumbnailer::new(temp.path().join("thumbs"));
+
+        let first = thumbnailer
+            .thumbnail(&volume, "wide.png", 100, None)
+            .await
+            .unwrap_or_else(|_| panic!("thumbnail"));
+        assert_eq!(first.format, OutputFormat::Jpeg);
+        let decoded = image::load_from_memory_with_format(&first.data, ImageFormat::Jpeg)
+            .expect("decode thumbnail");
+        assert_eq!((decoded.width(), decoded.height()), (128, 64));
+        let cached = std::fs::read_dir(temp.path().join("thumbs"))
+            .expect("cache dir")
+            .flatten()
+            .flat_map(|shard| std::fs::read_dir(shard.path()).expect("shard"))
+            .count();
+        assert_eq!(cached, 1);
+        let again = thumbnailer
+            .thumbnail(&volume, "wide.png", 128, None)
+            .await
+            .unwrap_or_else(|_| panic!("thumbnail"));
+        assert_eq!((again.data, again.etag), (first.data, first.etag.clone()));
+
+        RgbImage::from_pixel(10, 10, Rgb([0, 0, 0]))
+            .save(root.join("wide.png"))
+            .expect("replace png");
+        let std_file = std::fs::File::options()
+            .write(true)
+            .open(root.join("wide.png"))
+            .expect("open");
+        std_file
+            .set_modified(SystemTime::now() + Duration::from_secs(5))
+            .expect("touch");
+        let changed = thumbnailer
+            .thumbnail(&volume, "wide.png", 128, None)
+            .await
+            .unwrap_or_else(|_| panic!("thumbnail"));
+        assert_ne!(changed.etag, first.etag);
+
+        let clear = thumbnailer
+            .thumbnail(&volume, "clear.png", 64, None)
+            .await
+            .unwrap_or_else(|_| panic!("thumbnail"));
+        assert_eq!(clear.format, OutputFormat::Webp);
+        assert!(matches!(
+            thumbnailer.thumbnail(&volume, "notes.txt", 64, None).await,
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn transforms_enforce_allowed_sizes_and_keep_source_format() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create root");
+        RgbImage::from_pixel(1000, 500, Rgb([1, 2, 3]))
+            .save(root.join("chart.png"))
+            .expect("save png");
+        let volume = Volume::new("", Arc::new(Storage::new(root)));
+        let thumbnailer =
+            Thumbnailer::new(temp.path().join("thumbs")).with_transform_sizes(vec![320, 800]);
+        let query = |w, format, quality| TransformQuery {
+            w,
+            h: None,
+            format,
+            quality,
+        };
+
+        let png = thumbnailer
+            .transform(&volume, "chart.png", &query(Some(320), None, None))
+            .await
+            .unwrap_or_else(|_| panic!("transform"));
+        assert_eq!(png.format, OutputFormat::Png);
+        let decoded = image::load_from_memory(&png.data).expect("decode");
+        assert_eq!((decoded.width(), decoded.height()), (320, 160));
+
+        let jpeg = |quality| query(Some(800), Some(OutputFormat::Jpeg), Some(quality));
+        let high = thumbnailer
+            .transform(&volume, "chart.png", &jpeg(90))
+            .await
+            .unwrap_or_else(|_| panic!("transform"));
+        let low = thumbnailer
+            .transform(&volume, "chart.png", &jpeg(20))
+            .await
+            .unwrap_or_else(|_| panic!("transform"));
+        assert_eq!(high.format, OutputFormat::Jpeg);
+        assert_ne!(high.etag, low.etag);
+
+        let webp = |quality| query(Some(800), Some(OutputFormat::Webp), Some(quality));
+        let lossless = thumbnailer
+            .transform(&volume, "chart.png", &webp(90))
+            .await
+            .unwrap_or_else(|_| panic!("transform"));
+        let ignored = thumbnailer
+            .transform(&volume, "chart.png", &webp(20))
+            .await
+            .unwrap_or_else(|_| panic!("transform"));
+        assert_eq!(lossless.format, OutputFormat::Webp);
+        assert_eq!(lossless.etag, ignored.etag);
+
+        for rejected in [query(Some(500), None, None), jpeg(0)] {
+            assert!(matches!(
+                thumbnailer.transform(&volume, "chart.png", &rejected).await,
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn exif_orientation_is_applied() {
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 20, Rgb([10, 200, 10])))
+            .write_with_encoder(JpegEncoder::new(&mut jpeg))
+            .expect("encode jpeg");
+        // APP1 段：大端 TIFF 头，IFD0 仅含 Orientation = 6（顺时针旋转 90°）。
+        let exif: &[u8] = &[
+            b'E', b'x', b'i', b'f', 0, 0, b'M', b'M', 0, 42, 0, 0, 0, 8, 0, 1, 0x01, 0x12, 0, 3, 0,
+            0, 0, 1, 0, 6, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut source = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        source.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+        source.extend_from_slice(exif);
+        source.extend_from_slice(&jpeg[2..]);
+
+        let transform = ImageTransform {
+            width: Some(64),
+            height: Some(64),
+            format: FormatChoice::Auto,
+            quality: DEFAULT_IMAGE_QUALITY,
+        };
+        let (data, format) = render_image(source, transform).expect("render");
+        assert_eq!(format, OutputFormat::Jpeg);
+        let rotated = image::load_from_memory(&data).expect("decode");
+        assert_eq!((rotated.width(), rotated.height()), (20, 40));
+    }
+}
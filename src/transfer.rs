@@ -0,0 +1,282 @@
+//! 一次性上传：`curl -T` 上传文件后返回按天数或下载次数失效的临时下载链接。
+
+use axum::body::Body as AxumBody;
+use axum::extract::{Extension, Path, Query};
+use axum::http::{HeaderMap, Method, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::TRANSFER_MAX_DAYS;
+use crate::error::ApiError;
+use crate::events::EventHub;
+use crate::files::{ConflictPolicy, DownloadConfig, DownloadQuery, download_file, store_body};
+use crate::http::{Disposition, RequestScheme, encode_path_segment, link_base_url};
+use crate::links::{Link, LinkConfig, LinkKind, LinkStore, new_token, remove_link_files, unix_now};
+use crate::locking::LockManager;
+use crate::thumbnail::{Thumbnailer, TransformQuery};
+use crate::volumes::Volumes;
+
+#[derive(Debug)]
+pub struct TransferConfig {
+    /// 保存上传文件的目录（对外路径），每次上传位于其下的令牌子目录中。
+    pub dir: String,
+    pub default_days: u64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TransferQuery {
+    days: Option<u64>,
+    downloads: Option<u64>,
+}
+
+/// 上传文件并以纯文本返回临时下载链接。
+///
+/// 有效天数与下载次数可由 `days`、`downloads` 查询参数或 transfer.sh 兼容的
+/// `Max-Days`、`Max-Downloads` 请求头指定，未指定天数时使用默认值。
+#[allow(clippy::too_many_arguments)]
+pub async fn put_transfer(
+    Path(name): Path<String>,
+    Query(query): Query<TransferQuery>,
+    headers: HeaderMap,
+    Extension(scheme): Extension<RequestScheme>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+    Extension(config): Extension<Arc<TransferConfig>>,
+    Extension(link_config): Extension<Arc<LinkConfig>>,
+    body: AxumBody,
+) -> Result<Response, ApiError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(ApiError::BadRequest("invalid file name".into()));
+    }
+    let days = match query.days {
+        Some(days) => Some(days),
+        None => header_number(&headers, "max-days")?,
+    }
+    .unwrap_or(config.default_days);
+    if days == 0 || days > TRANSFER_MAX_DAYS {
+        return Err(ApiError::BadRequest(format!(
+            "days must be between 1 and {TRANSFER_MAX_DAYS}"
+        )));
+    }
+    let max_downloads = match query.downloads {
+        Some(downloads) => Some(downloads),
+        None => header_number(&headers, "max-downloads")?,
+    };
+    if max_downloads == Some(0) {
+        return Err(ApiError::BadRequest("downloads must be positive".into()));
+    }
+
+    let token = new_token();
+    let dir = config.dir.trim_matches('/');
+    let path = if dir.is_empty() {
+        format!("{token}/{name}")
+    } else {
+        format!("{dir}/{token}/{name}")
+    };
+    let (path, _) = store_body(
+        &volumes,
+        &lock_manager,
+        &hub,
+        &headers,
+        &path,
+        ConflictPolicy::Overwrite,
+        body,
+    )
+    .await?;
+    let now = unix_now();
+    let link = Link {
+        token,
+        kind: LinkKind::Transfer,
+        path,
+        created_at: now,
+        expires_at: Some(now + days * 24 * 60 * 60),
+        max_downloads,
+        downloads: 0,
+        password_hash: None,
+        max_files: None,
+        max_bytes: None,
+        files: 0,
+        bytes: 0,
+    };
+    if let Err(err) = links.insert(link.clone()).await {
+        remove_link_files(&volumes, &link).await;
+        return Err(ApiError::Internal(err.to_string()));
+    }
+
+    let base =
+        link_base_url(link_config.public_url.as_deref(), &headers, scheme).unwrap_or_default();
+    let url = format!("{base}/t/{}/{}", link.token, encode_path_segment(&name));
+    info!(
+        token = link.token,
+        path = link.path,
+        days,
+        max_downloads,
+        "create transfer"
+    );
+    Ok((
+        StatusCode::CREATED,
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        format!("{url}\n"),
+    )
+        .into_response())
+}
+
+/// 通过临时链接下载文件；成功的 GET 计入下载次数，HEAD 与 304 不计入。
+pub async fn get_transfer(
+    method: Method,
+    Path((token, name)): Path<(String, String)>,
+    headers: HeaderMap,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(thumbnailer): Extension<Arc<Thumbnailer>>,
+    Extension(download): Extension<Arc<DownloadConfig>>,
+    Extension(links): Extension<Arc<LinkStore>>,
+) -> Result<Response, ApiError> {
+    let not_found = || ApiError::NotFound("link not found or expired".into());
+    let link = links.get(&token).await.ok_or_else(not_found)?;
+    if link.kind != LinkKind::Transfer || link.path.rsplit('/').next() != Some(name.as_str()) {
+        return Err(not_found());
+    }
+    let counted = method != Method::HEAD;
+    if counted
+        && links
+            .claim_download(&token)
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?
+            .is_none()
+    {
+        return Err(not_found());
+    }
+
+    let result = download_file(
+        method,
+        Query(DownloadQuery {
+            path: link.path,
+            disposition: Disposition::Inline,
+        }),
+        Query(TransformQuery::default()),
+        headers,
+        Extension(volumes),
+        Extension(thumbnailer),
+        Extension(download),
+    )
+    .await;
+    let served = matches!(&result, Ok(response) if response.status().is_success());
+    if counted
+        && !served
+        && let Err(err) = links.release_download(&token).await
+    {
+        warn!(token, error = %err, "failed to release transfer download");
+    }
+    result
+}
+
+fn header_number(headers: &HeaderMap, name: &str) -> Result<Option<u64>, ApiError> {
+    headers
+        .get(name)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| ApiError::BadRequest(format!("{name} is invalid")))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use tempfile::tempdir;
+
+    use crate::storage::Storage;
+
+    #[tokio::test]
+    async fn transfer_links_expire_after_max_downloads() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(&root).expect("create storage root");
+        let volumes = Arc::new(Volumes::single(Arc::new(Storage::new(root.clone()))));
+        let links = Arc::new(
+            LinkStore::open(&temp.path().join("links.json"))
+                .await
+                .expect("links"),
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("drive.example"));
+        headers.insert("max-downloads", HeaderValue::from_static("1"));
+
+        let response = put_transfer(
+            Path("build log.txt".to_string()),
+            Query(TransferQuery {
+                days: Some(2),
+                downloads: None,
+            }),
+            headers,
+            Extension(RequestScheme::Https),
+            Extension(volumes.clone()),
+            Extension(Arc::new(LockManager::new())),
+            Extension(Arc::new(EventHub::new())),
+            Extension(links.clone()),
+            Extension(Arc::new(TransferConfig {
+                dir: "transfers".into(),
+                default_days: 14,
+            })),
+            Extension(Arc::new(LinkConfig { public_url: None })),
+            AxumBody::from("log"),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("put transfer failed"));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let url = String::from_utf8(body.to_vec()).expect("utf8");
+        let rest = url
+            .trim_end()
+            .strip_prefix("https://drive.example/t/")
+            .expect("link url");
+        let (token, name) = rest.split_once('/').expect("token and name");
+        assert_eq!(name, "build%20log.txt");
+        let link = links.get(token).await.expect("link");
+        assert_eq!(link.path, format!("transfers/{token}/build log.txt"));
+        assert!(link.expires_at.expect("expiry") >= unix_now() + 2 * 24 * 60 * 60 - 5);
+
+        let fetch = |method: Method, name: &str| {
+            get_transfer(
+                method,
+                Path((token.to_string(), name.to_string())),
+                HeaderMap::new(),
+                Extension(volumes.clone()),
+                Extension(Arc::new(Thumbnailer::new(temp.path().join("thumbs")))),
+                Extension(Arc::new(DownloadConfig {
+                    cache_control: HeaderValue::from_static("private, no-cache"),
+                })),
+                Extension(links.clone()),
+            )
+        };
+        assert!(matches!(
+            fetch(Method::GET, "other.txt").await,
+            Err(ApiError::NotFound(_))
+        ));
+        let head = fetch(Method::HEAD, "build log.txt")
+            .await
+            .unwrap_or_else(|_| panic!("head failed"));
+        assert_eq!(head.status(), StatusCode::OK);
+        let download = fetch(Method::GET, "build log.txt")
+            .await
+            .unwrap_or_else(|_| panic!("download failed"));
+        let body = axum::body::to_bytes(download.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        assert_eq!(&body[..], b"log");
+        assert!(matches!(
+            fetch(Method::GET, "build log.txt").await,
+            Err(ApiError::NotFound(_))
+        ));
+    }
+}
@@ -2,28 +2,37 @@
 
 use axum::Error as AxumError;
 use axum::body::Body as AxumBody;
-use axum::extract::{Extension, Json, Query};
-use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::extract::multipart::{Field, MultipartError};
+use axum::extract::{Extension, Json, Multipart, Query};
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{IntoResponse, Json as JsonResponse, Response};
 use futures_util::stream::StreamExt;
 use http_body_util::BodyExt;
-use httpdate::fmt_http_date;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use tokio::fs::{self, File};
-use tokio::io::AsyncWriteExt;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 use crate::atomic::AtomicFile;
-use crate::config::{DEFAULT_LOCK_WAIT_TIMEOUT_SECS, MAX_CHUNK_SIZE, UPLOAD_TEMP_DIR};
+use crate::config::{
+    DEFAULT_LOCK_WAIT_TIMEOUT_SECS, MAX_CHUNK_SIZE, UPLOAD_BATCH_MANIFEST, UPLOAD_BATCH_MAX_FILES,
+    UPLOAD_TEMP_DIR,
+};
+use crate::content::{ContentReader, ContentWriter};
 use crate::error::ApiError;
-use crate::etag::{check_preconditions, etag_from_metadata};
+use crate::etag::check_preconditions;
+use crate::events::{ChangeEvent, ChangeKind, EventHub, normalize_path};
+use crate::files::{
+    ConflictPolicy, WriteResponse, lock_write_target, mtime_from_millis, written_headers,
+};
 use crate::locking::LockManager;
-use crate::storage::Storage;
+use crate::storage::{Storage, StorageError};
+use crate::volumes::{Volume, Volumes};
 
 #[derive(Debug)]
 pub struct UploadConfig {
@@ -38,31 +47,45 @@ pub struct UploadConfig {
 pub(crate) struct UploadInitRequest {
     name: String,
     total_size: u64,
+    /// 客户端文件的修改时间（Unix 毫秒），合并完成后应用到目标文件。
+    last_modified: Option<u64>,
+    /// 目标已存在时的处理方式，合并时生效。
+    #[serde(default)]
+    on_conflict: ConflictPolicy,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct UploadInitResponse {
-    upload_id: String,
+    pub(crate) upload_id: String,
 }
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct UploadMetadata {
-    name: String,
-    total_size: u64,
+    pub(crate) name: String,
+    pub(crate) total_size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) last_modified: Option<u64>,
+    #[serde(default)]
+    pub(crate) on_conflict: ConflictPolicy,
+    /// 通过文件请求链接创建的上传会话所属的链接令牌。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) link: Option<String>,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct UploadChunkQuery {
-    upload_id: String,
+    pub(crate) upload_id: String,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct UploadCompleteRequest {
     upload_id: String,
+    /// 覆盖 init 时指定的冲突处理方式。
+    on_conflict: Option<ConflictPolicy>,
 }
 
 #[derive(Deserialize)]
@@ -71,9 +94,90 @@ pub(crate) struct UploadAbortRequest {
     upload_id: String,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchInitRequest {
+    /// 目标目录，清单中的路径均相对于该目录。
+    #[serde(default)]
+    target: String,
+    #[serde(default)]
+    files: Vec<BatchFile>,
+    /// 需要一并创建的目录（含空目录）。
+    #[serde(default)]
+    directories: Vec<String>,
+    #[serde(default)]
+    on_conflict: ConflictPolicy,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchFile {
+    path: String,
+    size: u64,
+    /// 客户端文件的修改时间（Unix 毫秒）。
+    last_modified: Option<u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchInitResponse {
+    batch_id: String,
+    uploads: Vec<BatchUpload>,
+}
+
+/// 批次中单个文件的上传会话；`completed` 为合并后的对外路径。
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchUpload {
+    path: String,
+    upload_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BatchManifest {
+    uploads: Vec<BatchUpload>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchCompleteRequest {
+    batch_id: String,
+    /// 覆盖 init 时指定的冲突处理方式。
+    on_conflict: Option<ConflictPolicy>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct UploadedFiles {
+    files: Vec<UploadedFile>,
+}
+
+/// `name` 为清单中的相对路径或表单中的文件名，`path` 为最终写入的对外路径。
+#[derive(Serialize)]
+pub(crate) struct UploadedFile {
+    name: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchAbortRequest {
+    batch_id: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UploadFormQuery {
+    /// 目标目录，默认为根目录。
+    #[serde(default)]
+    path: String,
+    #[serde(default, rename = "onConflict")]
+    on_conflict: ConflictPolicy,
+}
+
 /// 初始化上传会话，写入元数据。
 pub async fn init_upload(
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
     Extension(upload): Extension<Arc<UploadConfig>>,
     Json(payload): Json<UploadInitRequest>,
 ) -> Result<JsonResponse<UploadInitResponse>, ApiError> {
@@ -85,63 +189,110 @@ pub async fn init_upload(
     if normalized_name.is_empty() {
         return Err(ApiError::BadRequest("name is required".into()));
     }
-    storage.resolve_path_checked(&normalized_name, true).await?;
-    if upload.max_total_size > 0 && payload.total_size > upload.max_total_size {
+    let upload_id = start_upload(
+        &volumes,
+        &upload,
+        UploadMetadata {
+            name: normalized_name,
+            total_size: payload.total_size,
+            last_modified: payload.last_modified,
+            on_conflict: payload.on_conflict,
+            link: None,
+        },
+    )
+    .await?;
+    Ok(JsonResponse(UploadInitResponse { upload_id }))
+}
+
+/// 校验大小、分片数、配额与并发限制后创建上传会话，返回上传 ID。
+pub(crate) async fn start_upload(
+    volumes: &Volumes,
+    upload: &UploadConfig,
+    metadata: UploadMetadata,
+) -> Result<String, ApiError> {
+    let (volume, relative) = volumes.resolve_writable(&metadata.name)?;
+    let storage = volume.storage();
+    let target = match storage.resolve_path_checked(relative, false).await {
+        Ok(target) => Some(target),
+        // 父目录尚不存在时在合并阶段创建。
+        Err(StorageError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+    if upload.max_total_size > 0 && metadata.total_size > upload.max_total_size {
         return Err(ApiError::BadRequest("upload size exceeds limit".into()));
     }
-    if payload.total_size > 0 && upload.max_chunks > 0 {
-        let expected_chunks = payload.total_size.div_ceil(MAX_CHUNK_SIZE);
+    if metadata
+        .last_modified
+        .is_some_and(|millis| mtime_from_millis(millis).is_none())
+    {
+        return Err(ApiError::BadRequest("lastModified is out of range".into()));
+    }
+    if metadata.total_size > 0 && upload.max_chunks > 0 {
+        let expected_chunks = metadata.total_size.div_ceil(MAX_CHUNK_SIZE);
         if expected_chunks > upload.max_chunks {
             return Err(ApiError::BadRequest(
                 "upload chunk count exceeds limit".into(),
             ));
         }
     }
+    if volume.quota().is_some() {
+        // 冲突重命名不会替换已有文件，因此不计入释放空间。
+        let released = match &target {
+            Some(target) if metadata.on_conflict == ConflictPolicy::Overwrite => target
+                .metadata()
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or(0),
+            _ => 0,
+        };
+        volume.check_quota(metadata.total_size, released).await?;
+    }
     if upload.max_concurrent > 0 {
-        let active = count_upload_temp_dirs(&storage).await?;
+        let active = count_upload_temp_dirs(volumes).await?;
         if active >= upload.max_concurrent {
             return Err(ApiError::TooManyRequests(60));
         }
     }
 
     let upload_id = Uuid::new_v4().to_string();
-    let temp_dir = upload_temp_root(&storage).join(&upload_id);
+    let temp_dir = upload_temp_root(storage).join(&upload_id);
     fs::create_dir_all(&temp_dir)
         .await
         .map_err(|err| ApiError::Internal(err.to_string()))?;
     info!(
         upload_id,
-        name = normalized_name,
-        total_size = payload.total_size,
+        name = metadata.name,
+        total_size = metadata.total_size,
         "init upload"
     );
-
-    let metadata = UploadMetadata {
-        name: normalized_name,
-        total_size: payload.total_size,
-    };
-    let meta_path = temp_dir.join("meta.json");
-    let meta_content =
-        serde_json::to_vec(&metadata).map_err(|err| ApiError::Internal(err.to_string()))?;
-    fs::write(meta_path, meta_content)
-        .await
-        .map_err(|err| ApiError::Internal(err.to_string()))?;
-
-    Ok(JsonResponse(UploadInitResponse { upload_id }))
+    write_json(&temp_dir.join("meta.json"), &metadata).await?;
+    Ok(upload_id)
 }
 
 /// 上传单个分片。
 pub async fn upload_chunk(
     Query(UploadChunkQuery { upload_id }): Query<UploadChunkQuery>,
     headers: HeaderMap,
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
     Extension(upload): Extension<Arc<UploadConfig>>,
     body: AxumBody,
+) -> Result<StatusCode, ApiError> {
+    store_chunk(&volumes, &upload, &upload_id, &headers, body, None).await
+}
+
+/// 保存分片；`link` 为文件请求令牌时只接受该链接创建的上传会话。
+pub(crate) async fn store_chunk(
+    volumes: &Volumes,
+    upload: &UploadConfig,
+    upload_id: &str,
+    headers: &HeaderMap,
+    body: AxumBody,
+    link: Option<&str>,
 ) -> Result<StatusCode, ApiError> {
     if upload_id.is_empty() {
         return Err(ApiError::BadRequest("upload_id is required".into()));
     }
-    if Uuid::parse_str(&upload_id).is_err() {
+    if Uuid::parse_str(upload_id).is_err() {
         return Err(ApiError::BadRequest("upload_id is invalid".into()));
     }
 
@@ -151,16 +302,24 @@ pub async fn upload_chunk(
         .and_then(|value| value.parse::<u64>().ok())
         .ok_or_else(|| ApiError::BadRequest("X-Chunk-Index is required".into()))?;
 
-    let temp_dir = upload_temp_root(&storage).join(&upload_id);
+    let (volume, temp_dir, _) = find_upload(volumes, upload_id).await?;
     let meta_path = temp_dir.join("meta.json");
     let meta_bytes = fs::read(&meta_path)
         .await
         .map_err(|_| ApiError::NotFound("upload_id not found".into()))?;
     let metadata: UploadMetadata =
         serde_json::from_slice(&meta_bytes).map_err(|err| ApiError::Internal(err.to_string()))?;
+    if link.is_some() && metadata.link.as_deref() != link {
+        return Err(ApiError::NotFound("upload_id not found".into()));
+    }
     if upload.max_total_size > 0 && metadata.total_size > upload.max_total_size {
         return Err(ApiError::BadRequest("upload size exceeds limit".into()));
     }
+    // 文件请求的上传大小在 init 时已确定，不接受超出声明大小的分片。
+    if metadata.link.is_some() && chunk_index >= metadata.total_size.div_ceil(MAX_CHUNK_SIZE).max(1)
+    {
+        return Err(ApiError::BadRequest("chunk index exceeds limit".into()));
+    }
     if upload.max_chunks > 0 {
         let max_index = upload.max_chunks.saturating_sub(1);
         if chunk_index > max_index {
@@ -169,9 +328,10 @@ pub async fn upload_chunk(
     }
 
     let chunk_path = temp_dir.join(format!("{chunk_index}.part"));
-    let mut file = File::create(&chunk_path)
+    let file = File::create(&chunk_path)
         .await
         .map_err(|err| ApiError::Internal(err.to_string()))?;
+    let mut writer = ContentWriter::new(file, volume.storage().keyring(), None);
 
     let mut data_stream = BodyExt::into_data_stream(body);
     let mut total_written: u64 = 0;
@@ -185,10 +345,15 @@ pub async fn upload_chunk(
             let _ = fs::remove_file(&chunk_path).await;
             return Err(ApiError::BadRequest("chunk too large".into()));
         }
-        file.write_all(&chunk)
+        writer
+            .write_all(&chunk)
             .await
             .map_err(|err| ApiError::Internal(err.to_string()))?;
     }
+    writer
+        .finish()
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
 
     debug!(
         upload_id,
@@ -202,19 +367,62 @@ pub async fn upload_chunk(
 /// 合并分片并原子替换目标文件。
 pub async fn complete_upload(
     headers: HeaderMap,
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
     Extension(lock_manager): Extension<Arc<LockManager>>,
     Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(hub): Extension<Arc<EventHub>>,
     Json(payload): Json<UploadCompleteRequest>,
 ) -> Result<Response, ApiError> {
-    if payload.upload_id.trim().is_empty() {
+    let pending = open_upload(&volumes, &upload, &payload.upload_id, None).await?;
+    let policy = payload.on_conflict.unwrap_or(pending.metadata.on_conflict);
+    let (path, response_headers) =
+        finish_upload(&volumes, &lock_manager, &hub, &headers, pending, policy).await?;
+    Ok((
+        StatusCode::CREATED,
+        response_headers,
+        JsonResponse(WriteResponse { path }),
+    )
+        .into_response())
+}
+
+/// 已上传分片、等待合并的上传会话。
+pub(crate) struct PendingUpload {
+    upload_id: String,
+    temp_dir: PathBuf,
+    pub(crate) metadata: UploadMetadata,
+    parts: Vec<(u64, PathBuf)>,
+}
+
+/// 查找并读取待合并的上传会话；`link` 为文件请求令牌时只接受该链接创建的会话。
+pub(crate) async fn open_upload(
+    volumes: &Volumes,
+    upload: &UploadConfig,
+    upload_id: &str,
+    link: Option<&str>,
+) -> Result<PendingUpload, ApiError> {
+    if upload_id.trim().is_empty() {
         return Err(ApiError::BadRequest("upload_id is required".into()));
     }
-    if Uuid::parse_str(&payload.upload_id).is_err() {
+    if Uuid::parse_str(upload_id).is_err() {
         return Err(ApiError::BadRequest("upload_id is invalid".into()));
     }
+    let (_, temp_dir, batched) = find_upload(volumes, upload_id).await?;
+    if batched {
+        return Err(ApiError::Conflict("upload belongs to a batch".into()));
+    }
+    let pending = load_upload(upload, upload_id, temp_dir).await?;
+    if link.is_some() && pending.metadata.link.as_deref() != link {
+        return Err(ApiError::NotFound("upload_id not found".into()));
+    }
+    Ok(pending)
+}
 
-    let temp_dir = upload_temp_root(&storage).join(&payload.upload_id);
+/// 读取上传会话的元数据与分片列表，并校验分片连续且未超出限制。
+async fn load_upload(
+    upload: &UploadConfig,
+    upload_id: &str,
+    temp_dir: PathBuf,
+) -> Result<PendingUpload, ApiError> {
     let meta_path = temp_dir.join("meta.json");
     let meta_bytes = fs::read(&meta_path)
         .await
@@ -263,7 +471,7 @@ pub async fn complete_upload(
         let expected_index = expected_index as u64;
         if *index != expected_index {
             warn!(
-                upload_id = payload.upload_id,
+                upload_id,
                 expected = expected_index,
                 got = *index,
                 "missing chunk"
@@ -272,37 +480,58 @@ pub async fn complete_upload(
         }
     }
 
-    let _guard = lock_manager
-        .lock_path_with_timeout(
-            &metadata.name,
-            std::time::Duration::from_secs(DEFAULT_LOCK_WAIT_TIMEOUT_SECS),
-        )
-        .await
-        .map_err(|_| ApiError::Conflict("path locked".into()))?;
-    let target = storage.resolve_path_checked(&metadata.name, true).await?;
-    let existing = match fs::metadata(&target).await {
+    Ok(PendingUpload {
+        upload_id: upload_id.to_string(),
+        temp_dir,
+        metadata,
+        parts,
+    })
+}
+
+/// 将分片合并写入目标文件并删除临时目录，返回最终的对外路径与写入响应头。
+pub(crate) async fn finish_upload(
+    volumes: &Volumes,
+    lock_manager: &LockManager,
+    hub: &EventHub,
+    headers: &HeaderMap,
+    pending: PendingUpload,
+    policy: ConflictPolicy,
+) -> Result<(String, HeaderMap), ApiError> {
+    let PendingUpload {
+        upload_id,
+        temp_dir,
+        metadata,
+        parts,
+    } = pending;
+    let locked = lock_write_target(volumes, lock_manager, &metadata.name, policy).await?;
+    let (volume, relative) = volumes.resolve_writable(&locked.path)?;
+    let storage = volume.storage();
+    let target = storage.resolve_path_checked(relative, true).await?;
+    let existing = match target.metadata().await {
         Ok(metadata) => Some(metadata),
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
         Err(err) => return Err(ApiError::Internal(err.to_string())),
     };
     let exists = existing.is_some();
-    let etag = existing.as_ref().map(etag_from_metadata);
-    check_preconditions(&headers, etag.as_deref(), exists)?;
+    let etag = match existing.as_ref() {
+        Some(metadata) => Some(storage.content_etag(&target, metadata).await),
+        None => None,
+    };
+    check_preconditions(headers, etag.as_deref(), exists)?;
 
-    if let Some(parent) = target.parent() {
-        fs::create_dir_all(parent)
-            .await
-            .map_err(|err| ApiError::Internal(err.to_string()))?;
+    let mut atomic = AtomicFile::new(storage, &target).await?.with_volume(volume);
+    if let Some(modified) = metadata.last_modified.and_then(mtime_from_millis) {
+        atomic = atomic.with_modified(modified);
     }
-
-    let mut atomic = AtomicFile::new(&target).await?;
     let write_result: Result<u64, ApiError> = async {
         let mut total_written: u64 = 0;
         for (_, path) in &parts {
-            let mut part_file = File::open(path)
+            let mut part = ContentReader::open(path, storage.content_format())
                 .await
                 .map_err(|err| ApiError::Internal(err.to_string()))?;
-            let copied = tokio::io::copy(&mut part_file, atomic.file_mut())
+            let copied = atomic
+                .writer_mut()
+                .copy_from(&mut part)
                 .await
                 .map_err(|err| ApiError::Internal(err.to_string()))?;
             total_written += copied;
@@ -318,9 +547,11 @@ pub async fn complete_upload(
         }
     };
 
-    if metadata.total_size > 0 && total_written != metadata.total_size {
+    // 文件请求按声明的大小计入链接限额，因此空文件同样要求大小一致。
+    if (metadata.total_size > 0 || metadata.link.is_some()) && total_written != metadata.total_size
+    {
         warn!(
-            upload_id = payload.upload_id,
+            upload_id,
             expected = metadata.total_size,
             actual = total_written,
             "size mismatch after merge"
@@ -329,63 +560,636 @@ pub async fn complete_upload(
         return Err(ApiError::BadRequest("size mismatch".into()));
     }
     atomic.finalize().await?;
+    let kind = if exists {
+        ChangeKind::Modified
+    } else {
+        ChangeKind::Created
+    };
+    hub.record_local(
+        volume,
+        ChangeEvent::new(kind, normalize_path(&locked.path), false),
+    );
 
     fs::remove_dir_all(&temp_dir)
         .await
         .map_err(|err| ApiError::Internal(err.to_string()))?;
 
     info!(
-        upload_id = payload.upload_id,
-        name = metadata.name,
+        upload_id,
+        name = locked.path,
         total_size = metadata.total_size,
         "upload complete"
     );
-    let metadata = fs::metadata(&target)
-        .await
-        .map_err(|err| ApiError::Internal(err.to_string()))?;
-    let mut response_headers = HeaderMap::new();
-    let etag = etag_from_metadata(&metadata);
-    response_headers.insert(
-        header::ETAG,
-        HeaderValue::from_str(&etag).map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
-    );
-    if let Ok(modified) = metadata.modified() {
-        let value = fmt_http_date(modified);
-        response_headers.insert(
-            header::LAST_MODIFIED,
-            HeaderValue::from_str(&value)
-                .map_err(|_| ApiError::Internal("响应头构建失败".into()))?,
-        );
-    }
-    Ok((StatusCode::CREATED, response_headers).into_response())
+    let response_headers = written_headers(storage, &target).await?;
+    Ok((
+        volumes.public_path(volume, relative.trim_matches('/')),
+        response_headers,
+    ))
 }
 
 /// 中止上传并清理临时目录。
 pub async fn abort_upload(
-    Extension(storage): Extension<Arc<Storage>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
     Json(payload): Json<UploadAbortRequest>,
 ) -> Result<StatusCode, ApiError> {
-    if payload.upload_id.trim().is_empty() {
+    discard_upload(&volumes, &payload.upload_id, None).await
+}
+
+/// 删除上传会话的临时目录；`link` 为文件请求令牌时只接受该链接创建的会话。
+pub(crate) async fn discard_upload(
+    volumes: &Volumes,
+    upload_id: &str,
+    link: Option<&str>,
+) -> Result<StatusCode, ApiError> {
+    if upload_id.trim().is_empty() {
         return Err(ApiError::BadRequest("upload_id is required".into()));
     }
-    if Uuid::parse_str(&payload.upload_id).is_err() {
+    if Uuid::parse_str(upload_id).is_err() {
         return Err(ApiError::BadRequest("upload_id is invalid".into()));
     }
 
-    let temp_dir = upload_temp_root(&storage).join(&payload.upload_id);
-    if fs::metadata(&temp_dir).await.is_err() {
-        return Err(ApiError::NotFound("upload_id not found".into()));
+    let (_, temp_dir, batched) = find_upload(volumes, upload_id).await?;
+    if batched {
+        return Err(ApiError::Conflict("upload belongs to a batch".into()));
+    }
+    if let Some(link) = link {
+        let meta_bytes = fs::read(temp_dir.join("meta.json"))
+            .await
+            .map_err(|_| ApiError::NotFound("upload_id not found".into()))?;
+        let metadata: UploadMetadata = serde_json::from_slice(&meta_bytes)
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        if metadata.link.as_deref() != Some(link) {
+            return Err(ApiError::NotFound("upload_id not found".into()));
+        }
     }
     fs::remove_dir_all(&temp_dir)
         .await
         .map_err(|err| ApiError::Internal(err.to_string()))?;
 
-    info!(upload_id = payload.upload_id, "upload aborted");
+    info!(upload_id, "upload aborted");
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// 初始化批量上传：按清单在目标目录下创建目录结构，并为每个文件创建上传会话。
+pub async fn init_batch_upload(
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Json(payload): Json<BatchInitRequest>,
+) -> Result<JsonResponse<BatchInitResponse>, ApiError> {
+    if payload.files.is_empty() && payload.directories.is_empty() {
+        return Err(ApiError::BadRequest("manifest is empty".into()));
+    }
+    if payload.files.len() > UPLOAD_BATCH_MAX_FILES {
+        return Err(ApiError::BadRequest("too many files in manifest".into()));
+    }
+    let target = payload.target.trim().trim_matches(['/', '\\']);
+    let (volume, base) = volumes.resolve_writable(target)?;
+    let base = base.trim_matches('/');
+    let storage = volume.storage();
+
+    // 先完成全部校验，再创建任何目录或会话。
+    let mut directories = BTreeSet::new();
+    let mut add_with_parents = |relative: &str| {
+        let mut current = String::new();
+        for segment in relative.split('/') {
+            if !current.is_empty() {
+                current.push('/');
+            }
+            current.push_str(segment);
+            directories.insert(current.clone());
+        }
+    };
+    if !base.is_empty() {
+        add_with_parents(base);
+    }
+    for directory in &payload.directories {
+        add_with_parents(&join_relative(base, &normalize_manifest_path(directory)?));
+    }
+    let mut files = Vec::with_capacity(payload.files.len());
+    let mut seen = HashSet::new();
+    let mut total_size: u64 = 0;
+    for file in &payload.files {
+        let path = normalize_manifest_path(&file.path)?;
+        if !seen.insert(path.clone()) {
+            return Err(ApiError::BadRequest(format!(
+                "duplicate path in manifest: {path}"
+            )));
+        }
+        let relative = join_relative(base, &path);
+        if let Some((parent, _)) = relative.rsplit_once('/') {
+            add_with_parents(parent);
+        }
+        if file
+            .last_modified
+            .is_some_and(|millis| mtime_from_millis(millis).is_none())
+        {
+            return Err(ApiError::BadRequest(format!(
+                "lastModified is out of range: {path}"
+            )));
+        }
+        if upload.max_chunks > 0 && file.size.div_ceil(MAX_CHUNK_SIZE) > upload.max_chunks {
+            return Err(ApiError::BadRequest(
+                "upload chunk count exceeds limit".into(),
+            ));
+        }
+        total_size = total_size
+            .checked_add(file.size)
+            .ok_or_else(|| ApiError::BadRequest("upload size exceeds limit".into()))?;
+        files.push((path, relative, file));
+    }
+    if upload.max_total_size > 0 && total_size > upload.max_total_size {
+        return Err(ApiError::BadRequest("upload size exceeds limit".into()));
+    }
+    let mut released = 0;
+    for (path, relative, _) in &files {
+        if directories.contains(relative) {
+            return Err(ApiError::BadRequest(format!(
+                "path is both a file and a directory: {path}"
+            )));
+        }
+        let existing = match storage.resolve_path_checked(relative, false).await {
+            Ok(resolved) => resolved.metadata().await.ok(),
+            Err(StorageError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err.into()),
+        };
+        match existing {
+            Some(metadata) if metadata.is_dir() => {
+                return Err(ApiError::Conflict(format!("target is a directory: {path}")));
+            }
+            // 冲突重命名不会替换已有文件，因此不计入释放空间。
+            Some(metadata) if payload.on_conflict == ConflictPolicy::Overwrite => {
+                released += metadata.len();
+            }
+            _ => {}
+        }
+    }
+    if volume.quota().is_some() {
+        volume.check_quota(total_size, released).await?;
+    }
+    // 整个批次只占用一个上传并发名额。
+    if upload.max_concurrent > 0 {
+        let active = count_upload_temp_dirs(&volumes).await?;
+        if active >= upload.max_concurrent {
+            return Err(ApiError::TooManyRequests(60));
+        }
+    }
+
+    let created = create_directories(storage, &directories).await?;
+    let batch_id = Uuid::new_v4().to_string();
+    let batch_dir = upload_temp_root(storage).join(&batch_id);
+    let result: Result<Vec<BatchUpload>, ApiError> = async {
+        let mut uploads = Vec::with_capacity(files.len());
+        for (path, relative, file) in &files {
+            let upload_id = Uuid::new_v4().to_string();
+            let temp_dir = batch_dir.join(&upload_id);
+            fs::create_dir_all(&temp_dir)
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+            let metadata = UploadMetadata {
+                name: volumes.public_path(volume, relative),
+                total_size: file.size,
+                last_modified: file.last_modified,
+                on_conflict: payload.on_conflict,
+                link: None,
+            };
+            write_json(&temp_dir.join("meta.json"), &metadata).await?;
+            uploads.push(BatchUpload {
+                path: path.clone(),
+                upload_id,
+                completed: None,
+            });
+        }
+        let manifest = BatchManifest {
+            uploads: uploads.clone(),
+        };
+        write_json(&batch_dir.join(UPLOAD_BATCH_MANIFEST), &manifest).await?;
+        Ok(uploads)
+    }
+    .await;
+    let uploads = match result {
+        Ok(uploads) => uploads,
+        Err(err) => {
+            let _ = fs::remove_dir_all(&batch_dir).await;
+            remove_directories(storage, &created).await;
+            return Err(err);
+        }
+    };
+
+    for relative in &created {
+        hub.record_local(
+            volume,
+            ChangeEvent::new(
+                ChangeKind::Created,
+                normalize_path(&volumes.public_path(volume, relative)),
+                true,
+            ),
+        );
+    }
+    info!(
+        batch_id,
+        target,
+        files = uploads.len(),
+        directories = created.len(),
+        total_size,
+        "init batch upload"
+    );
+    Ok(JsonResponse(BatchInitResponse { batch_id, uploads }))
+}
+
+/// 合并批量上传中的全部文件；任一文件分片不完整时不合并任何文件。
+pub async fn complete_batch_upload(
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Json(payload): Json<BatchCompleteRequest>,
+) -> Result<Response, ApiError> {
+    if Uuid::parse_str(&payload.batch_id).is_err() {
+        return Err(ApiError::BadRequest("batch_id is invalid".into()));
+    }
+    // 同一批次的完成请求串行执行，避免重复合并；键含 NUL，不会与文件路径冲突。
+    let _guard = lock_manager
+        .lock_path_with_timeout(
+            &format!("\0upload-batch/{}", payload.batch_id),
+            Duration::from_secs(DEFAULT_LOCK_WAIT_TIMEOUT_SECS),
+        )
+        .await
+        .map_err(|_| ApiError::Conflict("batch locked".into()))?;
+    let batch_dir = find_batch(&volumes, &payload.batch_id).await?;
+    let manifest_path = batch_dir.join(UPLOAD_BATCH_MANIFEST);
+    let manifest_bytes = fs::read(&manifest_path)
+        .await
+        .map_err(|_| ApiError::NotFound("batch_id not found".into()))?;
+    let mut manifest: BatchManifest = serde_json::from_slice(&manifest_bytes)
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+
+    let mut pending = Vec::new();
+    for (index, entry) in manifest.uploads.iter().enumerate() {
+        if entry.completed.is_some() {
+            continue;
+        }
+        let temp_dir = batch_dir.join(&entry.upload_id);
+        pending.push((
+            index,
+            load_upload(&upload, &entry.upload_id, temp_dir).await?,
+        ));
+    }
+    // 已完成的文件写回清单，失败后重试时只合并剩余文件。
+    for (index, pending) in pending {
+        let policy = payload.on_conflict.unwrap_or(pending.metadata.on_conflict);
+        let (path, _) = finish_upload(
+            &volumes,
+            &lock_manager,
+            &hub,
+            &HeaderMap::new(),
+            pending,
+            policy,
+        )
+        .await?;
+        manifest.uploads[index].completed = Some(path);
+        write_json(&manifest_path, &manifest).await?;
+    }
+
+    fs::remove_dir_all(&batch_dir)
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+    info!(
+        batch_id = payload.batch_id,
+        files = manifest.uploads.len(),
+        "batch upload complete"
+    );
+    let files = manifest
+        .uploads
+        .into_iter()
+        .map(|entry| UploadedFile {
+            name: entry.path,
+            path: entry.completed.unwrap_or_default(),
+        })
+        .collect();
+    Ok((StatusCode::CREATED, JsonResponse(UploadedFiles { files })).into_response())
+}
+
+/// 中止批量上传并清理其全部上传会话；已创建的目录保留。
+pub async fn abort_batch_upload(
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Json(payload): Json<BatchAbortRequest>,
+) -> Result<StatusCode, ApiError> {
+    if Uuid::parse_str(&payload.batch_id).is_err() {
+        return Err(ApiError::BadRequest("batch_id is invalid".into()));
+    }
+    let batch_dir = find_batch(&volumes, &payload.batch_id).await?;
+    fs::remove_dir_all(&batch_dir)
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+
+    info!(batch_id = payload.batch_id, "batch upload aborted");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 接收 `multipart/form-data` 表单上传，每个文件部分直接流式写入目标目录下的原子文件。
+///
+/// 目标目录与冲突策略取自查询参数，也可由位于文件部分之前的 `path`、`onConflict`
+/// 文本字段覆盖；整个表单的文件总大小受上传大小上限约束。
+pub async fn upload_form(
+    Query(query): Query<UploadFormQuery>,
+    headers: HeaderMap,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(lock_manager): Extension<Arc<LockManager>>,
+    Extension(upload): Extension<Arc<UploadConfig>>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    mut multipart: Multipart,
+) -> Result<Response, ApiError> {
+    let mut target = query.path;
+    let mut on_conflict = query.on_conflict;
+    let mut received: u64 = 0;
+    let mut files = Vec::new();
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        let Some(file_name) = field.file_name().map(str::to_string) else {
+            let name = field.name().unwrap_or_default().to_string();
+            let value = field.text().await.map_err(multipart_error)?;
+            match name.as_str() {
+                "path" => target = value,
+                "onConflict" => {
+                    on_conflict = match value.trim() {
+                        "overwrite" => ConflictPolicy::Overwrite,
+                        "rename" => ConflictPolicy::Rename,
+                        _ => return Err(ApiError::BadRequest("onConflict is invalid".into())),
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        };
+        // 表单未选择文件时浏览器仍会提交文件名为空的部分。
+        if file_name.is_empty() {
+            continue;
+        }
+        let name = form_file_name(&file_name)?;
+        let path = join_relative(target.trim().trim_matches(['/', '\\']), name);
+        let limit =
+            (upload.max_total_size > 0).then(|| upload.max_total_size.saturating_sub(received));
+        let (path, written) = store_form_file(
+            &volumes,
+            &lock_manager,
+            &hub,
+            &headers,
+            &path,
+            on_conflict,
+            &mut field,
+            limit,
+            async |_| Ok(()),
+        )
+        .await?;
+        received += written;
+        files.push(UploadedFile {
+            name: name.to_string(),
+            path,
+        });
+    }
+
+    if files.is_empty() {
+        return Err(ApiError::BadRequest("no files in form".into()));
+    }
+    Ok((StatusCode::CREATED, JsonResponse(UploadedFiles { files })).into_response())
+}
+
+/// 取表单文件名的最后一段作为写入的文件名。
+pub(crate) fn form_file_name(file_name: &str) -> Result<&str, ApiError> {
+    let name = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(ApiError::BadRequest(format!(
+            "invalid file name: {file_name}"
+        )));
+    }
+    Ok(name)
+}
+
+/// 将表单中的一个文件流式写入 `path`，返回实际写入的对外路径与字节数。
+///
+/// `limit` 为该文件允许的最大字节数；`commit` 在内容写完、替换目标前以写入字节数
+/// 调用，返回错误时放弃本次写入。
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn store_form_file(
+    volumes: &Volumes,
+    lock_manager: &LockManager,
+    hub: &EventHub,
+    headers: &HeaderMap,
+    path: &str,
+    policy: ConflictPolicy,
+    field: &mut Field<'_>,
+    limit: Option<u64>,
+    commit: impl AsyncFnOnce(u64) -> Result<(), ApiError>,
+) -> Result<(String, u64), ApiError> {
+    let locked = lock_write_target(volumes, lock_manager, path, policy).await?;
+    let (volume, relative) = volumes.resolve_writable(&locked.path)?;
+    let storage = volume.storage();
+    let resolved = storage.resolve_path_checked(relative, true).await?;
+    let existing = match resolved.metadata().await {
+        Ok(metadata) => Some(metadata),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(ApiError::Internal(err.to_string())),
+    };
+    let exists = existing.is_some();
+    let etag = match existing.as_ref() {
+        Some(metadata) => Some(storage.content_etag(&resolved, metadata).await),
+        None => None,
+    };
+    check_preconditions(headers, etag.as_deref(), exists)?;
+
+    let mut atomic = AtomicFile::new(storage, &resolved)
+        .await?
+        .with_volume(volume);
+    let write_result: Result<u64, ApiError> = async {
+        let mut written: u64 = 0;
+        while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+            written += chunk.len() as u64;
+            if limit.is_some_and(|limit| written > limit) {
+                return Err(ApiError::BadRequest("upload size exceeds limit".into()));
+            }
+            atomic
+                .writer_mut()
+                .write_all(&chunk)
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+        }
+        Ok(written)
+    }
+    .await;
+    let written = match write_result {
+        Ok(written) => written,
+        Err(err) => {
+            atomic.cleanup().await;
+            return Err(err);
+        }
+    };
+    if let Err(err) = commit(written).await {
+        atomic.cleanup().await;
+        return Err(err);
+    }
+    atomic.finalize().await?;
+    let kind = if exists {
+        ChangeKind::Modified
+    } else {
+        ChangeKind::Created
+    };
+    hub.record_local(
+        volume,
+        ChangeEvent::new(kind, normalize_path(&locked.path), false),
+    );
+    info!(path = locked.path, "form upload");
+    Ok((
+        volumes.public_path(volume, relative.trim_matches('/')),
+        written,
+    ))
+}
+
+pub(crate) fn multipart_error(err: MultipartError) -> ApiError {
+    ApiError::BadRequest(err.body_text())
+}
+
+/// 规范化清单中的相对路径，拒绝空段、`.` 与 `..`。
+fn normalize_manifest_path(path: &str) -> Result<String, ApiError> {
+    let segments: Vec<&str> = path
+        .trim()
+        .trim_matches(['/', '\\'])
+        .split(['/', '\\'])
+        .collect();
+    if segments
+        .iter()
+        .any(|segment| segment.is_empty() || *segment == "." || *segment == "..")
+    {
+        return Err(ApiError::BadRequest(format!(
+            "invalid path in manifest: {path}"
+        )));
+    }
+    Ok(segments.join("/"))
+}
+
+pub(crate) fn join_relative(base: &str, path: &str) -> String {
+    if base.is_empty() {
+        path.to_string()
+    } else {
+        format!("{base}/{path}")
+    }
+}
+
+/// 按父目录优先的顺序创建缺失的目录，返回新建的目录；失败时删除本次已创建的目录。
+async fn create_directories(
+    storage: &Storage,
+    directories: &BTreeSet<String>,
+) -> Result<Vec<String>, ApiError> {
+    let mut created = Vec::new();
+    for relative in directories {
+        let result: Result<bool, ApiError> = async {
+            let existing = match storage.resolve_path_checked(relative, false).await {
+                Ok(resolved) => match resolved.metadata().await {
+                    Ok(metadata) => Some(metadata),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                    Err(err) => return Err(ApiError::Internal(err.to_string())),
+                },
+                Err(StorageError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err.into()),
+            };
+            match existing {
+                Some(metadata) if metadata.is_dir() => Ok(false),
+                Some(_) => Err(ApiError::Conflict(format!(
+                    "path exists and is not a directory: {relative}"
+                ))),
+                None => {
+                    storage.create_dir(relative).await?;
+                    Ok(true)
+                }
+            }
+        }
+        .await;
+        match result {
+            Ok(true) => created.push(relative.clone()),
+            Ok(false) => {}
+            Err(err) => {
+                remove_directories(storage, &created).await;
+                return Err(err);
+            }
+        }
+    }
+    Ok(created)
+}
+
+/// 逆序删除本次创建的目录（仅删除空目录）。
+async fn remove_directories(storage: &Storage, created: &[String]) {
+    for relative in created.iter().rev() {
+        let Ok(resolved) = storage.resolve_link_checked(relative).await else {
+            continue;
+        };
+        let Some(name) = resolved.name() else {
+            continue;
+        };
+        if let Err(err) = resolved.dir().remove_dir(name).await {
+            warn!(path = relative, error = %err, "failed to roll back batch directory");
+        }
+    }
+}
+
+async fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), ApiError> {
+    let content = serde_json::to_vec(value).map_err(|err| ApiError::Internal(err.to_string()))?;
+    fs::write(path, content)
+        .await
+        .map_err(|err| ApiError::Internal(err.to_string()))
+}
+
+/// 在各卷的上传临时目录中查找上传会话，返回所属卷、临时目录以及会话是否属于批量上传。
+async fn find_upload<'a>(
+    volumes: &'a Volumes,
+    upload_id: &str,
+) -> Result<(&'a Arc<Volume>, PathBuf, bool), ApiError> {
+    for volume in volumes.iter() {
+        let temp_root = upload_temp_root(volume.storage());
+        let temp_dir = temp_root.join(upload_id);
+        if fs::metadata(&temp_dir).await.is_ok() {
+            return Ok((volume, temp_dir, false));
+        }
+        // 批量上传的会话位于批次目录下；批次目录数量受并发上限约束。
+        let Ok(mut dir) = fs::read_dir(&temp_root).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            let batch_dir = entry.path();
+            let temp_dir = batch_dir.join(upload_id);
+            if fs::metadata(batch_dir.join(UPLOAD_BATCH_MANIFEST))
+                .await
+                .is_ok()
+                && fs::metadata(&temp_dir).await.is_ok()
+            {
+                return Ok((volume, temp_dir, true));
+            }
+        }
+    }
+    Err(ApiError::NotFound("upload_id not found".into()))
+}
+
+/// 在各卷的上传临时目录中查找批量上传目录。
+async fn find_batch(volumes: &Volumes, batch_id: &str) -> Result<PathBuf, ApiError> {
+    for volume in volumes.iter() {
+        let batch_dir = upload_temp_root(volume.storage()).join(batch_id);
+        if fs::metadata(batch_dir.join(UPLOAD_BATCH_MANIFEST))
+            .await
+            .is_ok()
+        {
+            return Ok(batch_dir);
+        }
+    }
+    Err(ApiError::NotFound("batch_id not found".into()))
+}
+
 /// 返回上传临时目录的根路径。
 pub fn upload_temp_root(storage: &Storage) -> PathBuf {
+    if let Some(temp_dir) = storage.temp_dir() {
+        return temp_dir.to_path_buf();
+    }
     let temp_path = Path::new(UPLOAD_TEMP_DIR);
     if temp_path.is_absolute() {
         return temp_path.to_path_buf();
@@ -409,32 +1213,67 @@ pub fn upload_temp_root(storage: &Storage) -> PathBuf {
     parent.join(temp_path)
 }
 
-/// 统计当前活跃的上传临时目录数量。
-pub async fn count_upload_temp_dirs(storage: &Storage) -> Result<u64, ApiError> {
-    let temp_root = upload_temp_root(storage);
-    if fs::metadata(&temp_root).await.is_err() {
-        return Ok(0);
-    }
-    let mut dir = fs::read_dir(&temp_root)
-        .await
-        .map_err(|err| ApiError::Internal(err.to_string()))?;
+/// 统计全部卷当前活跃的上传临时目录数量。
+pub async fn count_upload_temp_dirs(volumes: &Volumes) -> Result<u64, ApiError> {
     let mut count = 0;
-    while let Some(entry) = dir
-        .next_entry()
-        .await
-        .map_err(|err| ApiError::Internal(err.to_string()))?
-    {
-        let metadata = entry
-            .metadata()
+    for volume in volumes.iter() {
+        let temp_root = upload_temp_root(volume.storage());
+        if fs::metadata(&temp_root).await.is_err() {
+            continue;
+        }
+        let mut dir = fs::read_dir(&temp_root)
             .await
             .map_err(|err| ApiError::Internal(err.to_string()))?;
-        if metadata.is_dir() {
-            count += 1;
+        while let Some(entry) = dir
+            .next_entry()
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?
+        {
+            let metadata = entry
+                .metadata()
+                .await
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+            if metadata.is_dir() {
+                count += 1;
+            }
         }
     }
     Ok(count)
 }
 
+/// 统计文件请求链接尚未合并的上传会话，返回会话数与声明的总字节数。
+///
+/// 会话随中止或过期清理一并删除，因此预留的额度无需单独归还。
+pub(crate) async fn pending_link_uploads(
+    volumes: &Volumes,
+    token: &str,
+) -> Result<(u64, u64), ApiError> {
+    let mut count = 0;
+    let mut bytes: u64 = 0;
+    for volume in volumes.iter() {
+        let Ok(mut dir) = fs::read_dir(upload_temp_root(volume.storage())).await else {
+            continue;
+        };
+        while let Some(entry) = dir
+            .next_entry()
+            .await
+            .map_err(|err| ApiError::Internal(err.to_string()))?
+        {
+            let Ok(content) = fs::read(entry.path().join("meta.json")).await else {
+                continue;
+            };
+            let Ok(metadata) = serde_json::from_slice::<UploadMetadata>(&content) else {
+                continue;
+            };
+            if metadata.link.as_deref() == Some(token) {
+                count += 1;
+                bytes = bytes.saturating_add(metadata.total_size);
+            }
+        }
+    }
+    Ok((count, bytes))
+}
+
 /// 清理过期的上传临时目录。
 pub async fn cleanup_upload_temp(
     storage: &Storage,
@@ -456,10 +1295,22 @@ pub async fn cleanup_upload_temp(
         if !metadata.is_dir() {
             continue;
         }
-        let modified = match metadata.modified() {
+        let mut modified = match metadata.modified() {
             Ok(value) => value,
             Err(_) => continue,
         };
+        // 批次目录本身不随分片写入更新，以其中最近活跃的上传会话为准。
+        if fs::metadata(entry.path().join(UPLOAD_BATCH_MANIFEST))
+            .await
+            .is_ok()
+        {
+            let mut members = fs::read_dir(entry.path()).await?;
+            while let Some(member) = members.next_entry().await? {
+                if let Ok(member_modified) = member.metadata().await.and_then(|m| m.modified()) {
+                    modified = modified.max(member_modified);
+                }
+            }
+        }
         let age = match now.duration_since(modified) {
             Ok(value) => value,
             Err(_) => continue,
@@ -482,23 +1333,26 @@ mod tests {
     use super::*;
     use axum::Json;
     use axum::extract::{Extension, Query};
-    use axum::http::{HeaderMap, HeaderValue};
+    use axum::http::{HeaderMap, HeaderValue, header};
     use std::sync::Arc;
-    use std::time::Duration;
+    use std::time::UNIX_EPOCH;
     use tempfile::tempdir;
     use tokio::fs;
 
     use crate::config::{
         DEFAULT_UPLOAD_MAX_CHUNKS, DEFAULT_UPLOAD_MAX_CONCURRENT, DEFAULT_UPLOAD_MAX_SIZE,
-        DEFAULT_UPLOAD_TEMP_TTL_SECS,
+        DEFAULT_UPLOAD_TEMP_TTL_SECS, VOLUME_UPLOAD_TEMP_DIR,
     };
     use crate::locking::LockManager;
 
-    fn make_storage() -> (tempfile::TempDir, Arc<Storage>) {
+    fn make_volumes() -> (tempfile::TempDir, Arc<Volumes>) {
         let temp = tempdir().expect("tempdir");
         let root = temp.path().join("storage");
         std::fs::create_dir_all(&root).expect("create storage root");
-        (temp, Arc::new(Storage::new(root)))
+        (
+            temp,
+            Arc::new(Volumes::single(Arc::new(Storage::new(root)))),
+        )
     }
 
     fn make_upload_config() -> Arc<UploadConfig> {
@@ -512,14 +1366,16 @@ mod tests {
 
     #[tokio::test]
     async fn init_upload_rejects_traversal_path() {
-        let (_temp, storage) = make_storage();
+        let (_temp, volumes) = make_volumes();
         let upload = make_upload_config();
         let result = init_upload(
-            Extension(storage),
+            Extension(volumes),
             Extension(upload),
             Json(UploadInitRequest {
                 name: "../secret.txt".to_string(),
                 total_size: 1,
+                last_modified: None,
+                on_conflict: ConflictPolicy::Overwrite,
             }),
         )
         .await;
@@ -529,15 +1385,17 @@ mod tests {
 
     #[tokio::test]
     async fn upload_flow_missing_chunk_returns_error() {
-        let (_temp, storage) = make_storage();
+        let (_temp, volumes) = make_volumes();
         let upload = make_upload_config();
         let lock_manager = Arc::new(LockManager::new());
         let JsonResponse(init) = init_upload(
-            Extension(storage.clone()),
+            Extension(volumes.clone()),
             Extension(upload.clone()),
             Json(UploadInitRequest {
                 name: "file.bin".to_string(),
                 total_size: 3,
+                last_modified: None,
+                on_conflict: ConflictPolicy::Overwrite,
             }),
         )
         .await
@@ -550,7 +1408,7 @@ mod tests {
                 upload_id: init.upload_id.clone(),
             }),
             headers,
-            Extension(storage.clone()),
+            Extension(volumes.clone()),
             Extension(upload.clone()),
             AxumBody::from("abc"),
         )
@@ -559,11 +1417,13 @@ mod tests {
 
         let result = complete_upload(
             HeaderMap::new(),
-            Extension(storage),
+            Extension(volumes),
             Extension(lock_manager),
             Extension(upload.clone()),
+            Extension(Arc::new(EventHub::new())),
             Json(UploadCompleteRequest {
                 upload_id: init.upload_id,
+                on_conflict: None,
             }),
         )
         .await;
@@ -573,15 +1433,29 @@ mod tests {
 
     #[tokio::test]
     async fn upload_flow_success_cleans_temp_dir() {
-        let (temp, storage) = make_storage();
+        let (temp, volumes) = make_volumes();
         let upload = make_upload_config();
         let lock_manager = Arc::new(LockManager::new());
+        let out_of_range = init_upload(
+            Extension(volumes.clone()),
+            Extension(upload.clone()),
+            Json(UploadInitRequest {
+                name: "file.bin".to_string(),
+                total_size: 3,
+                last_modified: Some(u64::MAX),
+                on_conflict: ConflictPolicy::Overwrite,
+            }),
+        )
+        .await;
+        assert!(matches!(out_of_range, Err(ApiError::BadRequest(_))));
         let JsonResponse(init) = init_upload(
-            Extension(storage.clone()),
+            Extension(volumes.clone()),
             Extension(upload.clone()),
             Json(UploadInitRequest {
                 name: "file.bin".to_string(),
                 total_size: 3,
+                last_modified: Some(1_600_000_000_250),
+                on_conflict: ConflictPolicy::Overwrite,
             }),
         )
         .await
@@ -594,28 +1468,47 @@ mod tests {
                 upload_id: init.upload_id.clone(),
             }),
             headers,
-            Extension(storage.clone()),
+            Extension(volumes.clone()),
             Extension(upload.clone()),
             AxumBody::from("abc"),
         )
         .await
         .unwrap_or_else(|_| panic!("upload chunk failed"));
 
-        complete_upload(
+        let response = complete_upload(
             HeaderMap::new(),
-            Extension(storage.clone()),
+            Extension(volumes.clone()),
             Extension(lock_manager),
             Extension(upload.clone()),
+            Extension(Arc::new(EventHub::new())),
             Json(UploadCompleteRequest {
                 upload_id: init.upload_id.clone(),
+                on_conflict: None,
             }),
         )
         .await
         .unwrap_or_else(|_| panic!("complete upload failed"));
 
-        let file_path = storage.root_path().join("file.bin");
-        let contents = fs::read(file_path).await.expect("read file");
+        let file_path = temp.path().join("storage/file.bin");
+        let contents = fs::read(&file_path).await.expect("read file");
         assert_eq!(contents, b"abc");
+        let modified = fs::metadata(&file_path)
+            .await
+            .expect("metadata")
+            .modified()
+            .expect("modified");
+        assert_eq!(
+            modified,
+            UNIX_EPOCH + Duration::from_millis(1_600_000_000_250)
+        );
+        assert_eq!(
+            response.headers()[header::ETAG],
+            "\"3-1600000000-250000000\""
+        );
+        assert_eq!(
+            response.headers()[header::LAST_MODIFIED],
+            "Sun, 13 Sep 2020 12:26:40 GMT"
+        );
 
         let temp_root = temp.path().join(UPLOAD_TEMP_DIR);
         let temp_dir = temp_root.join(init.upload_id);
@@ -624,4 +1517,247 @@ mod tests {
             "upload temp dir should be removed"
         );
     }
+
+    #[tokio::test]
+    async fn named_volume_upload_keeps_temp_inside_volume() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("media");
+        std::fs::create_dir_all(&root).expect("create volume root");
+        let volumes = Arc::new(
+            Volumes::from_specs(&[format!("media={}", root.display())], Storage::new)
+                .expect("volumes"),
+        );
+        let upload = make_upload_config();
+        let JsonResponse(init) = init_upload(
+            Extension(volumes.clone()),
+            Extension(upload.clone()),
+            Json(UploadInitRequest {
+                name: "media/clip.bin".to_string(),
+                total_size: 3,
+                last_modified: None,
+                on_conflict: ConflictPolicy::Overwrite,
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("init upload failed"));
+        let temp_dir = root.join(VOLUME_UPLOAD_TEMP_DIR).join(&init.upload_id);
+        assert!(fs::metadata(&temp_dir).await.is_ok());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Chunk-Index", HeaderValue::from_static("0"));
+        upload_chunk(
+            Query(UploadChunkQuery {
+                upload_id: init.upload_id.clone(),
+            }),
+            headers,
+            Extension(volumes.clone()),
+            Extension(upload.clone()),
+            AxumBody::from("abc"),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("upload chunk failed"));
+        complete_upload(
+            HeaderMap::new(),
+            Extension(volumes.clone()),
+            Extension(Arc::new(LockManager::new())),
+            Extension(upload),
+            Extension(Arc::new(EventHub::new())),
+            Json(UploadCompleteRequest {
+                upload_id: init.upload_id,
+                on_conflict: None,
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("complete upload failed"));
+
+        assert_eq!(fs::read(root.join("clip.bin")).await.expect("read"), b"abc");
+        assert!(fs::metadata(&temp_dir).await.is_err());
+        let entries = volumes.list_dir(Some("media")).await.expect("list");
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn batch_upload_creates_tree_and_completes_all_files() {
+        let (temp, volumes) = make_volumes();
+        let upload = make_upload_config();
+        let hub = Arc::new(EventHub::new());
+        let manifest = |files: Vec<(&str, u64)>| BatchInitRequest {
+            target: "album".to_string(),
+            files: files
+                .into_iter()
+                .map(|(path, size)| BatchFile {
+                    path: path.to_string(),
+                    size,
+                    last_modified: None,
+                })
+                .collect(),
+            directories: vec!["empty/inner".to_string()],
+            on_conflict: ConflictPolicy::Overwrite,
+        };
+
+        let rejected = init_batch_upload(
+            Extension(volumes.clone()),
+            Extension(upload.clone()),
+            Extension(hub.clone()),
+            Json(manifest(vec![("a.txt", 1), ("../escape.txt", 1)])),
+        )
+        .await;
+        assert!(matches!(rejected, Err(ApiError::BadRequest(_))));
+        assert!(
+            fs::metadata(temp.path().join("storage/album"))
+                .await
+                .is_err()
+        );
+
+        let JsonResponse(init) = init_batch_upload(
+            Extension(volumes.clone()),
+            Extension(upload.clone()),
+            Extension(hub.clone()),
+            Json(manifest(vec![("a.txt", 3), ("sub/deep/b.txt", 2)])),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("init batch failed"));
+        assert_eq!(init.uploads.len(), 2);
+        assert!(temp.path().join("storage/album/sub/deep").is_dir());
+        assert!(temp.path().join("storage/album/empty/inner").is_dir());
+        assert_eq!(
+            count_upload_temp_dirs(&volumes)
+                .await
+                .unwrap_or_else(|_| panic!("count failed")),
+            1
+        );
+
+        for (entry, body) in init.uploads.iter().zip(["abc", "de"]) {
+            let mut headers = HeaderMap::new();
+            headers.insert("X-Chunk-Index", HeaderValue::from_static("0"));
+            upload_chunk(
+                Query(UploadChunkQuery {
+                    upload_id: entry.upload_id.clone(),
+                }),
+                headers,
+                Extension(volumes.clone()),
+                Extension(upload.clone()),
+                AxumBody::from(body),
+            )
+            .await
+            .unwrap_or_else(|_| panic!("upload chunk failed"));
+        }
+        // 批次成员只能随批次一起完成。
+        let member = complete_upload(
+            HeaderMap::new(),
+            Extension(volumes.clone()),
+            Extension(Arc::new(LockManager::new())),
+            Extension(upload.clone()),
+            Extension(hub.clone()),
+            Json(UploadCompleteRequest {
+                upload_id: init.uploads[0].upload_id.clone(),
+                on_conflict: None,
+            }),
+        )
+        .await;
+        assert!(matches!(member, Err(ApiError::Conflict(_))));
+        assert!(
+            fs::metadata(temp.path().join("storage/album/a.txt"))
+                .await
+                .is_err()
+        );
+
+        let response = complete_batch_upload(
+            Extension(volumes.clone()),
+            Extension(Arc::new(LockManager::new())),
+            Extension(upload),
+            Extension(hub),
+            Json(BatchCompleteRequest {
+                batch_id: init.batch_id.clone(),
+                on_conflict: None,
+            }),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("complete batch failed"));
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let root = temp.path().join("storage/album");
+        assert_eq!(fs::read(root.join("a.txt")).await.expect("read"), b"abc");
+        assert_eq!(
+            fs::read(root.join("sub/deep/b.txt")).await.expect("read"),
+            b"de"
+        );
+        let batch_dir = temp.path().join(UPLOAD_TEMP_DIR).join(&init.batch_id);
+        assert!(fs::metadata(&batch_dir).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn form_upload_streams_files_with_conflict_rename() {
+        use axum::extract::FromRequest;
+        use axum::http::Request;
+
+        let (temp, volumes) = make_volumes();
+        let root = temp.path().join("storage");
+        std::fs::create_dir_all(root.join("inbox")).expect("create inbox");
+        std::fs::write(root.join("inbox/a.txt"), "old").expect("write existing");
+        let form = |limit: u64| {
+            let volumes = volumes.clone();
+            async move {
+                let body = "--XX\r\n\
+                Content-Disposition: form-data; name=\"onConflict\"\r\n\r\n\
+                rename\r\n\
+                --XX\r\n\
+                Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+                Content-Type: text/plain\r\n\r\n\
+                hello\r\n\
+                --XX\r\n\
+                Content-Disposition: form-data; name=\"file\"; filename=\"../b.txt\"\r\n\r\n\
+                world\r\n\
+                --XX--\r\n";
+                let request = Request::builder()
+                    .header(header::CONTENT_TYPE, "multipart/form-data; boundary=XX")
+                    .body(AxumBody::from(body))
+                    .expect("request");
+                let multipart = Multipart::from_request(request, &())
+                    .await
+                    .unwrap_or_else(|_| panic!("multipart"));
+                let mut config = make_upload_config();
+                Arc::get_mut(&mut config).expect("config").max_total_size = limit;
+                upload_form(
+                    Query(UploadFormQuery {
+                        path: "inbox".to_string(),
+                        on_conflict: ConflictPolicy::Overwrite,
+                    }),
+                    HeaderMap::new(),
+                    Extension(volumes.clone()),
+                    Extension(Arc::new(LockManager::new())),
+                    Extension(config),
+                    Extension(Arc::new(EventHub::new())),
+                    multipart,
+                )
+                .await
+            }
+        };
+
+        let response = form(0)
+            .await
+            .unwrap_or_else(|_| panic!("form upload failed"));
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        let body: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(body["files"][0]["path"], "inbox/a (1).txt");
+        assert_eq!(body["files"][1]["path"], "inbox/b.txt");
+        assert_eq!(
+            std::fs::read(root.join("inbox/a.txt")).expect("read"),
+            b"old"
+        );
+        assert_eq!(
+            std::fs::read(root.join("inbox/a (1).txt")).expect("read"),
+            b"hello"
+        );
+        assert_eq!(
+            std::fs::read(root.join("inbox/b.txt")).expect("read"),
+            b"world"
+        );
+
+        assert!(matches!(form(8).await, Err(ApiError::BadRequest(_))));
+        assert!(!root.join("inbox/b (1).txt").exists());
+    }
 }
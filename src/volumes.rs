@@ -0,0 +1,451 @@
+//! 多存储卷：在同一服务下挂载多个命名的存储根目录。
+//!
+//! 未配置命名卷时只有一个匿名卷，请求路径原样交给其存储；配置后路径
+//! 首段为卷名，各卷作为顶层目录出现在文件列表与 WebDAV 中，并各自
+//! 拥有存储实例、容量配额与只读标记。
+
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tokio::fs;
+use tokio::sync::Mutex;
+
+use crate::atomic::is_temp_name;
+use crate::config::{VOLUME_UPLOAD_TEMP_DIR, VOLUME_USAGE_CACHE_SECS};
+use crate::storage::{FileEntry, Storage, StorageError, format_timestamp};
+
+/// 单个存储卷。
+#[derive(Debug)]
+pub struct Volume {
+    name: String,
+    storage: Arc<Storage>,
+    quota: Option<u64>,
+    read_only: bool,
+    usage: Mutex<Option<CachedUsage>>,
+}
+
+/// 缓存的卷占用字节数及其统计时间。
+#[derive(Debug)]
+struct CachedUsage {
+    bytes: u64,
+    scanned: Instant,
+}
+
+impl Volume {
+    /// 创建无配额、可写的存储卷。
+    pub fn new(name: impl Into<String>, storage: Arc<Storage>) -> Self {
+        Self {
+            name: name.into(),
+            storage,
+            quota: None,
+            read_only: false,
+            usage: Mutex::new(None),
+        }
+    }
+
+    /// 设置容量配额（字节，None 表示不限制）。
+    pub fn with_quota(mut self, quota: Option<u64>) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// 设置是否只读。
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// 返回卷名（匿名卷为空字符串）。
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 返回卷的存储实例。
+    pub fn storage(&self) -> &Arc<Storage> {
+        &self.storage
+    }
+
+    /// 返回容量配额。
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+    }
+
+    /// 是否只读。
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// 返回卷内文件占用的磁盘字节数（不含上传临时目录与原子写入临时
+    /// 文件），缓存过期时重新统计。
+    pub async fn used_bytes(&self) -> io::Result<u64> {
+        let mut usage = self.usage.lock().await;
+        self.cached_usage(&mut usage).await
+    }
+
+    async fn cached_usage(&self, usage: &mut Option<CachedUsage>) -> io::Result<u64> {
+        if let Some(cached) = usage
+            && cached.scanned.elapsed() < Duration::from_secs(VOLUME_USAGE_CACHE_SECS)
+        {
+            return Ok(cached.bytes);
+        }
+        let root = self.storage.root_path().to_path_buf();
+        let skip = self.storage.temp_dir().map(Path::to_path_buf);
+        let bytes = tokio::task::spawn_blocking(move || directory_size(&root, skip.as_deref()))
+            .await
+            .map_err(io::Error::other)??;
+        *usage = Some(CachedUsage {
+            bytes,
+            scanned: Instant::now(),
+        });
+        Ok(bytes)
+    }
+
+    /// 校验写入后占用不超过配额：`added` 为尚未落盘的新增字节，
+    /// `released` 为将被替换释放的字节。
+    pub async fn check_quota(&self, added: u64, released: u64) -> Result<(), StorageError> {
+        if self.quota.is_none() {
+            return Ok(());
+        }
+        let mut usage = self.usage.lock().await;
+        self.quota_after(&mut usage, added, released).await?;
+        Ok(())
+    }
+
+    /// 在锁内校验配额并将写入增量计入占用缓存，用于即将落盘的写入，
+    /// 保证并发写入不会共同超出配额。
+    pub async fn charge_quota(&self, added: u64, released: u64) -> Result<(), StorageError> {
+        if self.quota.is_none() {
+            return Ok(());
+        }
+        let mut usage = self.usage.lock().await;
+        let used = self.quota_after(&mut usage, added, released).await?;
+        if let Some(cached) = usage.as_mut() {
+            cached.bytes = used;
+        }
+        Ok(())
+    }
+
+    /// 返回写入后的占用，超出配额时返回错误。
+    async fn quota_after(
+        &self,
+        usage: &mut Option<CachedUsage>,
+        added: u64,
+        released: u64,
+    ) -> Result<u64, StorageError> {
+        let used = self
+            .cached_usage(usage)
+            .await?
+            .saturating_add(added)
+            .saturating_sub(released);
+        if self.quota.is_some_and(|quota| used > quota) {
+            return Err(StorageError::QuotaExceeded);
+        }
+        Ok(used)
+    }
+}
+
+fn directory_size(root: &Path, skip: Option<&Path>) -> io::Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                let path = entry.path();
+                if skip != Some(path.as_path()) {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() && !is_temp_name(&entry.file_name()) {
+                total += entry.metadata()?.len();
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// 服务挂载的全部存储卷。
+#[derive(Debug)]
+pub struct Volumes {
+    volumes: Vec<Arc<Volume>>,
+    named: bool,
+}
+
+impl Volumes {
+    /// 创建仅包含一个匿名卷的集合。
+    pub fn single(storage: Arc<Storage>) -> Self {
+        Self {
+            volumes: vec![Arc::new(Volume::new("", storage))],
+            named: false,
+        }
+    }
+
+    /// 创建命名卷集合。
+    pub fn named(volumes: Vec<Volume>) -> Self {
+        Self {
+            volumes: volumes.into_iter().map(Arc::new).collect(),
+            named: true,
+        }
+    }
+
+    /// 按 `name=path[;ro][;quota=bytes]` 格式解析命名卷，`build` 负责创建各卷的存储。
+    pub fn from_specs(specs: &[String], build: impl Fn(PathBuf) -> Storage) -> io::Result<Self> {
+        let mut volumes: Vec<Volume> = Vec::with_capacity(specs.len());
+        for spec in specs
+            .iter()
+            .map(|spec| spec.trim())
+            .filter(|s| !s.is_empty())
+        {
+            let VolumeSpec {
+                name,
+                root,
+                quota,
+                read_only,
+            } = parse_volume_spec(spec)?;
+            if volumes.iter().any(|volume| volume.name == name) {
+                return Err(invalid_spec(format!("duplicate volume name: {name}")));
+            }
+            let temp_dir = root.join(VOLUME_UPLOAD_TEMP_DIR);
+            let storage = build(root).with_temp_dir(temp_dir);
+            volumes.push(
+                Volume::new(name, Arc::new(storage))
+                    .with_quota(quota)
+                    .with_read_only(read_only),
+            );
+        }
+        Ok(Self::named(volumes))
+    }
+
+    /// 若为单一匿名卷则返回该卷。
+    pub fn single_volume(&self) -> Option<&Arc<Volume>> {
+        if self.named {
+            None
+        } else {
+            self.volumes.first()
+        }
+    }
+
+    /// 遍历全部卷。
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<Volume>> {
+        self.volumes.iter()
+    }
+
+    /// 确保全部卷的根目录存在。
+    pub async fn ensure_roots(&self) -> io::Result<()> {
+        for volume in &self.volumes {
+            volume.storage.ensure_root().await?;
+        }
+        Ok(())
+    }
+
+    /// 将请求路径解析为（卷，卷内相对路径）。
+    pub fn resolve<'a>(&self, path: &'a str) -> Result<(&Arc<Volume>, &'a str), StorageError> {
+        if let Some(volume) = self.single_volume() {
+            return Ok((volume, path));
+        }
+        let trimmed = path.trim_start_matches(['/', '\\']);
+        let (name, rest) = trimmed.split_once(['/', '\\']).unwrap_or((trimmed, ""));
+        if name.is_empty() {
+            return Err(StorageError::InvalidPath);
+        }
+        let volume = self
+            .volumes
+            .iter()
+            .find(|volume| volume.name == name)
+            .ok_or_else(|| {
+                StorageError::Io(io::Error::new(ErrorKind::NotFound, "volume not found"))
+            })?;
+        Ok((volume, rest))
+    }
+
+    /// 解析用于修改的路径：拒绝只读卷与卷根目录本身。
+    pub fn resolve_writable<'a>(
+        &self,
+        path: &'a str,
+    ) -> Result<(&Arc<Volume>, &'a str), StorageError> {
+        let (volume, relative) = self.resolve(path)?;
+        if volume.read_only {
+            return Err(StorageError::ReadOnly);
+        }
+        if self.named && relative.trim_matches(['/', '\\']).is_empty() {
+            return Err(StorageError::InvalidPath);
+        }
+        Ok((volume, relative))
+    }
+
+    /// 列出目录；命名卷模式下根目录列出各卷。
+    pub async fn list_dir(&self, path: Option<&str>) -> Result<Vec<FileEntry>, StorageError> {
+        if let Some(volume) = self.single_volume() {
+            return volume.storage.list_dir(path).await;
+        }
+        let path = path.unwrap_or_default();
+        if path.trim_matches(['/', '\\']).is_empty() {
+            let mut entries = Vec::with_capacity(self.volumes.len());
+            for volume in &self.volumes {
+                let modified = fs::metadata(volume.storage.root_path())
+                    .await
+                    .ok()
+                    .and_then(|metadata| metadata.modified().ok())
+                    .and_then(|ts| ts.duration_since(UNIX_EPOCH).ok())
+                    .and_then(format_timestamp);
+                entries.push(FileEntry {
+                    name: volume.name.clone(),
+                    path: volume.name.clone(),
+                    is_dir: true,
+                    is_symlink: false,
+                    size: 0,
+                    modified,
+                    etag: None,
+                    captured: None,
+                    details: None,
+                });
+            }
+            return Ok(entries);
+        }
+        let (volume, relative) = self.resolve(path)?;
+        let mut entries = volume.storage.list_dir(Some(relative)).await?;
+        for entry in &mut entries {
+            entry.path = self.public_path(volume, &entry.path);
+        }
+        Ok(entries)
+    }
+
+    /// 将卷内相对路径转换为对外路径：命名卷模式下加上卷名前缀。
+    pub fn public_path(&self, volume: &Volume, relative: &str) -> String {
+        if !self.named {
+            return relative.to_string();
+        }
+        if relative.is_empty() {
+            volume.name.clone()
+        } else {
+            format!("{}/{}", volume.name, relative)
+        }
+    }
+}
+
+struct VolumeSpec {
+    name: String,
+    root: PathBuf,
+    quota: Option<u64>,
+    read_only: bool,
+}
+
+fn parse_volume_spec(spec: &str) -> io::Result<VolumeSpec> {
+    let mut parts = spec.split(';').map(str::trim);
+    let location = parts.next().unwrap_or_default();
+    let (name, root) = location
+        .split_once('=')
+        .ok_or_else(|| invalid_spec(format!("invalid volume spec: {spec}")))?;
+    let name = name.trim();
+    let root = root.trim();
+    let valid_name = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'));
+    if !valid_name || root.is_empty() {
+        return Err(invalid_spec(format!("invalid volume spec: {spec}")));
+    }
+
+    let mut quota = None;
+    let mut read_only = false;
+    for option in parts.filter(|option| !option.is_empty()) {
+        match option.split_once('=') {
+            None if option == "ro" => read_only = true,
+            None if option == "rw" => read_only = false,
+            Some(("quota", value)) => {
+                let value: u64 = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid_spec(format!("invalid volume quota: {spec}")))?;
+                quota = (value > 0).then_some(value);
+            }
+            _ => return Err(invalid_spec(format!("unknown volume option: {option}"))),
+        }
+    }
+
+    Ok(VolumeSpec {
+        name: name.to_string(),
+        root: PathBuf::from(root),
+        quota,
+        read_only,
+    })
+}
+
+fn invalid_spec(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn resolve_routes_by_volume_name() {
+        let specs = vec![
+            "projects=/srv/p".to_string(),
+            "media=/mnt/media;ro;quota=1024".to_string(),
+        ];
+        let volumes = Volumes::from_specs(&specs, Storage::new).expect("volumes");
+
+        let (volume, relative) = volumes.resolve("/media/photos/a.jpg").expect("resolve");
+        assert_eq!(volume.name(), "media");
+        assert_eq!(relative, "photos/a.jpg");
+        assert_eq!(volume.quota(), Some(1024));
+        assert!(matches!(
+            volumes.resolve_writable("media/photos/a.jpg"),
+            Err(StorageError::ReadOnly)
+        ));
+        assert!(matches!(
+            volumes.resolve_writable("projects"),
+            Err(StorageError::InvalidPath)
+        ));
+        assert!(matches!(
+            volumes.resolve("missing/a.txt"),
+            Err(StorageError::Io(err)) if err.kind() == ErrorKind::NotFound
+        ));
+        assert!(Volumes::from_specs(&["a=/x".into(), "a=/y".into()], Storage::new).is_err());
+        assert!(Volumes::from_specs(&["../x=/x".into()], Storage::new).is_err());
+    }
+
+    #[tokio::test]
+    async fn quota_counts_stored_bytes_and_skips_upload_temp() {
+        let temp = tempdir().expect("tempdir");
+        let root = temp.path().join("vol");
+        std::fs::create_dir_all(root.join("docs")).expect("create docs");
+        std::fs::create_dir_all(root.join(VOLUME_UPLOAD_TEMP_DIR)).expect("create temp");
+        std::fs::write(root.join("docs/a.txt"), vec![0u8; 600]).expect("write a");
+        std::fs::write(
+            root.join(VOLUME_UPLOAD_TEMP_DIR).join("x.part"),
+            vec![0u8; 900],
+        )
+        .expect("write part");
+        let atomic_temp = format!(".b.txt.tmp.{}", uuid::Uuid::new_v4());
+        std::fs::write(root.join("docs").join(atomic_temp), vec![0u8; 500]).expect("write temp");
+
+        let storage = Storage::new(root.clone()).with_temp_dir(root.join(VOLUME_UPLOAD_TEMP_DIR));
+        let volume = Volume::new("vol", Arc::new(storage)).with_quota(Some(1000));
+        assert_eq!(volume.used_bytes().await.expect("used"), 600);
+        assert!(volume.check_quota(400, 0).await.is_ok());
+        assert!(matches!(
+            volume.check_quota(401, 0).await,
+            Err(StorageError::QuotaExceeded)
+        ));
+        assert!(volume.check_quota(1000, 600).await.is_ok());
+
+        // 已计入的写入在缓存过期前无需重新统计即可生效。
+        assert!(volume.charge_quota(300, 0).await.is_ok());
+        assert_eq!(volume.used_bytes().await.expect("used"), 900);
+        assert!(matches!(
+            volume.charge_quota(200, 0).await,
+            Err(StorageError::QuotaExceeded)
+        ));
+    }
+}
@@ -1,15 +1,106 @@
 //! WebDAV 请求处理封装。
 
 use axum::extract::Extension;
-use axum::http::Request;
+use axum::http::{Method, Request, StatusCode, Uri};
 use axum::response::Response;
+use dav_server::davpath::DavPath;
 use dav_server::{DavHandler, body::Body as DavBody};
 use std::sync::Arc;
 
+use crate::events::{ChangeEvent, ChangeKind, EventHub, normalize_path};
+use crate::volumes::Volumes;
+
 /// 代理 WebDAV 请求到 dav-server 处理器。
 pub async fn webdav_handler(
     Extension(dav_handler): Extension<Arc<DavHandler>>,
+    Extension(volumes): Extension<Arc<Volumes>>,
+    Extension(hub): Extension<Arc<EventHub>>,
     req: Request<axum::body::Body>,
 ) -> Response<DavBody> {
-    dav_handler.handle(req).await
+    // dav-server 删除目录时会先逐个删除子项，需在此拦截对挂载根目录的删除与移动。
+    let method = req.method();
+    if (method == Method::DELETE || method.as_str() == "MOVE")
+        && is_mount_root(&volumes, req.uri().path())
+    {
+        let mut response = Response::new(DavBody::from("volume root cannot be modified"));
+        *response.status_mut() = StatusCode::FORBIDDEN;
+        return response;
+    }
+    let method = method.clone();
+    let path = storage_path(req.uri().path());
+    let destination = req
+        .headers()
+        .get("Destination")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Uri>().ok())
+        .and_then(|uri| storage_path(uri.path()));
+    // 目录删除后无法再判断类型，需在执行前记录。
+    let deleted_dir = match (&method, &path) {
+        (&Method::DELETE, Some(path)) => is_dir(&volumes, path).await,
+        _ => false,
+    };
+    let response = dav_handler.handle(req).await;
+    if response.status().is_success()
+        && let Some(path) = path
+        && let Ok((volume, _)) = volumes.resolve(&path)
+    {
+        let created = response.status() == StatusCode::CREATED;
+        let change = match (method.as_str(), destination) {
+            ("PUT", _) if created => Some(ChangeEvent::new(ChangeKind::Created, path, false)),
+            ("PUT", _) => Some(ChangeEvent::new(ChangeKind::Modified, path, false)),
+            ("MKCOL", _) => Some(ChangeEvent::new(ChangeKind::Created, path, true)),
+            ("DELETE", _) => Some(ChangeEvent::new(ChangeKind::Deleted, path, deleted_dir)),
+            ("MOVE", Some(to)) => {
+                let is_dir = is_dir(&volumes, &to).await;
+                Some(ChangeEvent::moved(path, to, is_dir))
+            }
+            ("COPY", Some(to)) => {
+                let is_dir = is_dir(&volumes, &to).await;
+                let kind = if created {
+                    ChangeKind::Created
+                } else {
+                    ChangeKind::Modified
+                };
+                Some(ChangeEvent::new(kind, to, is_dir))
+            }
+            _ => None,
+        };
+        if let Some(change) = change {
+            hub.record_local(volume, change);
+        }
+    }
+    response
+}
+
+/// 将 WebDAV 请求路径转换为与文件 API 一致的对外路径。
+fn storage_path(uri_path: &str) -> Option<String> {
+    let relative = uri_path.strip_prefix("/webdav")?;
+    let path = DavPath::new(if relative.is_empty() { "/" } else { relative }).ok()?;
+    Some(normalize_path(&path.as_rel_ospath().to_string_lossy()))
+}
+
+async fn is_dir(volumes: &Volumes, path: &str) -> bool {
+    let Ok((volume, relative)) = volumes.resolve(path) else {
+        return false;
+    };
+    match volume.storage().resolve_path_checked(relative, false).await {
+        Ok(target) => target
+            .metadata()
+            .await
+            .is_ok_and(|metadata| metadata.is_dir()),
+        Err(_) => false,
+    }
+}
+
+/// 判断请求路径是否为 WebDAV 根目录或命名卷的根目录。
+fn is_mount_root(volumes: &Volumes, uri_path: &str) -> bool {
+    let relative = uri_path.strip_prefix("/webdav").unwrap_or(uri_path);
+    let Ok(path) = DavPath::new(if relative.is_empty() { "/" } else { relative }) else {
+        return false;
+    };
+    let depth = path.as_rel_ospath().components().count();
+    match volumes.single_volume() {
+        Some(_) => depth == 0,
+        None => depth <= 1,
+    }
 }